@@ -1,7 +1,91 @@
+use std::cell::Cell;
+
 use pgrx::pg_sys;
 
-fn clamp_row_estimate(estimate: i64) -> f64 {
-    estimate.max(0) as f64
+use super::{
+    PGL_CARDINALITY_BLEND, PGL_CARDINALITY_CLAMP_TO_CARTESIAN, PGL_CARDINALITY_MAX_RATIO,
+    PGL_CARDINALITY_MIN_ROWS,
+};
+
+/// Backend-local counts of how remote estimates fared against the guards.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GuardStats {
+    pub accepted: i64,
+    pub clamped: i64,
+    pub rejected: i64,
+}
+
+thread_local! {
+    static GUARD_STATS: Cell<GuardStats> = const {
+        Cell::new(GuardStats {
+            accepted: 0,
+            clamped: 0,
+            rejected: 0,
+        })
+    };
+}
+
+pub fn guard_stats() -> GuardStats {
+    GUARD_STATS.with(Cell::get)
+}
+
+pub fn reset_guard_stats() {
+    GUARD_STATS.with(|stats| stats.set(GuardStats::default()));
+}
+
+fn count(update: impl FnOnce(&mut GuardStats)) {
+    GUARD_STATS.with(|stats| {
+        let mut current = stats.get();
+        update(&mut current);
+        stats.set(current);
+    });
+}
+
+/// Checks a learned or recorded estimate against `native`, PostgreSQL's own
+/// estimate, and `max_rows`, the largest row count the relation can produce.
+/// Returns the rows to apply, or `None` when the estimate is rejected.
+pub fn guard_estimate(estimate: f64, native: f64, max_rows: Option<f64>) -> Option<f64> {
+    if !estimate.is_finite() {
+        count(|stats| stats.rejected += 1);
+        pgrx::log!("pgl: rejected cardinality estimate {estimate} (not a finite number)");
+        return None;
+    }
+
+    let min_rows = PGL_CARDINALITY_MIN_ROWS.get();
+
+    let mut rows = estimate.max(min_rows);
+    if let Some(max_rows) = max_rows.filter(|max_rows| *max_rows >= min_rows) {
+        if PGL_CARDINALITY_CLAMP_TO_CARTESIAN.get() {
+            rows = rows.min(max_rows);
+        }
+    }
+
+    let max_ratio = PGL_CARDINALITY_MAX_RATIO.get();
+    if max_ratio > 0.0 && native > 0.0 {
+        let ratio = (rows / native).max(native / rows.max(f64::MIN_POSITIVE));
+        if ratio > max_ratio {
+            count(|stats| stats.rejected += 1);
+            pgrx::log!(
+                "pgl: rejected cardinality estimate {estimate} (PostgreSQL estimated {native}, ratio {ratio:.1} exceeds pgl.cardinality_max_ratio {max_ratio})"
+            );
+            return None;
+        }
+    }
+
+    if rows == estimate {
+        count(|stats| stats.accepted += 1);
+    } else {
+        count(|stats| stats.clamped += 1);
+    }
+
+    // Geometric blend: a weight of 0.5 meets both estimates halfway in orders
+    // of magnitude, which suits errors that are multiplicative.
+    let blend = PGL_CARDINALITY_BLEND.get();
+    if blend < 1.0 && rows > 0.0 && native > 0.0 {
+        rows = (blend * rows.ln() + (1.0 - blend) * native.ln()).exp();
+    }
+
+    Some(rows)
 }
 
 unsafe fn apply_estimate_to_path(path: *mut pg_sys::Path, estimate: f64) {
@@ -30,13 +114,11 @@ unsafe fn apply_estimate_to_pathlist(pathlist: *mut pg_sys::List, estimate: f64)
     }
 }
 
-pub unsafe fn apply_estimate_to_rel(rel: *mut pg_sys::RelOptInfo, estimate: i64) {
+pub unsafe fn apply_rows_to_rel(rel: *mut pg_sys::RelOptInfo, estimate: f64) {
     if rel.is_null() {
         return;
     }
 
-    let estimate = clamp_row_estimate(estimate);
-
     (*rel).rows = estimate;
     apply_estimate_to_pathlist((*rel).pathlist, estimate);
     apply_estimate_to_pathlist((*rel).partial_pathlist, estimate);
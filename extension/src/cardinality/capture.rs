@@ -0,0 +1,59 @@
+use std::cell::{Cell, RefCell};
+
+use super::payload::RelationEstimatePayload;
+
+thread_local! {
+    static CAPTURED_PAYLOADS: RefCell<Option<Vec<RelationEstimatePayload>>> =
+        const { RefCell::new(None) };
+    static CAPTURE_FROZEN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Resets the capture buffer when dropped, so an error raised while planning
+/// never leaves capturing switched on for later queries.
+struct CaptureGuard;
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        CAPTURED_PAYLOADS.with(|captured| captured.borrow_mut().take());
+        CAPTURE_FROZEN.with(|frozen| frozen.set(false));
+    }
+}
+
+pub fn is_active() -> bool {
+    !CAPTURE_FROZEN.with(Cell::get)
+        && CAPTURED_PAYLOADS.with(|captured| captured.borrow().is_some())
+}
+
+/// Stops recording for the rest of the current capture. Called once the
+/// captured statement starts executing, so statements planned by functions it
+/// calls do not mix into its payloads.
+pub fn freeze() {
+    CAPTURE_FROZEN.with(|frozen| frozen.set(true));
+}
+
+pub fn record(payload: &RelationEstimatePayload) {
+    if CAPTURE_FROZEN.with(Cell::get) {
+        return;
+    }
+
+    CAPTURED_PAYLOADS.with(|captured| {
+        if let Some(payloads) = captured.borrow_mut().as_mut() {
+            payloads.push(payload.clone());
+        }
+    });
+}
+
+/// Runs `f` while recording every payload built by the pathlist hooks.
+pub fn with_capture<R>(f: impl FnOnce() -> R) -> (R, Vec<RelationEstimatePayload>) {
+    CAPTURED_PAYLOADS.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+    CAPTURE_FROZEN.with(|frozen| frozen.set(false));
+    let guard = CaptureGuard;
+
+    let result = f();
+    let payloads = CAPTURED_PAYLOADS
+        .with(|captured| captured.borrow_mut().take())
+        .unwrap_or_default();
+
+    drop(guard);
+    (result, payloads)
+}
@@ -5,10 +5,12 @@ use pgrx::pg_sys;
 
 use crate::utils::{bitmapset_members, cstr_to_string};
 
+use super::partition::partition_info;
 use super::payload::{
-    EstimateKind, FilterPredicate, JoinPredicate, RelationEstimatePayload, RelationRef,
-    TypedLiteral, CURRENT_PAYLOAD_VERSION,
+    EstimateKind, FilterPredicate, JoinPredicate, JoinSemantics, JoinSide, RelationEstimatePayload,
+    RelationRef, TypedLiteral, CURRENT_PAYLOAD_VERSION,
 };
+use super::state_key::relation_subset_key;
 
 const UNSUPPORTED_FILTER_SHAPE: &str = "unsupported_filter_shape";
 const UNSUPPORTED_JOIN_SHAPE: &str = "unsupported_join_shape";
@@ -16,7 +18,7 @@ const UNSUPPORTED_JOIN_TYPE: &str = "unsupported_join_type";
 const UNSUPPORTED_LITERAL_TYPE: &str = "unsupported_literal_type";
 const UNSUPPORTED_WRAPPER: &str = "unsupported_wrapper";
 
-unsafe fn relation_name(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
+pub(super) unsafe fn relation_name(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
     if rte.is_null() {
         return None;
     }
@@ -31,7 +33,7 @@ unsafe fn relation_name(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
     cstr_to_string((*(*rte).eref).aliasname)
 }
 
-unsafe fn alias_name(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
+pub(super) unsafe fn alias_name(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
     if rte.is_null() || (*rte).eref.is_null() {
         return None;
     }
@@ -39,7 +41,7 @@ unsafe fn alias_name(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
     cstr_to_string((*(*rte).eref).aliasname)
 }
 
-unsafe fn relation_schema(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
+pub(super) unsafe fn relation_schema(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
     if rte.is_null() || (*rte).relid == pg_sys::InvalidOid {
         return None;
     }
@@ -74,6 +76,22 @@ unsafe fn current_database_name() -> Option<String> {
     Some(owned)
 }
 
+/// The query id of the statement being planned. Subqueries are planned with
+/// their own `Query`, which carries no id, so the top-level one is used.
+pub(super) unsafe fn query_hash(root: *mut pg_sys::PlannerInfo) -> Option<i64> {
+    let mut top = root;
+    while !top.is_null() && !(*top).parent_root.is_null() {
+        top = (*top).parent_root;
+    }
+
+    if top.is_null() || (*top).parse.is_null() {
+        return None;
+    }
+
+    let query_id = (*(*top).parse).queryId;
+    (query_id != 0).then_some(query_id as i64)
+}
+
 unsafe fn relation_ref(rt_index: u32, rte: *mut pg_sys::RangeTblEntry) -> Option<RelationRef> {
     Some(RelationRef {
         rt_index,
@@ -178,7 +196,7 @@ unsafe fn relation_refs_from_relids(
     relations
 }
 
-unsafe fn operator_name(opno: pg_sys::Oid) -> Option<String> {
+pub(super) unsafe fn operator_name(opno: pg_sys::Oid) -> Option<String> {
     let name = pg_sys::get_opname(opno);
     if name.is_null() {
         return None;
@@ -270,7 +288,7 @@ unsafe fn binary_op_args(
     Some((left, right))
 }
 
-unsafe fn clause_string(expr: *mut pg_sys::Expr) -> Option<String> {
+pub(super) unsafe fn clause_string(expr: *mut pg_sys::Expr) -> Option<String> {
     let raw = pg_sys::nodeToString(expr.cast());
     if raw.is_null() {
         return None;
@@ -281,6 +299,228 @@ unsafe fn clause_string(expr: *mut pg_sys::Expr) -> Option<String> {
     Some(owned)
 }
 
+fn join_predicate(
+    clause: String,
+    operator: Option<String>,
+    operator_oid: Option<u32>,
+    (left_var, left_expression): (VarDescriptor, Option<String>),
+    (right_var, right_expression): (VarDescriptor, Option<String>),
+) -> JoinPredicate {
+    JoinPredicate {
+        clause,
+        left_relation: left_var.relation,
+        right_relation: right_var.relation,
+        left_schema: left_var.schema,
+        left_table_name: left_var.table_name,
+        left_alias: left_var.alias,
+        left_column_name: left_var.column_name,
+        left_attribute_number: left_var.attribute_number,
+        left_expression,
+        right_schema: right_var.schema,
+        right_table_name: right_var.table_name,
+        right_alias: right_var.alias,
+        right_column_name: right_var.column_name,
+        right_attribute_number: right_var.attribute_number,
+        right_expression,
+        operator,
+        operator_oid,
+        pushed_down: false,
+    }
+}
+
+unsafe fn function_name(funcid: pg_sys::Oid) -> Option<String> {
+    let name = pg_sys::get_func_name(funcid);
+    if name.is_null() {
+        return None;
+    }
+
+    let owned = CStr::from_ptr(name).to_string_lossy().into_owned();
+    pg_sys::pfree(name.cast());
+    Some(owned)
+}
+
+pub(super) unsafe fn list_exprs(list: *mut pg_sys::List) -> Vec<*mut pg_sys::Expr> {
+    if list.is_null() {
+        return Vec::new();
+    }
+
+    let len = (*list).length.max(0) as usize;
+    let elements = (*list).elements;
+    (0..len)
+        .map(|idx| (*elements.add(idx)).ptr_value as *mut pg_sys::Expr)
+        .collect()
+}
+
+/// Renders a scalar expression over columns as readable SQL-like text,
+/// collecting the `Var`s it references. Returns `None` for node types the
+/// payload does not describe.
+pub(super) unsafe fn render_expr(
+    root: *mut pg_sys::PlannerInfo,
+    expr: *mut pg_sys::Expr,
+    fallback_rte: *mut pg_sys::RangeTblEntry,
+    vars: &mut Vec<*mut pg_sys::Var>,
+) -> Option<String> {
+    if expr.is_null() {
+        return None;
+    }
+
+    match (*(expr as *mut pg_sys::Node)).type_ {
+        pg_sys::NodeTag::T_Var => {
+            let var = expr as *mut pg_sys::Var;
+            if (*var).varlevelsup != 0 {
+                return None;
+            }
+
+            vars.push(var);
+            let descriptor = describe_var(root, var, fallback_rte);
+            let relation = descriptor
+                .alias
+                .or(descriptor.table_name)
+                .unwrap_or_else(|| descriptor.relation.to_string());
+            let column = descriptor
+                .column_name
+                .unwrap_or_else(|| (*var).varattno.to_string());
+            Some(format!("{relation}.{column}"))
+        }
+        pg_sys::NodeTag::T_Const => {
+            let literal = const_to_literal(expr as *mut pg_sys::Const)?;
+            if literal.is_null {
+                return Some("NULL".to_string());
+            }
+            Some(format!(
+                "'{}'::{}",
+                literal.value.replace('\'', "''"),
+                literal.type_name
+            ))
+        }
+        pg_sys::NodeTag::T_RelabelType => render_expr(
+            root,
+            (*(expr as *mut pg_sys::RelabelType)).arg,
+            fallback_rte,
+            vars,
+        ),
+        pg_sys::NodeTag::T_CoerceViaIO => {
+            let coerce = expr as *mut pg_sys::CoerceViaIO;
+            let arg = render_expr(root, (*coerce).arg, fallback_rte, vars)?;
+            Some(format!("{arg}::{}", type_name((*coerce).resulttype)?))
+        }
+        pg_sys::NodeTag::T_OpExpr => {
+            let op_expr = expr as *mut pg_sys::OpExpr;
+            let operator = operator_name((*op_expr).opno)?;
+            let args = list_exprs((*op_expr).args)
+                .into_iter()
+                .map(|arg| render_expr(root, arg, fallback_rte, vars))
+                .collect::<Option<Vec<_>>>()?;
+
+            match args.as_slice() {
+                [left, right] => Some(format!("({left} {operator} {right})")),
+                [arg] => Some(format!("({operator} {arg})")),
+                _ => None,
+            }
+        }
+        pg_sys::NodeTag::T_FuncExpr => {
+            let func_expr = expr as *mut pg_sys::FuncExpr;
+            let name = function_name((*func_expr).funcid)?;
+            let args = list_exprs((*func_expr).args)
+                .into_iter()
+                .map(|arg| render_expr(root, arg, fallback_rte, vars))
+                .collect::<Option<Vec<_>>>()?;
+            Some(format!("{name}({})", args.join(", ")))
+        }
+        pg_sys::NodeTag::T_BoolExpr => {
+            let bool_expr = expr as *mut pg_sys::BoolExpr;
+            let args = list_exprs((*bool_expr).args)
+                .into_iter()
+                .map(|arg| render_expr(root, arg, fallback_rte, vars))
+                .collect::<Option<Vec<_>>>()?;
+
+            match (*bool_expr).boolop {
+                pg_sys::BoolExprType::AND_EXPR => Some(format!("({})", args.join(" AND "))),
+                pg_sys::BoolExprType::OR_EXPR => Some(format!("({})", args.join(" OR "))),
+                pg_sys::BoolExprType::NOT_EXPR => Some(format!("(NOT {})", args.first()?)),
+                _ => None,
+            }
+        }
+        pg_sys::NodeTag::T_NullTest => {
+            let null_test = expr as *mut pg_sys::NullTest;
+            let arg = render_expr(root, (*null_test).arg, fallback_rte, vars)?;
+            match (*null_test).nulltesttype {
+                pg_sys::NullTestType::IS_NULL => Some(format!("({arg} IS NULL)")),
+                _ => Some(format!("({arg} IS NOT NULL)")),
+            }
+        }
+        pg_sys::NodeTag::T_ScalarArrayOpExpr => {
+            let array_expr = expr as *mut pg_sys::ScalarArrayOpExpr;
+            let operator = operator_name((*array_expr).opno)?;
+            let args = list_exprs((*array_expr).args)
+                .into_iter()
+                .map(|arg| render_expr(root, arg, fallback_rte, vars))
+                .collect::<Option<Vec<_>>>()?;
+            let quantifier = if (*array_expr).useOr { "ANY" } else { "ALL" };
+
+            match args.as_slice() {
+                [left, right] => Some(format!("({left} {operator} {quantifier} ({right}))")),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Describes one side of a join clause that may be an expression over the
+/// columns of a single relation.
+unsafe fn describe_join_side(
+    root: *mut pg_sys::PlannerInfo,
+    expr: *mut pg_sys::Expr,
+    fallback_rte: *mut pg_sys::RangeTblEntry,
+) -> Result<(VarDescriptor, Option<String>), &'static str> {
+    let mut vars = Vec::new();
+    let rendered =
+        render_expr(root, expr, fallback_rte, &mut vars).ok_or(UNSUPPORTED_FILTER_SHAPE)?;
+
+    let first = *vars.first().ok_or(UNSUPPORTED_FILTER_SHAPE)?;
+    if vars.iter().any(|var| (**var).varno != (*first).varno) {
+        return Err(UNSUPPORTED_JOIN_SHAPE);
+    }
+
+    let mut descriptor = describe_var(root, first, fallback_rte);
+    if vars.iter().any(|var| (**var).varattno != (*first).varattno) {
+        descriptor.column_name = None;
+        descriptor.attribute_number = None;
+    }
+
+    let is_bare_var = matches!(
+        unwrap_expr(expr).map(|inner| (*(inner as *mut pg_sys::Node)).type_),
+        Ok(pg_sys::NodeTag::T_Var)
+    );
+
+    Ok((descriptor, (!is_bare_var).then_some(rendered)))
+}
+
+/// Builds a join predicate for clauses such as `lower(a.x) = b.y` or
+/// `a.x + 1 < b.y` whose sides are expressions over different relations.
+unsafe fn expression_join_predicate(
+    root: *mut pg_sys::PlannerInfo,
+    op_expr: *mut pg_sys::OpExpr,
+    left: *mut pg_sys::Expr,
+    right: *mut pg_sys::Expr,
+    fallback_rte: *mut pg_sys::RangeTblEntry,
+) -> Result<JoinPredicate, &'static str> {
+    let left_side = describe_join_side(root, left, fallback_rte)?;
+    let right_side = describe_join_side(root, right, fallback_rte)?;
+    if left_side.0.relation == right_side.0.relation {
+        return Err(UNSUPPORTED_FILTER_SHAPE);
+    }
+
+    Ok(join_predicate(
+        clause_string(op_expr as *mut pg_sys::Expr).ok_or(UNSUPPORTED_FILTER_SHAPE)?,
+        operator_name((*op_expr).opno),
+        ((*op_expr).opno != pg_sys::InvalidOid).then_some(u32::from((*op_expr).opno)),
+        left_side,
+        right_side,
+    ))
+}
+
 unsafe fn classify_clause(
     root: *mut pg_sys::PlannerInfo,
     clause: *mut pg_sys::Expr,
@@ -296,8 +536,21 @@ unsafe fn classify_clause(
 
     let op_expr = clause as *mut pg_sys::OpExpr;
     let (left_raw, right_raw) = binary_op_args((*op_expr).args).ok_or(UNSUPPORTED_FILTER_SHAPE)?;
-    let left = unwrap_expr(left_raw)?;
-    let right = unwrap_expr(right_raw)?;
+    let (left, right) = match (unwrap_expr(left_raw), unwrap_expr(right_raw)) {
+        (Ok(left), Ok(right)) => (left, right),
+        (left, right) => {
+            let reason = left
+                .err()
+                .or(right.err())
+                .unwrap_or(UNSUPPORTED_FILTER_SHAPE);
+            return match expression_join_predicate(root, op_expr, left_raw, right_raw, fallback_rte)
+            {
+                Ok(join) => Ok((None, Some(join))),
+                Err(UNSUPPORTED_JOIN_SHAPE) => Err(UNSUPPORTED_JOIN_SHAPE),
+                Err(_) => Err(reason),
+            };
+        }
+    };
     let operator = operator_name((*op_expr).opno);
     let operator_oid =
         ((*op_expr).opno != pg_sys::InvalidOid).then_some(u32::from((*op_expr).opno));
@@ -349,32 +602,22 @@ unsafe fn classify_clause(
             ))
         }
         (pg_sys::NodeTag::T_Var, pg_sys::NodeTag::T_Var) => {
-            if operator.as_deref() != Some("=") {
-                return Err(UNSUPPORTED_JOIN_SHAPE);
-            }
-
             let left_var = describe_var(root, left as *mut pg_sys::Var, fallback_rte);
             let right_var = describe_var(root, right as *mut pg_sys::Var, fallback_rte);
+            // Columns of one relation compared with each other, e.g. a.x < a.y.
+            if left_var.relation == right_var.relation {
+                return Err(UNSUPPORTED_FILTER_SHAPE);
+            }
 
             Ok((
                 None,
-                Some(JoinPredicate {
+                Some(join_predicate(
                     clause,
-                    left_relation: left_var.relation,
-                    right_relation: right_var.relation,
-                    left_schema: left_var.schema,
-                    left_table_name: left_var.table_name,
-                    left_alias: left_var.alias,
-                    left_column_name: left_var.column_name,
-                    left_attribute_number: left_var.attribute_number,
-                    right_schema: right_var.schema,
-                    right_table_name: right_var.table_name,
-                    right_alias: right_var.alias,
-                    right_column_name: right_var.column_name,
-                    right_attribute_number: right_var.attribute_number,
                     operator,
                     operator_oid,
-                }),
+                    (left_var, None),
+                    (right_var, None),
+                )),
             ))
         }
         (_, pg_sys::NodeTag::T_Const) | (pg_sys::NodeTag::T_Const, _) => {
@@ -411,7 +654,8 @@ unsafe fn classify_restrictinfo_list(
                 if let Some(filter) = filter {
                     filters.push(filter);
                 }
-                if let Some(join) = join {
+                if let Some(mut join) = join {
+                    join.pushed_down = (*restrict_info).is_pushed_down;
                     joins.push(join);
                 }
             }
@@ -438,7 +682,65 @@ unsafe fn classify_restrictinfo_list(
     (filters, joins, unsupported_reasons)
 }
 
-fn join_type_name(jointype: pg_sys::JoinType::Type) -> String {
+/// Which inputs keep their rows, and which may be null-extended, for a join
+/// of `outer` with `inner`. Semi and anti joins only return rows from the
+/// preserved side.
+fn join_sides(jointype: pg_sys::JoinType::Type) -> Option<(JoinSide, JoinSide)> {
+    match jointype {
+        pg_sys::JoinType::JOIN_INNER => Some((JoinSide::Both, JoinSide::None)),
+        pg_sys::JoinType::JOIN_LEFT => Some((JoinSide::Outer, JoinSide::Inner)),
+        pg_sys::JoinType::JOIN_RIGHT => Some((JoinSide::Inner, JoinSide::Outer)),
+        pg_sys::JoinType::JOIN_FULL => Some((JoinSide::Both, JoinSide::Both)),
+        pg_sys::JoinType::JOIN_SEMI | pg_sys::JoinType::JOIN_ANTI => {
+            Some((JoinSide::Outer, JoinSide::None))
+        }
+        pg_sys::JoinType::JOIN_RIGHT_SEMI | pg_sys::JoinType::JOIN_RIGHT_ANTI => {
+            Some((JoinSide::Inner, JoinSide::None))
+        }
+        // A semi join executed as an inner join against a de-duplicated side.
+        pg_sys::JoinType::JOIN_UNIQUE_OUTER | pg_sys::JoinType::JOIN_UNIQUE_INNER => {
+            Some((JoinSide::Both, JoinSide::None))
+        }
+        _ => None,
+    }
+}
+
+unsafe fn join_semantics(
+    outerrel: *mut pg_sys::RelOptInfo,
+    innerrel: *mut pg_sys::RelOptInfo,
+    jointype: pg_sys::JoinType::Type,
+    extra: *mut pg_sys::JoinPathExtraData,
+) -> Option<JoinSemantics> {
+    let (preserved_side, nullable_side) = join_sides(jointype)?;
+    let sjinfo = (*extra).sjinfo;
+
+    Some(JoinSemantics {
+        outer_relids: if outerrel.is_null() {
+            Vec::new()
+        } else {
+            bitmapset_members((*outerrel).relids)
+        },
+        inner_relids: if innerrel.is_null() {
+            Vec::new()
+        } else {
+            bitmapset_members((*innerrel).relids)
+        },
+        preserved_side,
+        nullable_side,
+        min_lefthand: if sjinfo.is_null() {
+            Vec::new()
+        } else {
+            bitmapset_members((*sjinfo).min_lefthand)
+        },
+        min_righthand: if sjinfo.is_null() {
+            Vec::new()
+        } else {
+            bitmapset_members((*sjinfo).min_righthand)
+        },
+    })
+}
+
+pub(super) fn join_type_name(jointype: pg_sys::JoinType::Type) -> String {
     match jointype {
         pg_sys::JoinType::JOIN_INNER => "inner",
         pg_sys::JoinType::JOIN_LEFT => "left",
@@ -448,6 +750,8 @@ fn join_type_name(jointype: pg_sys::JoinType::Type) -> String {
         pg_sys::JoinType::JOIN_ANTI => "anti",
         pg_sys::JoinType::JOIN_RIGHT_SEMI => "right_semi",
         pg_sys::JoinType::JOIN_RIGHT_ANTI => "right_anti",
+        pg_sys::JoinType::JOIN_UNIQUE_OUTER => "unique_outer",
+        pg_sys::JoinType::JOIN_UNIQUE_INNER => "unique_inner",
         _ => "unknown",
     }
     .to_string()
@@ -512,6 +816,7 @@ unsafe fn clause_strings(list: *mut pg_sys::List) -> Vec<String> {
 pub unsafe fn base_relation_payload(
     root: *mut pg_sys::PlannerInfo,
     rel: *mut pg_sys::RelOptInfo,
+    rti: pg_sys::Index,
     rte: *mut pg_sys::RangeTblEntry,
 ) -> Option<RelationEstimatePayload> {
     if rel.is_null() || rte.is_null() {
@@ -529,10 +834,13 @@ pub unsafe fn base_relation_payload(
         payload_version: CURRENT_PAYLOAD_VERSION,
         kind: EstimateKind::BaseRel,
         join_type: None,
+        join_semantics: None,
         database: current_database_name(),
         db_oid: (pg_sys::MyDatabaseId != pg_sys::InvalidOid)
             .then_some(u32::from(pg_sys::MyDatabaseId)),
-        state_key: None,
+        state_key: relation_subset_key(root, (*rel).relids),
+        query_level: (*root).query_level,
+        query_hash: query_hash(root),
         rt_indexes: relids.clone(),
         relids,
         relation_names,
@@ -541,6 +849,7 @@ pub unsafe fn base_relation_payload(
         relations: relation_ref(rt_index, rte).into_iter().collect(),
         filters,
         joins,
+        partition: partition_info(root, rel, rti, rte),
         fully_supported: unsupported_reasons.is_empty(),
         unsupported_reasons,
         rows: (*rel).rows,
@@ -551,6 +860,8 @@ pub unsafe fn base_relation_payload(
 pub unsafe fn join_relation_payload(
     root: *mut pg_sys::PlannerInfo,
     rel: *mut pg_sys::RelOptInfo,
+    outerrel: *mut pg_sys::RelOptInfo,
+    innerrel: *mut pg_sys::RelOptInfo,
     jointype: pg_sys::JoinType::Type,
     extra: *mut pg_sys::JoinPathExtraData,
 ) -> Option<RelationEstimatePayload> {
@@ -564,7 +875,8 @@ pub unsafe fn join_relation_payload(
     let (filters, joins, mut unsupported_reasons) =
         classify_restrictinfo_list(root, (*extra).restrictlist, ptr::null_mut());
     let join_type = join_type_name(jointype);
-    if jointype != pg_sys::JoinType::JOIN_INNER {
+    let join_semantics = join_semantics(outerrel, innerrel, jointype, extra);
+    if join_semantics.is_none() {
         unsupported_reasons.push(UNSUPPORTED_JOIN_TYPE.to_string());
     }
 
@@ -572,10 +884,13 @@ pub unsafe fn join_relation_payload(
         payload_version: CURRENT_PAYLOAD_VERSION,
         kind: EstimateKind::JoinRel,
         join_type: Some(join_type),
+        join_semantics,
         database: current_database_name(),
         db_oid: (pg_sys::MyDatabaseId != pg_sys::InvalidOid)
             .then_some(u32::from(pg_sys::MyDatabaseId)),
-        state_key: None,
+        state_key: relation_subset_key(root, (*rel).relids),
+        query_level: (*root).query_level,
+        query_hash: query_hash(root),
         rt_indexes: relids.clone(),
         relids,
         relation_names,
@@ -584,6 +899,7 @@ pub unsafe fn join_relation_payload(
         relations,
         filters,
         joins,
+        partition: None,
         fully_supported: unsupported_reasons.is_empty(),
         unsupported_reasons,
         rows: (*rel).rows,
@@ -0,0 +1,89 @@
+use std::collections::BTreeSet;
+
+use pgrx::prelude::*;
+
+use crate::executor::{self, NodeActuals};
+
+use super::capture;
+use super::payload::RelationEstimatePayload;
+
+/// A captured payload together with the row count its plan node produced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabeledPayload {
+    pub payload: RelationEstimatePayload,
+    /// Rows produced by the lowest plan node covering exactly the payload's
+    /// relations. Unknown when no such node exists, when the payload belongs
+    /// to a subquery, or when the node ran zero or several times, as the rows
+    /// of a rescanned node do not describe the relation subset on its own.
+    pub true_rows: Option<f64>,
+    pub loops: Option<f64>,
+    pub node_type: Option<String>,
+}
+
+/// Keeps one payload per relation subset. The join hook fires once for every
+/// input pair PostgreSQL considers, so the last payload built wins.
+fn dedup_payloads(payloads: Vec<RelationEstimatePayload>) -> Vec<RelationEstimatePayload> {
+    let mut unique: Vec<RelationEstimatePayload> = Vec::new();
+
+    for payload in payloads {
+        match unique
+            .iter_mut()
+            .find(|seen| seen.query_level == payload.query_level && seen.relids == payload.relids)
+        {
+            Some(seen) => *seen = payload,
+            None => unique.push(payload),
+        }
+    }
+
+    unique
+}
+
+/// Matches payloads to executed plan nodes by their sets of base relations.
+/// Payload relids may also contain outer-join indexes, which no plan node
+/// reports, so only indexes scanned somewhere in the plan are compared.
+pub fn label_payloads(
+    payloads: Vec<RelationEstimatePayload>,
+    nodes: &[NodeActuals],
+) -> Vec<LabeledPayload> {
+    let scanned = nodes
+        .iter()
+        .flat_map(|node| node.relids.iter().copied())
+        .collect::<BTreeSet<_>>();
+
+    dedup_payloads(payloads)
+        .into_iter()
+        .map(|payload| {
+            let relids = payload
+                .relids
+                .iter()
+                .copied()
+                .filter(|relid| scanned.contains(relid))
+                .collect::<Vec<_>>();
+
+            // Nodes are in post-order, so the first match is the lowest one,
+            // e.g. a join rather than the Sort or Hash above it.
+            let node = (payload.query_level == 1 && !relids.is_empty())
+                .then(|| nodes.iter().find(|node| node.relids == relids))
+                .flatten();
+
+            LabeledPayload {
+                true_rows: node
+                    .filter(|node| node.loops == 1.0)
+                    .map(|node| node.actual_rows),
+                loops: node.map(|node| node.loops),
+                node_type: node.map(|node| node.node_type.clone()),
+                payload,
+            }
+        })
+        .collect()
+}
+
+/// Plans and runs `query`, returning every payload built while planning it,
+/// labeled with the rows the executed plan actually produced.
+pub fn collect_labels(query: &str) -> anyhow::Result<Vec<LabeledPayload>> {
+    let ((result, nodes), payloads) =
+        capture::with_capture(|| executor::with_observation(|| Spi::run(query)));
+    result?;
+
+    Ok(label_payloads(payloads, &nodes))
+}
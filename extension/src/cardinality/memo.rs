@@ -0,0 +1,232 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+
+use pgrx::pg_sys;
+use serde::Serialize;
+
+use crate::models::ModelVersion;
+use crate::utils::bitmapset_members;
+
+use super::payload::RelationEstimatePayload;
+use super::PGL_CARDINALITY_CACHE_SIZE;
+
+/// How many planned statements keep their estimates for EXPLAIN.
+const MAX_EXPLAINED_STMTS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateSource {
+    Remote,
+    Table,
+    Model,
+    Cache,
+}
+
+impl EstimateSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EstimateSource::Remote => "remote",
+            EstimateSource::Table => "table",
+            EstimateSource::Model => "model",
+            EstimateSource::Cache => "cache",
+        }
+    }
+}
+
+/// An estimate applied to one relation subset.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppliedEstimate {
+    /// Base relations only; outer-join relids never appear in plan nodes.
+    pub relids: Vec<u32>,
+    pub native_rows: f64,
+    pub rows: f64,
+    pub source: EstimateSource,
+    /// The cardinality model version that made the estimate.
+    pub model: Option<ModelVersion>,
+    /// Made in shadow mode; the relation kept PostgreSQL's estimate.
+    pub shadow: bool,
+}
+
+struct PlanningMemo {
+    glob: *mut pg_sys::PlannerGlobal,
+    /// Keyed by the `PlannerInfo` as well, since subqueries number their
+    /// relations independently.
+    estimates: Vec<(*mut pg_sys::PlannerInfo, AppliedEstimate)>,
+}
+
+/// Query hash, state key and the model version that made the estimate.
+type CacheKey = (i64, String, Option<ModelVersion>);
+
+thread_local! {
+    static PLANNING: RefCell<Option<PlanningMemo>> = const { RefCell::new(None) };
+    static EXPLAINED: RefCell<VecDeque<(*mut pg_sys::PlannedStmt, Vec<AppliedEstimate>)>> =
+        const { RefCell::new(VecDeque::new()) };
+    static CACHE: RefCell<HashMap<CacheKey, f64>> = RefCell::new(HashMap::new());
+}
+
+unsafe fn base_relids(root: *mut pg_sys::PlannerInfo, relids: pg_sys::Relids) -> Vec<u32> {
+    bitmapset_members(relids)
+        .into_iter()
+        .filter(|rt_index| {
+            (*rt_index as usize) < (*root).simple_rel_array_size as usize
+                && !(*(*root).simple_rel_array.add(*rt_index as usize)).is_null()
+        })
+        .collect()
+}
+
+/// Returns the estimate already applied to `relids` during the current
+/// planning run. The join hook fires for every input pair of a join
+/// relation, and only the first one needs to ask the estimator.
+pub unsafe fn planned(
+    root: *mut pg_sys::PlannerInfo,
+    relids: pg_sys::Relids,
+) -> Option<AppliedEstimate> {
+    let relids = base_relids(root, relids);
+
+    PLANNING.with(|planning| {
+        planning
+            .borrow()
+            .as_ref()
+            .filter(|memo| memo.glob == (*root).glob)?
+            .estimates
+            .iter()
+            .find(|(owner, estimate)| *owner == root && estimate.relids == relids)
+            .map(|(_, estimate)| estimate.clone())
+    })
+}
+
+pub unsafe fn remember(
+    root: *mut pg_sys::PlannerInfo,
+    relids: pg_sys::Relids,
+    native_rows: f64,
+    rows: f64,
+    source: EstimateSource,
+    model: Option<ModelVersion>,
+    shadow: bool,
+) {
+    let estimate = AppliedEstimate {
+        relids: base_relids(root, relids),
+        native_rows,
+        rows,
+        source,
+        model,
+        shadow,
+    };
+
+    PLANNING.with(|planning| {
+        let mut planning = planning.borrow_mut();
+        if planning
+            .as_ref()
+            .is_none_or(|memo| memo.glob != (*root).glob)
+        {
+            *planning = Some(PlanningMemo {
+                glob: (*root).glob,
+                estimates: Vec::new(),
+            });
+        }
+
+        if let Some(memo) = planning.as_mut() {
+            memo.estimates.push((root, estimate));
+        }
+    });
+}
+
+/// Hands the estimates of the planning run that produced `stmt` over to
+/// EXPLAIN. Must be called after every `standard_planner` call, so a
+/// statement never shows estimates from an earlier one at the same address.
+pub unsafe fn attach(stmt: *mut pg_sys::PlannedStmt) {
+    let estimates = PLANNING
+        .with(|planning| planning.borrow_mut().take())
+        .map(|memo| {
+            memo.estimates
+                .into_iter()
+                .filter(|(root, _)| (**root).parent_root.is_null())
+                .map(|(_, estimate)| estimate)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    EXPLAINED.with(|explained| {
+        let mut explained = explained.borrow_mut();
+        explained.retain(|(planned, _)| *planned != stmt);
+        if estimates.is_empty() {
+            return;
+        }
+
+        if explained.len() >= MAX_EXPLAINED_STMTS {
+            explained.pop_front();
+        }
+        explained.push_back((stmt, estimates));
+    });
+}
+
+/// The estimate applied to the top-level relation subset `relids` of `stmt`.
+pub fn explained(stmt: *mut pg_sys::PlannedStmt, relids: &[u32]) -> Option<AppliedEstimate> {
+    EXPLAINED.with(|explained| {
+        explained
+            .borrow()
+            .iter()
+            .find(|(planned, _)| *planned == stmt)?
+            .1
+            .iter()
+            .find(|estimate| estimate.relids == relids)
+            .cloned()
+    })
+}
+
+/// Every estimate applied to the top-level query of `stmt`.
+pub fn applied(stmt: *mut pg_sys::PlannedStmt) -> Vec<AppliedEstimate> {
+    EXPLAINED.with(|explained| {
+        explained
+            .borrow()
+            .iter()
+            .find(|(planned, _)| *planned == stmt)
+            .map(|(_, estimates)| estimates.clone())
+            .unwrap_or_default()
+    })
+}
+
+fn cache_key(payload: &RelationEstimatePayload, model: Option<&ModelVersion>) -> Option<CacheKey> {
+    Some((
+        payload.query_hash?,
+        payload.state_key.clone()?,
+        model.cloned(),
+    ))
+}
+
+/// The estimate a remote server returned for the same query and relation
+/// subset in an earlier planning run of this backend, by the same model
+/// version. It has not been through the guards.
+pub fn cached(payload: &RelationEstimatePayload, model: Option<&ModelVersion>) -> Option<f64> {
+    if PGL_CARDINALITY_CACHE_SIZE.get() <= 0 {
+        return None;
+    }
+
+    let key = cache_key(payload, model)?;
+    CACHE.with(|cache| cache.borrow().get(&key).copied())
+}
+
+pub fn cache(payload: &RelationEstimatePayload, model: Option<&ModelVersion>, estimate: f64) {
+    let capacity = PGL_CARDINALITY_CACHE_SIZE.get();
+    if capacity <= 0 {
+        return;
+    }
+
+    let Some(key) = cache_key(payload, model) else {
+        return;
+    };
+
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        // Dropping everything keeps the cache simple; it refills within a few
+        // planning runs of the queries that matter.
+        if cache.len() >= capacity as usize && !cache.contains_key(&key) {
+            cache.clear();
+        }
+        cache.insert(key, estimate);
+    });
+}
+
+pub fn clear_cache() {
+    CACHE.with(|cache| cache.borrow_mut().clear());
+}
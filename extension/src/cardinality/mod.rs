@@ -1,20 +1,60 @@
-use pgrx::pg_guard;
+use std::rc::Rc;
+use std::time::Instant;
+
 use pgrx::pg_sys;
-use pgrx::GucSetting;
+use pgrx::prelude::*;
+use pgrx::{GucSetting, JsonB};
 
+use crate::executor::NodeActuals;
+use crate::experiment;
+use crate::models::{self, ActiveModel, ModelVersion};
+use crate::policy;
 use crate::rpc::client::PglRemoteSyncClient;
-use crate::rpc::remote_server_url;
+use crate::rpc::metadata::RequestMetadata;
+use crate::rpc::remote_cardinality_url;
+use crate::shadow;
+use crate::stats::{self, Call, Component, Outcome};
+use crate::utils::{bitmapset_members, in_internal_lookup};
+
+use self::memo::EstimateSource;
+use self::partition::AppendRelRole;
+use self::payload::RelationEstimatePayload;
+use self::types::{PglCardinalitySource, PglPartitionMode};
 
 mod apply;
+mod capture;
 mod extract;
+mod labels;
+mod memo;
+mod overrides;
+mod partition;
 mod payload;
+mod state_key;
+mod types;
+
+pub use self::labels::{collect_labels, LabeledPayload};
+pub use self::memo::AppliedEstimate;
 
 pub static PGL_ENABLE_REMOTE_CARDINALITY: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static PGL_CARDINALITY_PARTITION_MODE: GucSetting<PglPartitionMode> =
+    GucSetting::<PglPartitionMode>::new(PglPartitionMode::Children);
+pub static PGL_CARDINALITY_SOURCE: GucSetting<PglCardinalitySource> =
+    GucSetting::<PglCardinalitySource>::new(PglCardinalitySource::Remote);
+pub static PGL_CARDINALITY_MIN_ROWS: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static PGL_CARDINALITY_CLAMP_TO_CARTESIAN: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static PGL_CARDINALITY_MAX_RATIO: GucSetting<f64> = GucSetting::<f64>::new(0.0);
+pub static PGL_CARDINALITY_BLEND: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static PGL_CARDINALITY_CACHE_SIZE: GucSetting<i32> = GucSetting::<i32>::new(0);
 
 static mut PREV_SET_REL_PATHLIST_HOOK: pg_sys::set_rel_pathlist_hook_type = None;
 static mut PREV_SET_JOIN_PATHLIST_HOOK: pg_sys::set_join_pathlist_hook_type = None;
 
-unsafe fn request_estimate(url: &str, payload: &payload::RelationEstimatePayload) -> Option<i64> {
+unsafe fn request_estimate(
+    url: &str,
+    payload: &RelationEstimatePayload,
+    model: Option<&ModelVersion>,
+    call: &mut Call,
+) -> Option<i64> {
     let request = match serde_json::to_string(payload) {
         Ok(request) => request,
         Err(err) => {
@@ -23,15 +63,21 @@ unsafe fn request_estimate(url: &str, payload: &payload::RelationEstimatePayload
         }
     };
 
+    let started = Instant::now();
     let mut client = match PglRemoteSyncClient::connect(url.to_string()) {
         Ok(client) => client,
         Err(err) => {
+            call.latency = Some(started.elapsed());
             pgrx::warning!("failed to connect to remote cardinality server: {err}");
             return None;
         }
     };
 
-    match client.cardinality_estimate(vec![request]) {
+    client.set_metadata(RequestMetadata::planned());
+    let estimates = client.cardinality_estimate(vec![request], model);
+    call.latency = Some(started.elapsed());
+    call.bytes_sent = client.bytes_sent();
+    match estimates {
         Ok(estimates) => estimates.into_iter().next(),
         Err(err) => {
             pgrx::warning!("failed to request cardinality estimate: {err}");
@@ -40,6 +86,219 @@ unsafe fn request_estimate(url: &str, payload: &payload::RelationEstimatePayload
     }
 }
 
+/// Where injected estimates come from.
+enum Estimator {
+    Remote {
+        url: String,
+        model: Option<ModelVersion>,
+    },
+    Table,
+    Model(Rc<ActiveModel>),
+}
+
+impl Estimator {
+    fn model(&self) -> Option<ModelVersion> {
+        match self {
+            Estimator::Remote { model, .. } => model.clone(),
+            Estimator::Table => None,
+            Estimator::Model(model) => Some(model.version()),
+        }
+    }
+}
+
+fn estimator() -> Option<Estimator> {
+    if !PGL_ENABLE_REMOTE_CARDINALITY.get() || experiment::planning_natively() {
+        return None;
+    }
+
+    let source = PGL_CARDINALITY_SOURCE.get();
+    let model = match source {
+        PglCardinalitySource::Table => None,
+        _ => match models::cardinality_model() {
+            Ok(model) => model,
+            Err(err) => {
+                pgrx::warning!("{err}; keeping PostgreSQL's estimates");
+                return None;
+            }
+        },
+    };
+
+    Some(match source {
+        PglCardinalitySource::Remote => {
+            let endpoint = match model.as_deref().map(ActiveModel::remote).transpose() {
+                Ok(endpoint) => endpoint.flatten().map(str::to_string),
+                Err(err) => {
+                    pgrx::warning!("{err}; keeping PostgreSQL's estimates");
+                    return None;
+                }
+            };
+            let url = endpoint.or_else(remote_cardinality_url).unwrap_or_else(|| {
+                pgrx::error!(
+                    "pgl.enable_remote_cardinality is on, but neither pgl.remote_cardinality_url nor pgl.remote_server_url is set"
+                )
+            });
+            Estimator::Remote {
+                url,
+                model: model.map(|model| model.version()),
+            }
+        }
+        PglCardinalitySource::Table => Estimator::Table,
+        PglCardinalitySource::Model => {
+            let model = model.unwrap_or_else(|| {
+                pgrx::error!(
+                    "pgl.cardinality_source is model, but pgl.cardinality_model is not set"
+                )
+            });
+            if let Err(err) = model.local() {
+                pgrx::warning!("{err}; keeping PostgreSQL's estimates");
+                return None;
+            }
+            Estimator::Model(model)
+        }
+    })
+}
+
+/// Applies an estimate for `rel`, reusing one made earlier in this planning
+/// run or cached from an earlier run before asking the estimator. Returns
+/// whether PostgreSQL's estimate was replaced.
+unsafe fn estimate_and_apply(
+    root: *mut pg_sys::PlannerInfo,
+    rel: *mut pg_sys::RelOptInfo,
+    estimator: &Estimator,
+    payload: Option<&RelationEstimatePayload>,
+    max_rows: Option<f64>,
+) -> bool {
+    if let Some(planned) = memo::planned(root, (*rel).relids) {
+        if planned.shadow {
+            return false;
+        }
+        apply::apply_rows_to_rel(rel, planned.rows);
+        return true;
+    }
+
+    let Some(payload) = payload else {
+        return false;
+    };
+
+    let native_rows = (*rel).rows;
+    let started = Instant::now();
+    let mut call = Call::new(Component::Cardinality);
+    let (estimated, outcome) = match estimator {
+        Estimator::Remote { url, model } => {
+            call.endpoint = Some(url.clone());
+            // The cache holds the server's answer, so the guards see it on
+            // every hit against this planning run's native estimate.
+            let (estimate, source, applied) = match memo::cached(payload, model.as_ref()) {
+                Some(estimate) => (Some(estimate), EstimateSource::Cache, Outcome::Cached),
+                None => {
+                    let estimate = request_estimate(url, payload, model.as_ref(), &mut call)
+                        .map(|estimate| estimate as f64);
+                    if let Some(estimate) = estimate {
+                        memo::cache(payload, model.as_ref(), estimate);
+                    }
+                    (estimate, EstimateSource::Remote, Outcome::Applied)
+                }
+            };
+            match estimate.map(|estimate| apply::guard_estimate(estimate, native_rows, max_rows)) {
+                Some(Some(rows)) => (Some((rows, source)), applied),
+                Some(None) => (None, Outcome::Fallback),
+                None => (None, Outcome::Failed),
+            }
+        }
+        Estimator::Table => {
+            call.endpoint = Some(stats::OVERRIDES_ENDPOINT.to_string());
+            let rows = overrides::lookup_rows(payload);
+            call.latency = Some(started.elapsed());
+            match rows.map(|rows| apply::guard_estimate(rows, native_rows, max_rows)) {
+                Some(Some(rows)) => (Some((rows, EstimateSource::Table)), Outcome::Applied),
+                _ => (None, Outcome::Fallback),
+            }
+        }
+        Estimator::Model(model) => {
+            call.endpoint = Some(model.name.clone());
+            let predicted = serde_json::to_value(payload)
+                .ok()
+                .and_then(|payload| models::estimate_rows(model.local().ok()?, &payload));
+            call.latency = Some(started.elapsed());
+            match predicted.map(|rows| apply::guard_estimate(rows.round(), native_rows, max_rows)) {
+                Some(Some(rows)) => (Some((rows, EstimateSource::Model)), Outcome::Applied),
+                Some(None) => (None, Outcome::Fallback),
+                None => (None, Outcome::Failed),
+            }
+        }
+    };
+    call.record(outcome, started.elapsed());
+
+    let Some((rows, source)) = estimated else {
+        return false;
+    };
+
+    let shadow = shadow::enabled();
+    memo::remember(
+        root,
+        (*rel).relids,
+        native_rows,
+        rows,
+        source,
+        estimator.model(),
+        shadow,
+    );
+    if shadow {
+        shadow::record_estimate(
+            payload.query_hash,
+            payload.state_key.clone(),
+            native_rows,
+            rows,
+        );
+        return false;
+    }
+
+    apply::apply_rows_to_rel(rel, rows);
+    true
+}
+
+/// The most rows a relation subset can produce: the product of its base
+/// relations' tuples, or `None` when one of them has no tuple count.
+unsafe fn cartesian_rows(root: *mut pg_sys::PlannerInfo, relids: pg_sys::Relids) -> Option<f64> {
+    let mut product = 1.0;
+    for rt_index in bitmapset_members(relids) {
+        let rel = partition::rel_at(root, rt_index);
+        // Outer joins have a range table index but no base relation.
+        if rel.is_null() {
+            continue;
+        }
+        if (*rel).tuples <= 0.0 {
+            return None;
+        }
+        product *= (*rel).tuples;
+    }
+    Some(product)
+}
+
+/// The most rows a join can produce given the base relations of its inputs.
+unsafe fn join_max_rows(
+    root: *mut pg_sys::PlannerInfo,
+    outerrel: *mut pg_sys::RelOptInfo,
+    innerrel: *mut pg_sys::RelOptInfo,
+    jointype: pg_sys::JoinType::Type,
+) -> Option<f64> {
+    if outerrel.is_null() || innerrel.is_null() {
+        return None;
+    }
+
+    let outer = cartesian_rows(root, (*outerrel).relids)?;
+    let inner = cartesian_rows(root, (*innerrel).relids)?;
+    let product = outer * inner;
+
+    Some(match jointype {
+        pg_sys::JoinType::JOIN_SEMI | pg_sys::JoinType::JOIN_ANTI => outer,
+        pg_sys::JoinType::JOIN_LEFT => product.max(outer),
+        pg_sys::JoinType::JOIN_RIGHT => product.max(inner),
+        pg_sys::JoinType::JOIN_FULL => product.max(outer + inner),
+        _ => product,
+    })
+}
+
 #[pg_guard]
 extern "C-unwind" fn pgl_set_rel_pathlist(
     root: *mut pg_sys::PlannerInfo,
@@ -48,29 +307,51 @@ extern "C-unwind" fn pgl_set_rel_pathlist(
     rte: *mut pg_sys::RangeTblEntry,
 ) {
     unsafe {
-        if !PGL_ENABLE_REMOTE_CARDINALITY.get() {
+        if overrides::in_lookup()
+            || in_internal_lookup()
+            || (!PGL_ENABLE_REMOTE_CARDINALITY.get() && !capture::is_active())
+        {
             if let Some(prev) = PREV_SET_REL_PATHLIST_HOOK {
                 prev(root, rel, rti, rte);
             }
             return;
         }
 
-        let remote_url = remote_server_url().unwrap_or_else(|| {
-            pgrx::error!(
-                "pgl.enable_remote_cardinality is on, but pgl.remote_server_url is not set"
-            )
-        });
+        let estimator = estimator();
 
         if let Some(prev) = PREV_SET_REL_PATHLIST_HOOK {
             prev(root, rel, rti, rte);
         }
 
-        let estimate = extract::base_relation_payload(root, rel, rte)
-            .as_ref()
-            .and_then(|payload| request_estimate(&remote_url, payload));
+        let payload = extract::base_relation_payload(root, rel, rti, rte);
+        if let Some(payload) = payload.as_ref() {
+            capture::record(payload);
+        }
+
+        let Some(estimator) = estimator else {
+            return;
+        };
+        if !policy::cardinality_enabled(root, (*rel).relids) {
+            return;
+        }
 
-        if let Some(estimate) = estimate {
-            apply::apply_estimate_to_rel(rel, estimate);
+        let max_rows = ((*rel).tuples > 0.0).then_some((*rel).tuples);
+        let role = partition::appendrel_role(root, rel, rti, rte);
+        match (PGL_CARDINALITY_PARTITION_MODE.get(), role) {
+            (_, AppendRelRole::Plain) | (PglPartitionMode::Children, AppendRelRole::Leaf) => {
+                estimate_and_apply(root, rel, &estimator, payload.as_ref(), max_rows);
+            }
+            // Children were estimated individually, so a parent is their sum.
+            (PglPartitionMode::Children, _) => {
+                apply::apply_rows_to_rel(rel, partition::sum_child_rows(root, rti));
+            }
+            // Only the top-level parent is estimated; it pushes rows down.
+            (PglPartitionMode::Parent, AppendRelRole::TopParent) => {
+                if estimate_and_apply(root, rel, &estimator, payload.as_ref(), max_rows) {
+                    partition::distribute_rows(root, rti, (*rel).rows);
+                }
+            }
+            (PglPartitionMode::Parent, _) => {}
         }
     }
 }
@@ -85,33 +366,167 @@ extern "C-unwind" fn pgl_set_join_pathlist(
     extra: *mut pg_sys::JoinPathExtraData,
 ) {
     unsafe {
-        if !PGL_ENABLE_REMOTE_CARDINALITY.get() {
+        if overrides::in_lookup()
+            || in_internal_lookup()
+            || (!PGL_ENABLE_REMOTE_CARDINALITY.get() && !capture::is_active())
+        {
             if let Some(prev) = PREV_SET_JOIN_PATHLIST_HOOK {
                 prev(root, joinrel, outerrel, innerrel, jointype, extra);
             }
             return;
         }
 
-        let remote_url = remote_server_url().unwrap_or_else(|| {
-            pgrx::error!(
-                "pgl.enable_remote_cardinality is on, but pgl.remote_server_url is not set"
-            )
-        });
+        let estimator = estimator();
 
         if let Some(prev) = PREV_SET_JOIN_PATHLIST_HOOK {
             prev(root, joinrel, outerrel, innerrel, jointype, extra);
         }
 
-        let estimate = extract::join_relation_payload(root, joinrel, jointype, extra)
-            .as_ref()
-            .and_then(|payload| request_estimate(&remote_url, payload));
+        let payload =
+            extract::join_relation_payload(root, joinrel, outerrel, innerrel, jointype, extra);
+        if let Some(payload) = payload.as_ref() {
+            capture::record(payload);
+        }
 
-        if let Some(estimate) = estimate {
-            apply::apply_estimate_to_rel(joinrel, estimate);
+        let Some(estimator) = estimator else {
+            return;
+        };
+        if !policy::cardinality_enabled(root, (*joinrel).relids) {
+            return;
         }
+
+        let max_rows = join_max_rows(root, outerrel, innerrel, jointype);
+        estimate_and_apply(root, joinrel, &estimator, payload.as_ref(), max_rows);
     }
 }
 
+extension_sql!(
+    "CREATE TABLE IF NOT EXISTS pgl.cardinality_overrides (
+         query_hash BIGINT NOT NULL,
+         state_key TEXT NOT NULL,
+         rows FLOAT8 NOT NULL,
+         PRIMARY KEY (query_hash, state_key)
+     );",
+    name = "create_cardinality_overrides",
+    requires = ["create_schema_pglearned"]
+);
+
+#[pg_extern]
+fn pgl_cardinality_payloads(
+    query: &str,
+) -> anyhow::Result<TableIterator<'static, (name!(payload, JsonB),)>> {
+    let explain = format!("EXPLAIN (FORMAT JSON) {}", query);
+    let (result, payloads) = capture::with_capture(|| Spi::run(&explain));
+    result?;
+
+    let payloads = payloads
+        .into_iter()
+        .map(|payload| Ok((JsonB(serde_json::to_value(payload)?),)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(TableIterator::new(payloads))
+}
+
+#[pg_extern]
+fn pgl_cardinality_labels(
+    query: &str,
+) -> anyhow::Result<
+    TableIterator<
+        'static,
+        (
+            name!(payload, JsonB),
+            name!(estimated_rows, f64),
+            name!(true_rows, Option<f64>),
+            name!(loops, Option<f64>),
+            name!(node_type, Option<String>),
+        ),
+    >,
+> {
+    let labels = collect_labels(query)?
+        .into_iter()
+        .map(|label| {
+            Ok((
+                JsonB(serde_json::to_value(&label.payload)?),
+                label.payload.rows,
+                label.true_rows,
+                label.loops,
+                label.node_type,
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(TableIterator::new(labels))
+}
+
+#[pg_extern]
+fn pgl_cardinality_guard_stats() -> TableIterator<
+    'static,
+    (
+        name!(accepted, i64),
+        name!(clamped, i64),
+        name!(rejected, i64),
+    ),
+> {
+    let stats = apply::guard_stats();
+    TableIterator::once((stats.accepted, stats.clamped, stats.rejected))
+}
+
+#[pg_extern]
+fn pgl_cardinality_guard_stats_reset() {
+    apply::reset_guard_stats();
+}
+
+#[pg_extern]
+fn pgl_cardinality_cache_reset() {
+    memo::clear_cache();
+}
+
+/// Hands the estimates applied while planning `stmt` over to EXPLAIN.
+pub unsafe fn attach_estimates(stmt: *mut pg_sys::PlannedStmt) {
+    memo::attach(stmt);
+}
+
+/// Every estimate applied while planning `stmt`.
+pub fn applied_estimates(stmt: *mut pg_sys::PlannedStmt) -> Vec<AppliedEstimate> {
+    memo::applied(stmt)
+}
+
+/// The q-error of every estimate applied to `stmt` against the rows its
+/// executed plan produced, from the first node covering the same relations.
+pub fn q_errors(stmt: *mut pg_sys::PlannedStmt, nodes: &[NodeActuals]) -> Vec<f64> {
+    memo::applied(stmt)
+        .iter()
+        .filter(|estimate| !estimate.shadow)
+        .filter_map(|estimate| {
+            let node = nodes
+                .iter()
+                .find(|node| node.relids == estimate.relids && node.loops > 0.0)?;
+            let actual = (node.actual_rows / node.loops).max(1.0);
+            let rows = estimate.rows.max(1.0);
+            Some((rows / actual).max(actual / rows))
+        })
+        .collect()
+}
+
+/// The estimate applied to (or, in shadow mode, made for) the top-level
+/// relation subset `relids` of `stmt`.
+pub fn explained_estimate(
+    stmt: *mut pg_sys::PlannedStmt,
+    relids: &[u32],
+) -> Option<AppliedEstimate> {
+    memo::explained(stmt, relids)
+}
+
+/// Stops payload capture once the captured statement starts executing.
+pub fn freeze_capture() {
+    capture::freeze();
+}
+
+/// True while the hooks are reading `pgl.cardinality_overrides`.
+pub fn in_override_lookup() -> bool {
+    overrides::in_lookup()
+}
+
 pub unsafe fn register() {
     PREV_SET_REL_PATHLIST_HOOK = pg_sys::set_rel_pathlist_hook;
     pg_sys::set_rel_pathlist_hook = Some(pgl_set_rel_pathlist);
@@ -0,0 +1,72 @@
+use std::cell::Cell;
+
+use pgrx::prelude::*;
+
+use super::payload::RelationEstimatePayload;
+
+thread_local! {
+    static LOOKUP_ACTIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Clears the lookup flag when dropped, even if the lookup query errors.
+struct LookupGuard;
+
+impl Drop for LookupGuard {
+    fn drop(&mut self) {
+        LOOKUP_ACTIVE.with(|active| active.set(false));
+    }
+}
+
+/// True while an override lookup query is being planned or executed. The
+/// hooks must leave that query alone, or every lookup would recurse.
+pub fn in_lookup() -> bool {
+    LOOKUP_ACTIVE.with(Cell::get)
+}
+
+/// Reads the recorded rows for the payload's query and relation subset from
+/// `pgl.cardinality_overrides`.
+pub fn lookup_rows(payload: &RelationEstimatePayload) -> Option<f64> {
+    let query_hash = payload.query_hash?;
+    let state_key = payload.state_key.as_deref()?;
+
+    LOOKUP_ACTIVE.with(|active| active.set(true));
+    let guard = LookupGuard;
+
+    let rows = Spi::connect(|client| {
+        // Missing where pgl is loaded but not installed, e.g. by LOAD.
+        let installed = client
+            .select(
+                "SELECT to_regclass('pgl.cardinality_overrides') IS NOT NULL",
+                Some(1),
+                &[],
+            )?
+            .first()
+            .get_one::<bool>()?;
+        if installed != Some(true) {
+            return Ok(None);
+        }
+
+        let args = vec![
+            pgrx::datum::DatumWithOid::from(query_hash),
+            pgrx::datum::DatumWithOid::from(state_key),
+        ];
+        let res = client.select(
+            "SELECT rows FROM pgl.cardinality_overrides WHERE query_hash = $1 AND state_key = $2",
+            Some(1),
+            &args,
+        )?;
+        if res.is_empty() {
+            return Ok(None);
+        }
+        res.first().get_one::<f64>()
+    });
+
+    drop(guard);
+    match rows {
+        Ok(rows) => rows,
+        Err(err) => {
+            pgrx::warning!("failed to read cardinality override: {err}");
+            None
+        }
+    }
+}
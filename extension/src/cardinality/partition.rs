@@ -0,0 +1,238 @@
+use pgrx::pg_sys;
+use pgrx::IntoDatum;
+
+use super::apply::apply_rows_to_rel;
+use super::extract::relation_name;
+use super::payload::PartitionInfo;
+
+/// How a relation participates in an appendrel (partitioned or inheritance) tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendRelRole {
+    /// Not part of an appendrel.
+    Plain,
+    /// The top-level parent that the query references.
+    TopParent,
+    /// A child that is itself partitioned.
+    IntermediateParent,
+    /// A leaf partition or inheritance child.
+    Leaf,
+}
+
+pub(super) unsafe fn rel_at(
+    root: *mut pg_sys::PlannerInfo,
+    rt_index: u32,
+) -> *mut pg_sys::RelOptInfo {
+    if root.is_null() || (*root).simple_rel_array.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let rel_index = rt_index as usize;
+    if rel_index >= (*root).simple_rel_array_size as usize {
+        return std::ptr::null_mut();
+    }
+
+    *(*root).simple_rel_array.add(rel_index)
+}
+
+pub(super) unsafe fn rte_at(
+    root: *mut pg_sys::PlannerInfo,
+    rt_index: u32,
+) -> *mut pg_sys::RangeTblEntry {
+    if root.is_null() || (*root).simple_rte_array.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let rel_index = rt_index as usize;
+    if rel_index >= (*root).simple_rel_array_size as usize {
+        return std::ptr::null_mut();
+    }
+
+    *(*root).simple_rte_array.add(rel_index)
+}
+
+unsafe fn append_rel_info(
+    root: *mut pg_sys::PlannerInfo,
+    child_rt_index: u32,
+) -> *mut pg_sys::AppendRelInfo {
+    if root.is_null() || (*root).append_rel_array.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let rel_index = child_rt_index as usize;
+    if rel_index >= (*root).simple_rel_array_size as usize {
+        return std::ptr::null_mut();
+    }
+
+    *(*root).append_rel_array.add(rel_index)
+}
+
+unsafe fn is_appendrel_parent(rte: *mut pg_sys::RangeTblEntry) -> bool {
+    !rte.is_null() && (*rte).rtekind == pg_sys::RTEKind::RTE_RELATION && (*rte).inh
+}
+
+pub unsafe fn appendrel_role(
+    root: *mut pg_sys::PlannerInfo,
+    rel: *mut pg_sys::RelOptInfo,
+    rti: pg_sys::Index,
+    rte: *mut pg_sys::RangeTblEntry,
+) -> AppendRelRole {
+    if rel.is_null() {
+        return AppendRelRole::Plain;
+    }
+
+    let is_child = (*rel).reloptkind == pg_sys::RelOptKind::RELOPT_OTHER_MEMBER_REL
+        && !append_rel_info(root, rti).is_null();
+
+    match (is_appendrel_parent(rte), is_child) {
+        (true, false) => AppendRelRole::TopParent,
+        (true, true) => AppendRelRole::IntermediateParent,
+        (false, true) => AppendRelRole::Leaf,
+        (false, false) => AppendRelRole::Plain,
+    }
+}
+
+/// Returns the rt indexes of the children that survived partition pruning.
+pub unsafe fn child_rt_indexes(root: *mut pg_sys::PlannerInfo, parent_rt_index: u32) -> Vec<u32> {
+    let mut children = Vec::new();
+
+    if root.is_null() || (*root).append_rel_array.is_null() {
+        return children;
+    }
+
+    for child in 1..(*root).simple_rel_array_size.max(0) as u32 {
+        let appinfo = append_rel_info(root, child);
+        if appinfo.is_null() || (*appinfo).parent_relid != parent_rt_index {
+            continue;
+        }
+        if rel_at(root, child).is_null() {
+            continue;
+        }
+        children.push(child);
+    }
+
+    children
+}
+
+fn strategy_name(strategy: std::os::raw::c_char) -> Option<String> {
+    match strategy as u8 {
+        b'r' => Some("range"),
+        b'l' => Some("list"),
+        b'h' => Some("hash"),
+        _ => None,
+    }
+    .map(str::to_string)
+}
+
+unsafe fn partition_bound(relid: pg_sys::Oid) -> Option<String> {
+    if relid == pg_sys::InvalidOid {
+        return None;
+    }
+
+    let tuple = pg_sys::SearchSysCache1(
+        pg_sys::SysCacheIdentifier::RELOID as i32,
+        pg_sys::Datum::from(u32::from(relid)),
+    );
+    if tuple.is_null() {
+        return None;
+    }
+
+    let mut is_null = true;
+    let bound = pg_sys::SysCacheGetAttr(
+        pg_sys::SysCacheIdentifier::RELOID as i32,
+        tuple,
+        pg_sys::Anum_pg_class_relpartbound as pg_sys::AttrNumber,
+        &mut is_null,
+    );
+
+    let rendered = if is_null {
+        None
+    } else {
+        pgrx::direct_function_call::<String>(
+            pg_sys::pg_get_expr,
+            &[Some(bound), relid.into_datum()],
+        )
+    };
+
+    pg_sys::ReleaseSysCache(tuple);
+    rendered
+}
+
+pub unsafe fn partition_info(
+    root: *mut pg_sys::PlannerInfo,
+    rel: *mut pg_sys::RelOptInfo,
+    rti: pg_sys::Index,
+    rte: *mut pg_sys::RangeTblEntry,
+) -> Option<PartitionInfo> {
+    let role = appendrel_role(root, rel, rti, rte);
+    if role == AppendRelRole::Plain {
+        return None;
+    }
+
+    let mut info = PartitionInfo {
+        parent_rt_index: None,
+        parent_name: None,
+        bound: None,
+        strategy: None,
+        child_rt_indexes: Vec::new(),
+    };
+
+    if matches!(
+        role,
+        AppendRelRole::IntermediateParent | AppendRelRole::Leaf
+    ) {
+        let appinfo = append_rel_info(root, rti);
+        info.parent_rt_index = Some((*appinfo).parent_relid);
+        info.parent_name = relation_name(rte_at(root, (*appinfo).parent_relid));
+        info.bound = partition_bound((*rte).relid);
+    }
+
+    if matches!(
+        role,
+        AppendRelRole::TopParent | AppendRelRole::IntermediateParent
+    ) {
+        if !(*rel).part_scheme.is_null() {
+            info.strategy = strategy_name((*(*rel).part_scheme).strategy);
+        }
+        info.child_rt_indexes = child_rt_indexes(root, rti);
+    }
+
+    Some(info)
+}
+
+/// Sums the row estimates of the surviving children of an appendrel parent.
+pub unsafe fn sum_child_rows(root: *mut pg_sys::PlannerInfo, parent_rt_index: u32) -> f64 {
+    child_rt_indexes(root, parent_rt_index)
+        .into_iter()
+        .map(|child| (*rel_at(root, child)).rows)
+        .sum()
+}
+
+/// Spreads `rows` across the surviving children of an appendrel parent in
+/// proportion to PostgreSQL's own child estimates, recursing into
+/// sub-partitioned children.
+pub unsafe fn distribute_rows(root: *mut pg_sys::PlannerInfo, parent_rt_index: u32, rows: f64) {
+    let children = child_rt_indexes(root, parent_rt_index);
+    if children.is_empty() {
+        return;
+    }
+
+    let child_count = children.len() as f64;
+    let native_total: f64 = children
+        .iter()
+        .map(|child| (*rel_at(root, *child)).rows)
+        .sum();
+
+    for child in children {
+        let child_rel = rel_at(root, child);
+        let share = if native_total > 0.0 {
+            rows * (*child_rel).rows / native_total
+        } else {
+            rows / child_count
+        };
+
+        if is_appendrel_parent(rte_at(root, child)) {
+            distribute_rows(root, child, share);
+        }
+        apply_rows_to_rel(child_rel, share);
+    }
+}
@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-pub const CURRENT_PAYLOAD_VERSION: u32 = 1;
+pub const CURRENT_PAYLOAD_VERSION: u32 = 2;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -66,6 +66,8 @@ pub struct JoinPredicate {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub left_attribute_number: Option<i16>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub left_expression: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub right_schema: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub right_table_name: Option<String>,
@@ -76,9 +78,45 @@ pub struct JoinPredicate {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub right_attribute_number: Option<i16>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub right_expression: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub operator: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub operator_oid: Option<u32>,
+    pub pushed_down: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinSide {
+    Outer,
+    Inner,
+    Both,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JoinSemantics {
+    pub outer_relids: Vec<u32>,
+    pub inner_relids: Vec<u32>,
+    pub preserved_side: JoinSide,
+    pub nullable_side: JoinSide,
+    pub min_lefthand: Vec<u32>,
+    pub min_righthand: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PartitionInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_rt_index: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bound: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub child_rt_indexes: Vec<u32>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
@@ -88,11 +126,17 @@ pub struct RelationEstimatePayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub join_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub join_semantics: Option<JoinSemantics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub database: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub db_oid: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub state_key: Option<String>,
+    /// 1 for the top-level query, deeper for subqueries planned separately.
+    pub query_level: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_hash: Option<i64>,
     pub relids: Vec<u32>,
     pub relation_names: Vec<String>,
     pub alias_names: Vec<String>,
@@ -101,6 +145,8 @@ pub struct RelationEstimatePayload {
     pub relations: Vec<RelationRef>,
     pub filters: Vec<FilterPredicate>,
     pub joins: Vec<JoinPredicate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partition: Option<PartitionInfo>,
     pub fully_supported: bool,
     pub unsupported_reasons: Vec<String>,
     pub rows: f64,
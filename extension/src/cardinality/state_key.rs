@@ -0,0 +1,211 @@
+use std::collections::BTreeSet;
+use std::ptr;
+
+use pgrx::pg_sys;
+
+use crate::utils::{bitmapset_members, fnv1a_128};
+
+use super::extract::{
+    alias_name, clause_string, join_type_name, list_exprs, operator_name, relation_name,
+    relation_schema, render_expr,
+};
+use super::partition::{rel_at, rte_at};
+
+const STATE_KEY_VERSION: &str = "v1";
+
+unsafe fn list_ptrs<T>(list: *mut pg_sys::List) -> Vec<*mut T> {
+    list_exprs(list).into_iter().map(|ptr| ptr.cast()).collect()
+}
+
+/// `schema.name AS alias`, which identifies a relation independently of its
+/// position in the range table.
+unsafe fn relation_identity(rte: *mut pg_sys::RangeTblEntry) -> Option<String> {
+    let name = relation_name(rte)?;
+    let qualified = match relation_schema(rte) {
+        Some(schema) => format!("{schema}.{name}"),
+        None => name,
+    };
+
+    Some(match alias_name(rte) {
+        Some(alias) => format!("{qualified} AS {alias}"),
+        None => qualified,
+    })
+}
+
+/// Node dumps carry parse locations, which change with whitespace in the query
+/// text; they are dropped so the fallback rendering stays stable.
+fn strip_locations(node: &str) -> String {
+    let mut stripped = String::with_capacity(node.len());
+    let mut rest = node;
+
+    while let Some(pos) = rest.find(" :location ") {
+        stripped.push_str(&rest[..pos]);
+        rest = &rest[pos + " :location ".len()..];
+        let end = rest
+            .find(|c: char| c != '-' && !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        rest = &rest[end..];
+    }
+
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Renders a clause in a form that does not depend on which side of a
+/// commutative operator a column was written on.
+unsafe fn canonical_clause(root: *mut pg_sys::PlannerInfo, clause: *mut pg_sys::Expr) -> String {
+    let mut vars = Vec::new();
+
+    if !clause.is_null() && (*(clause as *mut pg_sys::Node)).type_ == pg_sys::NodeTag::T_OpExpr {
+        let op_expr = clause as *mut pg_sys::OpExpr;
+        let args = list_exprs((*op_expr).args)
+            .into_iter()
+            .map(|arg| render_expr(root, arg, ptr::null_mut(), &mut vars))
+            .collect::<Option<Vec<_>>>();
+
+        if let Some([left, right]) = args.as_deref() {
+            let commutator = pg_sys::get_commutator((*op_expr).opno);
+            if left > right && commutator != pg_sys::InvalidOid {
+                if let Some(operator) = operator_name(commutator) {
+                    return format!("({right} {operator} {left})");
+                }
+            }
+        }
+    }
+
+    render_expr(root, clause, ptr::null_mut(), &mut vars)
+        .or_else(|| clause_string(clause).map(|node| strip_locations(&node)))
+        .unwrap_or_default()
+}
+
+unsafe fn restrictinfo_clauses(
+    root: *mut pg_sys::PlannerInfo,
+    list: *mut pg_sys::List,
+    within: pg_sys::Relids,
+) -> Vec<String> {
+    list_ptrs::<pg_sys::RestrictInfo>(list)
+        .into_iter()
+        .filter(|rinfo| !rinfo.is_null() && !(**rinfo).clause.is_null())
+        .filter(|rinfo| {
+            within.is_null() || pg_sys::bms_is_subset((**rinfo).required_relids, within)
+        })
+        .map(|rinfo| canonical_clause(root, (*rinfo).clause))
+        .collect()
+}
+
+/// Equality classes linking two or more members of `relids`; these are the
+/// equi-join predicates PostgreSQL does not keep in `joininfo`.
+unsafe fn equivalence_parts(root: *mut pg_sys::PlannerInfo, relids: pg_sys::Relids) -> Vec<String> {
+    let mut parts = Vec::new();
+
+    for ec in list_ptrs::<pg_sys::EquivalenceClass>((*root).eq_classes) {
+        if ec.is_null() || (*ec).ec_has_const {
+            continue;
+        }
+
+        let mut members = BTreeSet::new();
+        for em in list_ptrs::<pg_sys::EquivalenceMember>((*ec).ec_members) {
+            if em.is_null() || (*em).em_is_const || (*em).em_is_child {
+                continue;
+            }
+            if !pg_sys::bms_is_subset((*em).em_relids, relids) {
+                continue;
+            }
+
+            let mut vars = Vec::new();
+            if let Some(rendered) = render_expr(root, (*em).em_expr, ptr::null_mut(), &mut vars) {
+                members.insert(rendered);
+            }
+        }
+
+        if members.len() > 1 {
+            parts.push(format!(
+                "eq {{{}}}",
+                members.into_iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+    }
+
+    parts
+}
+
+unsafe fn identity_set(root: *mut pg_sys::PlannerInfo, relids: pg_sys::Relids) -> String {
+    let identities = bitmapset_members(relids)
+        .into_iter()
+        .filter_map(|rt_index| relation_identity(rte_at(root, rt_index)))
+        .collect::<BTreeSet<_>>();
+
+    identities.into_iter().collect::<Vec<_>>().join(", ")
+}
+
+/// Outer, semi and anti joins whose both sides lie inside `relids`.
+unsafe fn special_join_parts(
+    root: *mut pg_sys::PlannerInfo,
+    relids: pg_sys::Relids,
+) -> Vec<String> {
+    let mut parts = Vec::new();
+
+    for sjinfo in list_ptrs::<pg_sys::SpecialJoinInfo>((*root).join_info_list) {
+        if sjinfo.is_null()
+            || !pg_sys::bms_is_subset((*sjinfo).min_lefthand, relids)
+            || !pg_sys::bms_is_subset((*sjinfo).min_righthand, relids)
+        {
+            continue;
+        }
+
+        parts.push(format!(
+            "join {} {{{}}} {{{}}}",
+            join_type_name((*sjinfo).jointype),
+            identity_set(root, (*sjinfo).min_lefthand),
+            identity_set(root, (*sjinfo).min_righthand)
+        ));
+    }
+
+    parts
+}
+
+/// Computes a key identifying the relation subset `relids` by its relations,
+/// their filters and the join predicates among them. The key only depends on
+/// names and predicates, so it is identical across runs, brute-force arms and
+/// the different input pairs PostgreSQL tries for one join relation.
+pub unsafe fn relation_subset_key(
+    root: *mut pg_sys::PlannerInfo,
+    relids: pg_sys::Relids,
+) -> Option<String> {
+    if root.is_null() || relids.is_null() {
+        return None;
+    }
+
+    let mut parts = BTreeSet::new();
+
+    for rt_index in bitmapset_members(relids) {
+        let rel = rel_at(root, rt_index);
+        if rel.is_null() {
+            continue;
+        }
+
+        let identity = relation_identity(rte_at(root, rt_index))?;
+        let filters = restrictinfo_clauses(root, (*rel).baserestrictinfo, ptr::null_mut())
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        parts.insert(format!(
+            "rel {identity} [{}]",
+            filters.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+
+        for clause in restrictinfo_clauses(root, (*rel).joininfo, relids) {
+            parts.insert(format!("pred {clause}"));
+        }
+    }
+
+    if pg_sys::bms_membership(relids) == pg_sys::BMS_Membership::BMS_MULTIPLE {
+        parts.extend(equivalence_parts(root, relids));
+        parts.extend(special_join_parts(root, relids));
+    }
+
+    let canonical = parts.into_iter().collect::<Vec<_>>().join("\n");
+    Some(format!(
+        "{STATE_KEY_VERSION}:{:032x}",
+        fnv1a_128(canonical.as_bytes())
+    ))
+}
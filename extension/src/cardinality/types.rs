@@ -0,0 +1,12 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, pgrx::PostgresGucEnum)]
+pub enum PglPartitionMode {
+    Children,
+    Parent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, pgrx::PostgresGucEnum)]
+pub enum PglCardinalitySource {
+    Remote,
+    Table,
+    Model,
+}
@@ -1,5 +1,6 @@
 use pgrx::{prelude::*, JsonB};
 
+use crate::cardinality::collect_labels;
 use crate::planner::EXPLAIN_PLANNER_MAP;
 
 #[pg_extern]
@@ -96,6 +97,123 @@ fn pgl_qdataset_collect(
     ))
 }
 
+#[pg_extern]
+fn pgl_qdataset_collect_cardinality(
+    dataset_name: &str,
+    offset: i64,
+    limit: i64,
+) -> anyhow::Result<
+    TableIterator<
+        'static,
+        (
+            name!(query_id, i64),
+            name!(state_key, Option<String>),
+            name!(payload, JsonB),
+            name!(estimated_rows, f64),
+            name!(true_rows, Option<f64>),
+        ),
+    >,
+> {
+    let current_pos: Option<i64> = Spi::get_one_with_args(
+        "SELECT current_pos FROM pgl.pgl_qdataset_status WHERE dataset_name = $1",
+        &vec![pgrx::datum::DatumWithOid::from(dataset_name)],
+    )?;
+
+    let current_pos = current_pos.ok_or_else(|| anyhow::anyhow!("Dataset not found"))?;
+
+    let effective_offset = if offset == -1 { current_pos } else { offset };
+    let table_name = format!("pgl_qdataset_{}", dataset_name);
+    let label_table_name = format!("pgl_qdataset_{}_cardinality", dataset_name);
+    let fetch_sql = format!(
+        "SELECT id, content FROM pgl.{} ORDER BY id OFFSET $1 LIMIT $2",
+        table_name
+    );
+
+    let mut queries = Vec::new();
+
+    Spi::connect(|client| {
+        let args = vec![
+            pgrx::datum::DatumWithOid::from(effective_offset),
+            pgrx::datum::DatumWithOid::from(limit),
+        ];
+
+        let res = client.select(&fetch_sql, None, &args)?;
+        for row in res {
+            if let (Some(id), Some(content)) = (
+                row.get_by_name::<i64, _>("id")?,
+                row.get_by_name::<String, _>("content")?,
+            ) {
+                queries.push((id, content));
+            }
+        }
+        Ok::<(), pgrx::spi::SpiError>(())
+    })?;
+
+    Spi::run(&format!(
+        "CREATE TABLE IF NOT EXISTS pgl.{} (
+             id BIGSERIAL PRIMARY KEY,
+             query_id BIGINT,
+             state_key TEXT,
+             kind TEXT,
+             payload JSONB,
+             estimated_rows FLOAT8,
+             true_rows FLOAT8,
+             loops FLOAT8
+         )",
+        label_table_name
+    ))?;
+
+    let insert_sql = format!(
+        "INSERT INTO pgl.{} (query_id, state_key, kind, payload, estimated_rows, true_rows, loops)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        label_table_name
+    );
+
+    let processed_count = queries.len() as i64;
+    let mut results = Vec::new();
+
+    for (query_id, content) in queries {
+        for label in collect_labels(&content)? {
+            let payload = JsonB(serde_json::to_value(&label.payload)?);
+            let kind = serde_json::to_value(label.payload.kind)?
+                .as_str()
+                .map(str::to_string);
+
+            let args = vec![
+                pgrx::datum::DatumWithOid::from(query_id),
+                pgrx::datum::DatumWithOid::from(label.payload.state_key.clone()),
+                pgrx::datum::DatumWithOid::from(kind),
+                pgrx::datum::DatumWithOid::from(JsonB(payload.0.clone())),
+                pgrx::datum::DatumWithOid::from(label.payload.rows),
+                pgrx::datum::DatumWithOid::from(label.true_rows),
+                pgrx::datum::DatumWithOid::from(label.loops),
+            ];
+            Spi::run_with_args(&insert_sql, &args)?;
+
+            results.push((
+                query_id,
+                label.payload.state_key,
+                payload,
+                label.payload.rows,
+                label.true_rows,
+            ));
+        }
+    }
+
+    if processed_count > 0 {
+        let new_pos = effective_offset + processed_count;
+        let update_sql =
+            "UPDATE pgl.pgl_qdataset_status SET current_pos = $1 WHERE dataset_name = $2";
+        let args = vec![
+            pgrx::datum::DatumWithOid::from(new_pos),
+            pgrx::datum::DatumWithOid::from(dataset_name),
+        ];
+        Spi::run_with_args(update_sql, &args)?;
+    }
+
+    Ok(TableIterator::new(results))
+}
+
 #[pg_extern]
 fn pgl_qdataset_create(dataset_name: &str) -> Result<(), pgrx::spi::SpiError> {
     let table_name = format!("pgl_qdataset_{}", dataset_name);
@@ -122,6 +240,9 @@ fn pgl_qdataset_delete(dataset_name: &str) -> Result<(), pgrx::spi::SpiError> {
     let query = format!("DROP TABLE IF EXISTS pgl.{};", table_name);
     Spi::run(&query)?;
 
+    let query = format!("DROP TABLE IF EXISTS pgl.{}_cardinality;", table_name);
+    Spi::run(&query)?;
+
     let delete_status_sql = "DELETE FROM pgl.pgl_qdataset_status WHERE dataset_name = $1";
     let args = vec![pgrx::datum::DatumWithOid::from(dataset_name)];
     Spi::run_with_args(delete_status_sql, &args)?;
@@ -0,0 +1,298 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use pgrx::pg_sys;
+use pgrx::prelude::*;
+use serde::Serialize;
+
+use crate::utils::{bitmapset_members, fnv1a_128};
+
+static mut PREV_EXECUTOR_START_HOOK: pg_sys::ExecutorStart_hook_type = None;
+static mut PREV_EXECUTOR_END_HOOK: pg_sys::ExecutorEnd_hook_type = None;
+
+/// What one executed plan node produced.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeActuals {
+    pub node_type: String,
+    /// Range table indexes of the base relations below this node. Append
+    /// nodes report their parent relation rather than each partition.
+    pub relids: Vec<u32>,
+    pub plan_rows: f64,
+    /// Rows summed over all loops.
+    pub actual_rows: f64,
+    pub loops: f64,
+}
+
+/// Identifies the shape of an executed plan by its node types and the
+/// relations below each node, in the order `collect_actuals` records them.
+pub fn plan_fingerprint(nodes: &[NodeActuals]) -> String {
+    let shape = nodes
+        .iter()
+        .map(|node| format!("{} {:?}", node.node_type, node.relids))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{:032x}", fnv1a_128(shape.as_bytes()))
+}
+
+struct Observation {
+    query_desc: *mut pg_sys::QueryDesc,
+    nodes: Vec<NodeActuals>,
+}
+
+thread_local! {
+    static OBSERVATION: RefCell<Option<Observation>> = const { RefCell::new(None) };
+}
+
+/// Clears the observation when dropped, even if the observed query errors.
+struct ObservationGuard;
+
+impl Drop for ObservationGuard {
+    fn drop(&mut self) {
+        OBSERVATION.with(|observation| observation.borrow_mut().take());
+    }
+}
+
+/// Runs `f` and returns the per-node actuals of the first statement it
+/// executes. Statements run by functions inside that statement are ignored.
+pub fn with_observation<R>(f: impl FnOnce() -> R) -> (R, Vec<NodeActuals>) {
+    OBSERVATION.with(|observation| {
+        *observation.borrow_mut() = Some(Observation {
+            query_desc: std::ptr::null_mut(),
+            nodes: Vec::new(),
+        })
+    });
+    let guard = ObservationGuard;
+
+    let result = f();
+    let nodes = OBSERVATION
+        .with(|observation| observation.borrow_mut().take())
+        .map(|observation| observation.nodes)
+        .unwrap_or_default();
+
+    drop(guard);
+    (result, nodes)
+}
+
+/// Claims `query_desc` for observation if nothing was claimed yet.
+fn claim(query_desc: *mut pg_sys::QueryDesc) -> bool {
+    OBSERVATION.with(|observation| match observation.borrow_mut().as_mut() {
+        Some(observation) if observation.query_desc.is_null() => {
+            observation.query_desc = query_desc;
+            true
+        }
+        _ => false,
+    })
+}
+
+fn is_claimed(query_desc: *mut pg_sys::QueryDesc) -> bool {
+    OBSERVATION.with(|observation| {
+        observation
+            .borrow()
+            .as_ref()
+            .is_some_and(|observation| observation.query_desc == query_desc)
+    })
+}
+
+unsafe fn plan_state_array(
+    states: *mut *mut pg_sys::PlanState,
+    len: std::os::raw::c_int,
+) -> Vec<*mut pg_sys::PlanState> {
+    if states.is_null() {
+        return Vec::new();
+    }
+
+    (0..len.max(0) as usize)
+        .map(|idx| *states.add(idx))
+        .collect()
+}
+
+unsafe fn plan_state_list(list: *mut pg_sys::List) -> Vec<*mut pg_sys::PlanState> {
+    if list.is_null() {
+        return Vec::new();
+    }
+
+    let elements = (*list).elements;
+    (0..(*list).length.max(0) as usize)
+        .map(|idx| (*elements.add(idx)).ptr_value as *mut pg_sys::PlanState)
+        .collect()
+}
+
+pub unsafe fn child_plan_states(planstate: *mut pg_sys::PlanState) -> Vec<*mut pg_sys::PlanState> {
+    let mut children = vec![(*planstate).lefttree, (*planstate).righttree];
+
+    match (*(*planstate).plan).type_ {
+        pg_sys::NodeTag::T_Append => {
+            let state = planstate as *mut pg_sys::AppendState;
+            children.extend(plan_state_array((*state).appendplans, (*state).as_nplans));
+        }
+        pg_sys::NodeTag::T_MergeAppend => {
+            let state = planstate as *mut pg_sys::MergeAppendState;
+            children.extend(plan_state_array((*state).mergeplans, (*state).ms_nplans));
+        }
+        pg_sys::NodeTag::T_BitmapAnd => {
+            let state = planstate as *mut pg_sys::BitmapAndState;
+            children.extend(plan_state_array((*state).bitmapplans, (*state).nplans));
+        }
+        pg_sys::NodeTag::T_BitmapOr => {
+            let state = planstate as *mut pg_sys::BitmapOrState;
+            children.extend(plan_state_array((*state).bitmapplans, (*state).nplans));
+        }
+        pg_sys::NodeTag::T_SubqueryScan => {
+            let state = planstate as *mut pg_sys::SubqueryScanState;
+            children.push((*state).subplan);
+        }
+        pg_sys::NodeTag::T_CustomScan => {
+            let state = planstate as *mut pg_sys::CustomScanState;
+            children.extend(plan_state_list((*state).custom_ps));
+        }
+        _ => {}
+    }
+
+    children.retain(|child| !child.is_null());
+    children
+}
+
+/// Relids a node contributes itself, or `None` when it only passes on the
+/// relids of its children.
+unsafe fn own_relids(plan: *mut pg_sys::Plan) -> Option<Vec<u32>> {
+    match (*plan).type_ {
+        pg_sys::NodeTag::T_SeqScan
+        | pg_sys::NodeTag::T_SampleScan
+        | pg_sys::NodeTag::T_IndexScan
+        | pg_sys::NodeTag::T_IndexOnlyScan
+        | pg_sys::NodeTag::T_BitmapHeapScan
+        | pg_sys::NodeTag::T_TidScan
+        | pg_sys::NodeTag::T_TidRangeScan
+        | pg_sys::NodeTag::T_SubqueryScan
+        | pg_sys::NodeTag::T_FunctionScan
+        | pg_sys::NodeTag::T_TableFuncScan
+        | pg_sys::NodeTag::T_ValuesScan
+        | pg_sys::NodeTag::T_CteScan
+        | pg_sys::NodeTag::T_NamedTuplestoreScan
+        | pg_sys::NodeTag::T_WorkTableScan
+        | pg_sys::NodeTag::T_ForeignScan
+        | pg_sys::NodeTag::T_CustomScan => {
+            let scanrelid = (*(plan as *mut pg_sys::Scan)).scanrelid;
+            (scanrelid > 0).then(|| vec![scanrelid])
+        }
+        pg_sys::NodeTag::T_Append => {
+            let apprelids = (*(plan as *mut pg_sys::Append)).apprelids;
+            (!apprelids.is_null()).then(|| bitmapset_members(apprelids))
+        }
+        pg_sys::NodeTag::T_MergeAppend => {
+            let apprelids = (*(plan as *mut pg_sys::MergeAppend)).apprelids;
+            (!apprelids.is_null()).then(|| bitmapset_members(apprelids))
+        }
+        // Bitmap index scans count TIDs, not rows of their relation.
+        pg_sys::NodeTag::T_BitmapIndexScan
+        | pg_sys::NodeTag::T_BitmapAnd
+        | pg_sys::NodeTag::T_BitmapOr => Some(Vec::new()),
+        _ => None,
+    }
+}
+
+/// Range table indexes of the base relations below `planstate`, reported the
+/// same way as `NodeActuals::relids`.
+pub unsafe fn plan_state_relids(planstate: *mut pg_sys::PlanState) -> Vec<u32> {
+    own_relids((*planstate).plan).unwrap_or_else(|| {
+        child_plan_states(planstate)
+            .into_iter()
+            .flat_map(|child| plan_state_relids(child))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    })
+}
+
+/// Walks the plan state tree in post-order, so a node is recorded after the
+/// nodes below it, and returns the relids of `planstate`.
+unsafe fn collect_actuals(
+    planstate: *mut pg_sys::PlanState,
+    out: &mut Vec<NodeActuals>,
+) -> Vec<u32> {
+    let plan = (*planstate).plan;
+
+    let mut below = BTreeSet::new();
+    for child in child_plan_states(planstate) {
+        below.extend(collect_actuals(child, out));
+    }
+
+    let relids = own_relids(plan).unwrap_or_else(|| below.into_iter().collect());
+
+    let instrument = (*planstate).instrument;
+    if !instrument.is_null() {
+        pg_sys::InstrEndLoop(instrument);
+        out.push(NodeActuals {
+            node_type: format!("{:?}", (*plan).type_)
+                .trim_start_matches("T_")
+                .to_string(),
+            relids: relids.clone(),
+            plan_rows: (*plan).plan_rows,
+            actual_rows: (*instrument).ntuples,
+            loops: (*instrument).nloops,
+        });
+    }
+
+    relids
+}
+
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_executor_start(query_desc: *mut pg_sys::QueryDesc, eflags: i32) {
+    let explain_only = eflags & pg_sys::EXEC_FLAG_EXPLAIN_ONLY as i32 != 0;
+    // Lookups pgl runs while planning are not the statements being planned.
+    let lookup = crate::cardinality::in_override_lookup() || crate::utils::in_internal_lookup();
+    if !explain_only && !lookup && claim(query_desc) {
+        (*query_desc).instrument_options |= pg_sys::InstrumentOption::INSTRUMENT_ROWS as i32;
+        crate::cardinality::freeze_capture();
+    }
+
+    if !lookup && crate::feedback::begin(query_desc, eflags) {
+        (*query_desc).instrument_options |= pg_sys::InstrumentOption::INSTRUMENT_ROWS as i32;
+    }
+
+    if let Some(prev) = PREV_EXECUTOR_START_HOOK {
+        prev(query_desc, eflags);
+    } else {
+        pg_sys::standard_ExecutorStart(query_desc, eflags);
+    }
+}
+
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_executor_end(query_desc: *mut pg_sys::QueryDesc) {
+    let observed = is_claimed(query_desc);
+    let recorded = crate::feedback::is_recording(query_desc);
+    if (observed || recorded) && !(*query_desc).planstate.is_null() {
+        let mut nodes = Vec::new();
+        collect_actuals((*query_desc).planstate, &mut nodes);
+
+        if recorded {
+            crate::feedback::finish(query_desc, &nodes);
+            crate::stats::record_q_errors(&crate::cardinality::q_errors(
+                (*query_desc).plannedstmt,
+                &nodes,
+            ));
+        }
+
+        if observed {
+            OBSERVATION.with(|observation| {
+                if let Some(observation) = observation.borrow_mut().as_mut() {
+                    observation.nodes = nodes;
+                }
+            });
+        }
+    }
+
+    if let Some(prev) = PREV_EXECUTOR_END_HOOK {
+        prev(query_desc);
+    } else {
+        pg_sys::standard_ExecutorEnd(query_desc);
+    }
+}
+
+pub unsafe fn register() {
+    PREV_EXECUTOR_START_HOOK = pg_sys::ExecutorStart_hook;
+    pg_sys::ExecutorStart_hook = Some(pgl_executor_start);
+
+    PREV_EXECUTOR_END_HOOK = pg_sys::ExecutorEnd_hook;
+    pg_sys::ExecutorEnd_hook = Some(pgl_executor_end);
+}
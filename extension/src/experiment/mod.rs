@@ -0,0 +1,96 @@
+use std::cell::Cell;
+use std::ffi::CStr;
+
+use pgrx::prelude::*;
+use pgrx::{pg_sys, GucSetting};
+
+use crate::utils::fnv1a_128;
+
+mod types;
+
+pub use self::types::{ExperimentGroup, PglExperimentAssignment};
+
+pub static PGL_EXPERIMENT_FRACTION: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static PGL_EXPERIMENT_ASSIGNMENT: GucSetting<PglExperimentAssignment> =
+    GucSetting::<PglExperimentAssignment>::new(PglExperimentAssignment::Hash);
+
+thread_local! {
+    /// Set while a statement assigned to the native group is planned.
+    static PLANNING_NATIVELY: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Assigns the statement being planned to a group, or returns `None` when no
+/// experiment is running.
+pub unsafe fn assign(
+    parse: *mut pg_sys::Query,
+    query_string: *const std::os::raw::c_char,
+) -> Option<ExperimentGroup> {
+    let fraction = PGL_EXPERIMENT_FRACTION.get();
+    if fraction >= 1.0 {
+        return None;
+    }
+
+    let draw = match PGL_EXPERIMENT_ASSIGNMENT.get() {
+        PglExperimentAssignment::Hash => query_draw(parse, query_string),
+        PglExperimentAssignment::Random => {
+            pg_sys::pg_prng_double(std::ptr::addr_of_mut!(pg_sys::pg_global_prng_state))
+        }
+    };
+
+    Some(if draw < fraction {
+        ExperimentGroup::Learned
+    } else {
+        ExperimentGroup::Native
+    })
+}
+
+/// Maps the query id, or the query text when no id was computed, to [0, 1).
+unsafe fn query_draw(parse: *mut pg_sys::Query, query_string: *const std::os::raw::c_char) -> f64 {
+    let hash = if (*parse).queryId != 0 {
+        fnv1a_128(&(*parse).queryId.to_le_bytes())
+    } else if !query_string.is_null() {
+        fnv1a_128(CStr::from_ptr(query_string).to_bytes())
+    } else {
+        0
+    };
+
+    (hash >> 75) as f64 / (1u64 << 53) as f64
+}
+
+/// Runs `f` with the learned components switched off.
+pub fn plan_natively<T>(f: impl FnOnce() -> T) -> T {
+    struct Reset(bool);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            PLANNING_NATIVELY.with(|native| native.set(self.0));
+        }
+    }
+
+    let _reset = Reset(PLANNING_NATIVELY.with(|native| native.replace(true)));
+    f()
+}
+
+/// Whether the statement being planned belongs to the native group.
+pub fn planning_natively() -> bool {
+    PLANNING_NATIVELY.with(|native| native.get())
+}
+
+extension_sql!(
+    "CREATE VIEW pgl.experiment_summary AS
+     SELECT experiment_group,
+            count(*) AS executions,
+            avg(execution_ms) AS mean_ms,
+            stddev_samp(execution_ms) AS stddev_ms,
+            avg(execution_ms) - 1.96 * stddev_samp(execution_ms) / sqrt(count(*)) AS mean_ms_ci_low,
+            avg(execution_ms) + 1.96 * stddev_samp(execution_ms) / sqrt(count(*)) AS mean_ms_ci_high,
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY execution_ms) AS p50_ms,
+            percentile_cont(0.95) WITHIN GROUP (ORDER BY execution_ms) AS p95_ms,
+            percentile_cont(0.99) WITHIN GROUP (ORDER BY execution_ms) AS p99_ms,
+            max(execution_ms) AS max_ms
+     FROM pgl.feedback_log
+     WHERE experiment_group IS NOT NULL
+     GROUP BY experiment_group;",
+    name = "create_experiment_summary_view",
+    requires = ["create_schema_pglearned", "create_feedback_log"]
+);
@@ -0,0 +1,21 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, pgrx::PostgresGucEnum)]
+pub enum PglExperimentAssignment {
+    Hash,
+    Random,
+}
+
+/// Which side of an experiment a statement was planned on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExperimentGroup {
+    Learned,
+    Native,
+}
+
+impl ExperimentGroup {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExperimentGroup::Learned => "learned",
+            ExperimentGroup::Native => "native",
+        }
+    }
+}
@@ -2,11 +2,125 @@ use pgrx::pg_sys;
 use pgrx::prelude::*;
 use std::ffi::CString;
 
+use crate::{cardinality, executor, planner};
+
 static mut PREV_EXPLAIN_PER_PLAN_HOOK: pg_sys::explain_per_plan_hook_type = None;
 static mut PREV_EXPLAIN_PER_NODE_HOOK: pg_sys::explain_per_node_hook_type = None;
+static mut PGL_EXPLAIN_EXTENSION_ID: i32 = -1;
 
 const PGL_SCHEMA_VERSION: i64 = 1;
 
+/// Handles `EXPLAIN (PGL [boolean])`.
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_explain_option(
+    es: *mut pg_sys::ExplainState,
+    opt: *mut pg_sys::DefElem,
+    _pstate: *mut pg_sys::ParseState,
+) {
+    let enabled = pg_sys::palloc0(std::mem::size_of::<bool>()) as *mut bool;
+    *enabled = pg_sys::defGetBoolean(opt);
+    pg_sys::SetExplainExtensionState(es, PGL_EXPLAIN_EXTENSION_ID, enabled.cast());
+}
+
+unsafe fn pgl_option_enabled(es: *mut pg_sys::ExplainState) -> bool {
+    let enabled = pg_sys::GetExplainExtensionState(es, PGL_EXPLAIN_EXTENSION_ID) as *mut bool;
+    !enabled.is_null() && *enabled
+}
+
+unsafe fn explain_property_text(label: &str, value: &str, es: *mut pg_sys::ExplainState) {
+    let label = CString::new(label).unwrap();
+    let value = CString::new(value).unwrap();
+    pg_sys::ExplainPropertyText(label.as_ptr(), value.as_ptr(), es);
+}
+
+unsafe fn explain_property_integer(label: &str, value: i64, es: *mut pg_sys::ExplainState) {
+    let label = CString::new(label).unwrap();
+    pg_sys::ExplainPropertyInteger(label.as_ptr(), std::ptr::null(), value, es);
+}
+
+/// Prints how the planner hook chose `plannedstmt`.
+unsafe fn explain_planner_decision(
+    plannedstmt: *mut pg_sys::PlannedStmt,
+    es: *mut pg_sys::ExplainState,
+) {
+    let Some(decision) = planner::planner_decision(plannedstmt) else {
+        return;
+    };
+
+    let method = format!("{:?}", decision.method).to_lowercase();
+    explain_property_text("Planner Method", &method, es);
+
+    if let Some(hints) = decision.hints.as_deref() {
+        explain_property_text("Hints", hints, es);
+    }
+
+    if let Some(rule) = decision.policy_rule {
+        explain_property_integer("Policy Rule", rule as i64, es);
+    }
+
+    if let Some(reason) = decision.trivial {
+        explain_property_text("Skipped", reason.as_str(), es);
+    }
+
+    if let Some(group) = decision.experiment_group {
+        explain_property_text("Experiment Group", group.as_str(), es);
+    }
+
+    if let Some(mode) = decision.mode {
+        explain_property_text("Planner Mode", &format!("{:?}", mode).to_lowercase(), es);
+    }
+
+    if let Some(arm) = decision.arm {
+        explain_property_integer("Arm", arm as i64, es);
+
+        let settings = planner::arm_settings(arm)
+            .into_iter()
+            .map(|(name, enabled)| format!("{}={}", name, if enabled { "on" } else { "off" }))
+            .collect::<Vec<_>>()
+            .join(", ");
+        explain_property_text("Arm Settings", &settings, es);
+    }
+
+    if let Some(model) = decision.model.as_ref() {
+        explain_property_text("Model", &model.name, es);
+        explain_property_integer("Model Version", model.version as i64, es);
+    }
+
+    if decision.pinned {
+        let pinned_label = CString::new("Pinned").unwrap();
+        pg_sys::ExplainPropertyBool(pinned_label.as_ptr(), true, es);
+    }
+
+    if let Some(shadow_arm) = decision.shadow_arm {
+        explain_property_integer("Shadow Arm", shadow_arm as i64, es);
+    }
+
+    explain_property_integer("Candidates", decision.candidates as i64, es);
+    explain_property_integer(
+        "Distinct Candidates",
+        decision.distinct_candidates as i64,
+        es,
+    );
+
+    if let Some(latency) = decision.remote_latency {
+        let label = CString::new("Remote Latency").unwrap();
+        let unit = CString::new("ms").unwrap();
+        pg_sys::ExplainPropertyFloat(
+            label.as_ptr(),
+            unit.as_ptr(),
+            latency.as_secs_f64() * 1000.0,
+            3,
+            es,
+        );
+    }
+
+    let fallback_label = CString::new("Fallback").unwrap();
+    pg_sys::ExplainPropertyBool(fallback_label.as_ptr(), decision.fallback.is_some(), es);
+    if let Some(reason) = decision.fallback.as_deref() {
+        explain_property_text("Fallback Reason", reason, es);
+    }
+}
+
 #[pg_guard]
 pub extern "C-unwind" fn pgl_explain_per_plan(
     plannedstmt: *mut pg_sys::PlannedStmt,
@@ -33,6 +147,10 @@ pub extern "C-unwind" fn pgl_explain_per_plan(
             es,
         );
 
+        if pgl_option_enabled(es) {
+            explain_planner_decision(plannedstmt, es);
+        }
+
         pg_sys::ExplainCloseGroup(group_name.as_ptr(), group_name.as_ptr(), true, es);
     }
 }
@@ -49,10 +167,88 @@ pub extern "C-unwind" fn pgl_explain_per_node(
         if let Some(prev) = PREV_EXPLAIN_PER_NODE_HOOK {
             prev(planstate, ancestors, relationship, plan_name, es);
         }
+
+        explain_node_estimate(planstate, es);
+    }
+}
+
+/// Prints the learned estimate applied to the relations of `planstate`.
+/// Nodes that only pass on their child's relations, such as Hash or Sort,
+/// are skipped so each estimate is shown once, on the scan or join.
+unsafe fn explain_node_estimate(planstate: *mut pg_sys::PlanState, es: *mut pg_sys::ExplainState) {
+    if planstate.is_null() || (*planstate).plan.is_null() {
+        return;
+    }
+
+    let relids = executor::plan_state_relids(planstate);
+    if relids.is_empty()
+        || executor::child_plan_states(planstate)
+            .into_iter()
+            .any(|child| executor::plan_state_relids(child) == relids)
+    {
+        return;
+    }
+
+    let Some(estimate) = cardinality::explained_estimate((*es).pstmt, &relids) else {
+        return;
+    };
+
+    let native_label = CString::new("PGL Native Rows").unwrap();
+    pg_sys::ExplainPropertyFloat(
+        native_label.as_ptr(),
+        std::ptr::null(),
+        estimate.native_rows,
+        0,
+        es,
+    );
+
+    // Shadow estimates were not applied, so the node shows the native rows.
+    let rows_label = if estimate.shadow {
+        CString::new("PGL Shadow Rows").unwrap()
+    } else {
+        CString::new("PGL Learned Rows").unwrap()
+    };
+    pg_sys::ExplainPropertyFloat(rows_label.as_ptr(), std::ptr::null(), estimate.rows, 0, es);
+
+    let source_label = CString::new("PGL Estimate Source").unwrap();
+    let source_value = CString::new(estimate.source.as_str()).unwrap();
+    pg_sys::ExplainPropertyText(source_label.as_ptr(), source_value.as_ptr(), es);
+
+    if let Some(model) = estimate.model.as_ref() {
+        let model_label = CString::new("PGL Model").unwrap();
+        let model_value = CString::new(model.name.as_str()).unwrap();
+        pg_sys::ExplainPropertyText(model_label.as_ptr(), model_value.as_ptr(), es);
+
+        let version_label = CString::new("PGL Model Version").unwrap();
+        pg_sys::ExplainPropertyInteger(
+            version_label.as_ptr(),
+            std::ptr::null(),
+            model.version as i64,
+            es,
+        );
+    }
+
+    let rows = estimate.rows;
+
+    let instrument = (*planstate).instrument;
+    if (*es).analyze && !instrument.is_null() {
+        pg_sys::InstrEndLoop(instrument);
+        if (*instrument).nloops > 0.0 {
+            // Estimates are per loop, as are the actual rows EXPLAIN shows.
+            let actual_rows = ((*instrument).ntuples / (*instrument).nloops).max(1.0);
+            let rows = rows.max(1.0);
+            let q_error = (rows / actual_rows).max(actual_rows / rows);
+
+            let q_error_label = CString::new("PGL Q-Error").unwrap();
+            pg_sys::ExplainPropertyFloat(q_error_label.as_ptr(), std::ptr::null(), q_error, 2, es);
+        }
     }
 }
 
 pub unsafe fn register() {
+    PGL_EXPLAIN_EXTENSION_ID = pg_sys::GetExplainExtensionId(c"pglearned".as_ptr());
+    pg_sys::RegisterExtensionExplainOption(c"pgl".as_ptr(), Some(pgl_explain_option));
+
     PREV_EXPLAIN_PER_PLAN_HOOK = pg_sys::explain_per_plan_hook;
     pg_sys::explain_per_plan_hook = Some(pgl_explain_per_plan);
 
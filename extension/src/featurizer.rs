@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+use std::ffi::CString;
+
+use pgrx::prelude::*;
+use pgrx::{pg_sys, GucSetting, JsonB};
+use serde::Serialize;
+use serde_json::Value;
+
+pub const PLAN_FEATURES_VERSION: u32 = 1;
+
+pub static PGL_PLAN_FEATURES: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+/// Node types with their own one-hot slot; any other type uses the last one.
+const NODE_TYPES: [&str; 42] = [
+    "Result",
+    "ProjectSet",
+    "ModifyTable",
+    "Append",
+    "Merge Append",
+    "Recursive Union",
+    "BitmapAnd",
+    "BitmapOr",
+    "Nested Loop",
+    "Merge Join",
+    "Hash Join",
+    "Seq Scan",
+    "Sample Scan",
+    "Gather",
+    "Gather Merge",
+    "Index Scan",
+    "Index Only Scan",
+    "Bitmap Index Scan",
+    "Bitmap Heap Scan",
+    "Tid Scan",
+    "Tid Range Scan",
+    "Subquery Scan",
+    "Function Scan",
+    "Table Function Scan",
+    "Values Scan",
+    "CTE Scan",
+    "Named Tuplestore Scan",
+    "WorkTable Scan",
+    "Foreign Scan",
+    "Custom Scan",
+    "Materialize",
+    "Memoize",
+    "Sort",
+    "Incremental Sort",
+    "Group",
+    "Aggregate",
+    "WindowAgg",
+    "Unique",
+    "SetOp",
+    "LockRows",
+    "Limit",
+    "Hash",
+];
+
+/// Numeric features after the one-hot slots, each `ln(1 + x)`.
+const NUMERIC_FEATURES: [(&str, &str); 3] = [
+    ("log_rows", "Plan Rows"),
+    ("log_total_cost", "Total Cost"),
+    ("log_startup_cost", "Startup Cost"),
+];
+
+const FEATURE_WIDTH: usize = NODE_TYPES.len() + 1 + NUMERIC_FEATURES.len();
+
+/// A plan as a binary tree for tree convolution, as in Bao and Neo. Row 0 of
+/// every array is padding that `left` and `right` point to for a missing
+/// child; the root is row 1 and rows are in preorder.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlanTensor {
+    pub version: u32,
+    pub features: Vec<Vec<f64>>,
+    pub left: Vec<u32>,
+    pub right: Vec<u32>,
+    /// OID of the scanned relation, 0 for none or unknown.
+    pub relations: Vec<u32>,
+    /// OID of the scanned index, 0 for none or unknown.
+    pub indexes: Vec<u32>,
+}
+
+/// Names of the columns of `PlanTensor::features`.
+pub fn feature_names() -> Vec<String> {
+    NODE_TYPES
+        .iter()
+        .map(|node_type| format!("node.{node_type}"))
+        .chain(std::iter::once("node.Other".to_string()))
+        .chain(NUMERIC_FEATURES.iter().map(|(name, _)| name.to_string()))
+        .collect()
+}
+
+fn log_value(node: &Value, key: &str) -> f64 {
+    node.get(key)
+        .and_then(Value::as_f64)
+        .unwrap_or(0.0)
+        .max(0.0)
+        .ln_1p()
+}
+
+struct Builder<'a> {
+    tensor: PlanTensor,
+    resolve: &'a dyn Fn(Option<&str>, &str) -> u32,
+}
+
+impl Builder<'_> {
+    fn oid(&self, node: &Value, key: &str) -> u32 {
+        let schema = node.get("Schema").and_then(Value::as_str);
+        node.get(key)
+            .and_then(Value::as_str)
+            .map_or(0, |name| (self.resolve)(schema, name))
+    }
+
+    fn push_row(&mut self, node: &Value, numeric: bool) -> usize {
+        let mut row = vec![0.0; FEATURE_WIDTH];
+        let node_type = node.get("Node Type").and_then(Value::as_str);
+        let slot = node_type
+            .and_then(|node_type| NODE_TYPES.iter().position(|known| *known == node_type))
+            .unwrap_or(NODE_TYPES.len());
+        row[slot] = 1.0;
+        let (relation, index) = if numeric {
+            for (i, (_, key)) in NUMERIC_FEATURES.iter().enumerate() {
+                row[NODE_TYPES.len() + 1 + i] = log_value(node, key);
+            }
+            (
+                self.oid(node, "Relation Name"),
+                self.oid(node, "Index Name"),
+            )
+        } else {
+            (0, 0)
+        };
+
+        self.tensor.features.push(row);
+        self.tensor.left.push(0);
+        self.tensor.right.push(0);
+        self.tensor.relations.push(relation);
+        self.tensor.indexes.push(index);
+        self.tensor.features.len() - 1
+    }
+
+    /// Adds `node` with `children` below it. Nodes with more than two
+    /// children keep the first as the left child and chain the rest to the
+    /// right through nodes of the same type without numeric features.
+    fn add(&mut self, node: &Value, children: &[Value], numeric: bool) -> usize {
+        let row = self.push_row(node, numeric);
+        match children {
+            [] => {}
+            [only] => {
+                let left = self.add_node(only);
+                self.tensor.left[row] = left as u32;
+            }
+            [first, second] => {
+                let left = self.add_node(first);
+                let right = self.add_node(second);
+                self.tensor.left[row] = left as u32;
+                self.tensor.right[row] = right as u32;
+            }
+            [first, rest @ ..] => {
+                let left = self.add_node(first);
+                let right = self.add(node, rest, false);
+                self.tensor.left[row] = left as u32;
+                self.tensor.right[row] = right as u32;
+            }
+        }
+        row
+    }
+
+    fn add_node(&mut self, node: &Value) -> usize {
+        let children = node
+            .get("Plans")
+            .and_then(Value::as_array)
+            .map_or(&[][..], Vec::as_slice);
+        self.add(node, children, true)
+    }
+}
+
+/// Converts EXPLAIN (FORMAT JSON) output, the object in it or its `Plan`
+/// node into a `PlanTensor`. `resolve` maps a schema and relation or index
+/// name to its OID.
+pub fn featurize(
+    explain: &Value,
+    resolve: &dyn Fn(Option<&str>, &str) -> u32,
+) -> anyhow::Result<PlanTensor> {
+    let explain = explain
+        .as_array()
+        .and_then(|items| items.first())
+        .unwrap_or(explain);
+    let root = explain.get("Plan").unwrap_or(explain);
+    if root.get("Node Type").is_none() {
+        anyhow::bail!("not an EXPLAIN (FORMAT JSON) plan");
+    }
+
+    let mut builder = Builder {
+        tensor: PlanTensor {
+            version: PLAN_FEATURES_VERSION,
+            features: vec![vec![0.0; FEATURE_WIDTH]],
+            left: vec![0],
+            right: vec![0],
+            relations: vec![0],
+            indexes: vec![0],
+        },
+        resolve,
+    };
+    builder.add_node(root);
+    Ok(builder.tensor)
+}
+
+/// Sums `tensor` into named features for the local planner models: the
+/// numeric features of the root, `nodes`, and per node type its count as
+/// `node.<type>` and its summed `log_rows` as `rows.<type>`.
+pub fn named_features(tensor: &PlanTensor) -> BTreeMap<String, f64> {
+    let names = feature_names();
+    let mut features = BTreeMap::new();
+    let Some(root) = tensor.features.get(1) else {
+        return features;
+    };
+    for (name, value) in names.iter().zip(root).skip(NODE_TYPES.len() + 1) {
+        features.insert(name.clone(), *value);
+    }
+
+    features.insert("nodes".to_string(), (tensor.features.len() - 1) as f64);
+    for row in &tensor.features[1..] {
+        let slot = row[..=NODE_TYPES.len()]
+            .iter()
+            .position(|value| *value == 1.0)
+            .unwrap_or(NODE_TYPES.len());
+        let node_type = &names[slot]["node.".len()..];
+        *features.entry(names[slot].clone()).or_insert(0.0) += 1.0;
+        *features.entry(format!("rows.{node_type}")).or_insert(0.0) += row[NODE_TYPES.len() + 1];
+    }
+
+    features
+}
+
+/// Looks a relation up in the current database, by the search path when
+/// EXPLAIN did not print its schema.
+pub fn relation_oid(schema: Option<&str>, name: &str) -> u32 {
+    let Ok(name) = CString::new(name) else {
+        return 0;
+    };
+
+    let oid = unsafe {
+        match schema.map(CString::new) {
+            Some(Ok(schema)) => {
+                let namespace = pg_sys::get_namespace_oid(schema.as_ptr(), true);
+                if namespace == pg_sys::InvalidOid {
+                    return 0;
+                }
+                pg_sys::get_relname_relid(name.as_ptr(), namespace)
+            }
+            Some(Err(_)) => return 0,
+            None => pg_sys::RelnameGetRelid(name.as_ptr()),
+        }
+    };
+    u32::from(oid)
+}
+
+/// `featurize` for the current database, as JSON.
+pub fn plan_features_json(explain: &Value) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(&featurize(explain, &relation_oid)?)?)
+}
+
+#[pg_extern]
+fn pgl_plan_features(plan: JsonB) -> anyhow::Result<JsonB> {
+    Ok(JsonB(serde_json::to_value(featurize(
+        &plan.0,
+        &relation_oid,
+    )?)?))
+}
+
+#[pg_extern]
+fn pgl_plan_feature_names() -> SetOfIterator<'static, String> {
+    SetOfIterator::new(feature_names())
+}
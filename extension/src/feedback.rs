@@ -0,0 +1,432 @@
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::time::{Duration, Instant};
+
+use pgrx::bgworkers::*;
+use pgrx::lwlock::PgLwLock;
+use pgrx::prelude::*;
+use pgrx::shmem::*;
+use pgrx::{pg_shmem_init, pg_sys, GucSetting};
+use serde_json::json;
+
+use crate::executor::{plan_fingerprint, NodeActuals};
+use crate::rpc::client::pgl_rpc::{ExecutedNode, ModelVersion, ReportExecutionRequest};
+use crate::rpc::report;
+use crate::{cardinality, planner, regression};
+
+/// Largest serialized record that fits in one queue slot.
+const FEEDBACK_SLOT_SIZE: usize = 16 * 1024;
+/// Records queued for the writer before new ones are dropped.
+const FEEDBACK_QUEUE_LEN: usize = 32;
+/// Longest query text kept in a record.
+const MAX_QUERY_TEXT: usize = 4 * 1024;
+
+pub static PGL_FEEDBACK: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static PGL_FEEDBACK_DATABASE: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(Some(c"postgres"));
+
+#[derive(Copy, Clone)]
+pub struct FeedbackSlot {
+    len: usize,
+    data: [u8; FEEDBACK_SLOT_SIZE],
+}
+
+unsafe impl PGRXSharedMemory for FeedbackSlot {}
+
+#[derive(Copy, Clone)]
+pub struct FeedbackQueue {
+    slots: heapless::Vec<FeedbackSlot, FEEDBACK_QUEUE_LEN>,
+    dropped: u64,
+}
+
+impl Default for FeedbackQueue {
+    fn default() -> Self {
+        Self {
+            slots: heapless::Vec::new(),
+            dropped: 0,
+        }
+    }
+}
+
+unsafe impl PGRXSharedMemory for FeedbackQueue {}
+
+static FEEDBACK_QUEUE: PgLwLock<FeedbackQueue> = unsafe { PgLwLock::new(c"pgl_feedback_queue") };
+
+/// Whether the queue is shared and has a writer, i.e. the library was
+/// preloaded.
+static mut FEEDBACK_SHARED: bool = false;
+
+struct ActiveExecution {
+    query_desc: *mut pg_sys::QueryDesc,
+    /// The subtransaction the execution began in.
+    subxact: pg_sys::SubTransactionId,
+    started: Instant,
+    /// Write a record to `pgl.feedback_log`.
+    log: bool,
+    /// Send a `ReportExecution` RPC to the model server.
+    report: bool,
+}
+
+thread_local! {
+    /// The top-level statement being executed with feedback or reporting on.
+    /// Statements run by functions inside it are not recorded on their own.
+    static ACTIVE: RefCell<Option<ActiveExecution>> = const { RefCell::new(None) };
+
+    /// This backend's records when there is no shared queue, until
+    /// `pgl_feedback_flush()` writes them.
+    static LOCAL_QUEUE: RefCell<Box<FeedbackQueue>> = RefCell::new(Box::default());
+}
+
+fn with_queue<T>(f: impl FnOnce(&mut FeedbackQueue) -> T) -> T {
+    if unsafe { FEEDBACK_SHARED } {
+        f(&mut FEEDBACK_QUEUE.exclusive())
+    } else {
+        LOCAL_QUEUE.with(|queue| f(&mut queue.borrow_mut()))
+    }
+}
+
+/// Called from ExecutorStart; returns whether `query_desc` needs row
+/// instrumentation for its feedback record or execution report.
+pub unsafe fn begin(query_desc: *mut pg_sys::QueryDesc, eflags: i32) -> bool {
+    if eflags & pg_sys::EXEC_FLAG_EXPLAIN_ONLY as i32 != 0 {
+        return false;
+    }
+
+    let log = PGL_FEEDBACK.get();
+    let report = report::enabled();
+    if !log && !report {
+        return false;
+    }
+
+    ACTIVE.with(|active| {
+        let mut active = active.borrow_mut();
+        if active.is_some() {
+            return false;
+        }
+
+        *active = Some(ActiveExecution {
+            query_desc,
+            subxact: pg_sys::GetCurrentSubTransactionId(),
+            started: Instant::now(),
+            log,
+            report,
+        });
+        true
+    })
+}
+
+/// Whether `query_desc` is the execution a feedback record or report is
+/// built for.
+pub fn is_recording(query_desc: *mut pg_sys::QueryDesc) -> bool {
+    ACTIVE.with(|active| {
+        active
+            .borrow()
+            .as_ref()
+            .is_some_and(|execution| execution.query_desc == query_desc)
+    })
+}
+
+/// Called from ExecutorEnd, with the actuals of the finished execution.
+pub unsafe fn finish(query_desc: *mut pg_sys::QueryDesc, nodes: &[NodeActuals]) {
+    if !is_recording(query_desc) {
+        return;
+    }
+
+    let Some(execution) = ACTIVE.with(|active| active.borrow_mut().take()) else {
+        return;
+    };
+
+    let execution_time = execution.started.elapsed();
+    if execution.log {
+        enqueue(&feedback_record(query_desc, execution_time, nodes));
+    }
+    if execution.report {
+        let stmt = (*query_desc).plannedstmt;
+        let query = crate::utils::statement_at(
+            (*query_desc).sourceText,
+            (*stmt).stmt_location,
+            (*stmt).stmt_len,
+        );
+        report::report(execution_report(query_desc, execution_time, nodes), &query);
+    }
+}
+
+unsafe fn feedback_record(
+    query_desc: *mut pg_sys::QueryDesc,
+    execution_time: Duration,
+    nodes: &[NodeActuals],
+) -> serde_json::Value {
+    let stmt = (*query_desc).plannedstmt;
+    let decision = planner::planner_decision(stmt);
+
+    let mut query =
+        crate::utils::cstr_to_string((*query_desc).sourceText as *mut _).unwrap_or_default();
+    if query.len() > MAX_QUERY_TEXT {
+        let mut end = MAX_QUERY_TEXT;
+        while !query.is_char_boundary(end) {
+            end -= 1;
+        }
+        query.truncate(end);
+    }
+
+    let database = pg_sys::get_database_name(pg_sys::MyDatabaseId);
+
+    json!({
+        "database": crate::utils::cstr_to_string(database),
+        "query_id": (*stmt).queryId as i64,
+        "planner_method": decision.as_ref().map(|d| format!("{:?}", d.method).to_lowercase()),
+        "planner_mode": decision.as_ref().and_then(|d| d.mode).map(|m| format!("{:?}", m).to_lowercase()),
+        "arm": decision.as_ref().and_then(|d| d.arm),
+        "shadow_arm": decision.as_ref().and_then(|d| d.shadow_arm),
+        "pinned": decision.as_ref().is_some_and(|d| d.pinned),
+        "fallback": decision.as_ref().is_some_and(|d| d.fallback.is_some()),
+        "experiment_group": decision.as_ref().and_then(|d| d.experiment_group).map(|g| g.as_str()),
+        "model": decision.as_ref().and_then(|d| d.model.as_ref()).map(|m| m.name.clone()),
+        "model_version": decision.as_ref().and_then(|d| d.model.as_ref()).map(|m| m.version),
+        "execution_ms": execution_time.as_secs_f64() * 1000.0,
+        "estimates": cardinality::applied_estimates(stmt),
+        "nodes": nodes,
+        "query": query,
+    })
+}
+
+unsafe fn execution_report(
+    query_desc: *mut pg_sys::QueryDesc,
+    execution_time: Duration,
+    nodes: &[NodeActuals],
+) -> ReportExecutionRequest {
+    let stmt = (*query_desc).plannedstmt;
+    let decision = planner::planner_decision(stmt);
+
+    ReportExecutionRequest {
+        query_id: (*stmt).queryId as i64,
+        plan: decision
+            .as_ref()
+            .and_then(|d| d.plan.clone())
+            .unwrap_or_default(),
+        plan_fingerprint: plan_fingerprint(nodes),
+        arm: decision.as_ref().and_then(|d| d.arm).unwrap_or(-1),
+        planning_ms: decision
+            .as_ref()
+            .and_then(|d| d.planning_time)
+            .map_or(0.0, |t| t.as_secs_f64() * 1000.0),
+        execution_ms: execution_time.as_secs_f64() * 1000.0,
+        nodes: nodes
+            .iter()
+            .map(|node| ExecutedNode {
+                node_type: node.node_type.clone(),
+                relids: node.relids.clone(),
+                plan_rows: node.plan_rows,
+                actual_rows: node.actual_rows,
+                loops: node.loops,
+            })
+            .collect(),
+        model: decision
+            .as_ref()
+            .and_then(|d| d.model.as_ref())
+            .map(ModelVersion::from),
+    }
+}
+
+fn enqueue(record: &serde_json::Value) {
+    let Ok(bytes) = serde_json::to_vec(record) else {
+        return;
+    };
+
+    with_queue(|queue| {
+        if bytes.len() > FEEDBACK_SLOT_SIZE || queue.slots.is_full() {
+            queue.dropped += 1;
+            return;
+        }
+
+        let mut slot = FeedbackSlot {
+            len: bytes.len(),
+            data: [0; FEEDBACK_SLOT_SIZE],
+        };
+        slot.data[..bytes.len()].copy_from_slice(&bytes);
+        let _ = queue.slots.push(slot);
+    })
+}
+
+fn drain() -> (Vec<String>, u64) {
+    with_queue(|queue| {
+        let records = queue
+            .slots
+            .iter()
+            .map(|slot| String::from_utf8_lossy(&slot.data[..slot.len]).into_owned())
+            .collect();
+        queue.slots.clear();
+
+        let dropped = std::mem::take(&mut queue.dropped);
+        (records, dropped)
+    })
+}
+
+/// Forgets the active execution when its transaction aborts, since
+/// ExecutorEnd is never called for it.
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_feedback_xact_callback(
+    event: pg_sys::XactEvent::Type,
+    _arg: *mut std::os::raw::c_void,
+) {
+    if event == pg_sys::XactEvent::XACT_EVENT_ABORT
+        || event == pg_sys::XactEvent::XACT_EVENT_PARALLEL_ABORT
+    {
+        ACTIVE.with(|active| active.borrow_mut().take());
+    }
+}
+
+/// Forgets the active execution when the subtransaction it began in aborts.
+/// Errors caught inside it, e.g. by a PL/pgSQL exception block, abort only
+/// their own subtransaction and leave it running.
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_feedback_subxact_callback(
+    event: pg_sys::SubXactEvent::Type,
+    my_subid: pg_sys::SubTransactionId,
+    _parent_subid: pg_sys::SubTransactionId,
+    _arg: *mut std::os::raw::c_void,
+) {
+    if event == pg_sys::SubXactEvent::SUBXACT_EVENT_ABORT_SUB {
+        ACTIVE.with(|active| {
+            let mut active = active.borrow_mut();
+            if active
+                .as_ref()
+                .is_some_and(|execution| execution.subxact >= my_subid)
+            {
+                active.take();
+            }
+        });
+    }
+}
+
+extension_sql!(
+    "CREATE TABLE IF NOT EXISTS pgl.feedback_log (
+         id BIGSERIAL PRIMARY KEY,
+         logged_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+         database TEXT,
+         query_id BIGINT,
+         planner_method TEXT,
+         planner_mode TEXT,
+         arm INT,
+         shadow_arm INT,
+         pinned BOOLEAN,
+         fallback BOOLEAN,
+         experiment_group TEXT,
+         model TEXT,
+         model_version INT,
+         execution_ms FLOAT8,
+         estimates JSONB,
+         nodes JSONB,
+         query TEXT
+     );",
+    name = "create_feedback_log",
+    requires = ["create_schema_pglearned"]
+);
+
+const INSERT_FEEDBACK_SQL: &str = "INSERT INTO pgl.feedback_log
+         (database, query_id, planner_method, planner_mode, arm, shadow_arm,
+          pinned, fallback, experiment_group, model, model_version, execution_ms, estimates,
+          nodes, query)
+     SELECT r->>'database', (r->>'query_id')::bigint, r->>'planner_method',
+            r->>'planner_mode', (r->>'arm')::int, (r->>'shadow_arm')::int,
+            (r->>'pinned')::boolean, (r->>'fallback')::boolean, r->>'experiment_group',
+            r->>'model', (r->>'model_version')::int, (r->>'execution_ms')::float8, r->'estimates', r->'nodes', r->>'query'
+     FROM (SELECT $1::jsonb AS r) AS record";
+
+/// Writes queued feedback into `pgl.feedback_log` of `pgl.feedback_database`.
+/// Backends cannot always write themselves, e.g. in read-only transactions,
+/// so a single worker does it for them.
+#[pg_guard]
+#[no_mangle]
+pub extern "C-unwind" fn pgl_feedback_worker_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+
+    let database = PGL_FEEDBACK_DATABASE
+        .get()
+        .map(|database| database.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "postgres".to_string());
+    BackgroundWorker::connect_worker_to_spi(Some(&database), None);
+
+    while BackgroundWorker::wait_latch(Some(Duration::from_secs(1))) {
+        if BackgroundWorker::sighup_received() {
+            unsafe { pg_sys::ProcessConfigFile(pg_sys::GucContext::PGC_SIGHUP) };
+        }
+
+        let (records, dropped) = drain();
+        if dropped > 0 {
+            pgrx::log!("pgl: dropped {dropped} feedback records because the queue was full");
+        }
+        if records.is_empty() {
+            continue;
+        }
+
+        BackgroundWorker::transaction(|| {
+            let installed =
+                Spi::get_one::<bool>("SELECT to_regclass('pgl.feedback_log') IS NOT NULL")
+                    .ok()
+                    .flatten()
+                    .unwrap_or(false);
+            if !installed {
+                pgrx::log!(
+                    "pgl: discarding {} feedback records, pgl is not installed in database {database}",
+                    records.len()
+                );
+                return;
+            }
+
+            write_records(&records);
+        });
+    }
+}
+
+/// Writes `records` into `pgl.feedback_log` and folds them into the plan
+/// latencies the regression guard compares.
+fn write_records(records: &[String]) {
+    for record in records {
+        let args = vec![pgrx::datum::DatumWithOid::from(record.as_str())];
+        if let Err(err) = Spi::run_with_args(INSERT_FEEDBACK_SQL, &args) {
+            pgrx::log!("pgl: failed to write feedback record: {err}");
+        }
+        if let Err(err) = regression::observe(record, planner::NATIVE_ARM) {
+            pgrx::log!("pgl: failed to record plan latency: {err}");
+        }
+    }
+}
+
+/// Writes the records this backend queued into `pgl.feedback_log` of the
+/// current database and returns how many were written. Only needed when pgl
+/// is not preloaded; otherwise the feedback writer drains the shared queue.
+#[pg_extern]
+fn pgl_feedback_flush() -> i64 {
+    if unsafe { FEEDBACK_SHARED } {
+        return 0;
+    }
+
+    let (records, dropped) = drain();
+    if dropped > 0 {
+        pgrx::log!("pgl: dropped {dropped} feedback records because the queue was full");
+    }
+    write_records(&records);
+    records.len() as i64
+}
+
+/// Sets up the feedback queue and writer; only possible while the library is
+/// being preloaded.
+pub unsafe fn register() {
+    pg_sys::RegisterXactCallback(Some(pgl_feedback_xact_callback), std::ptr::null_mut());
+    pg_sys::RegisterSubXactCallback(Some(pgl_feedback_subxact_callback), std::ptr::null_mut());
+
+    if !pg_sys::process_shared_preload_libraries_in_progress {
+        return;
+    }
+
+    pg_shmem_init!(FEEDBACK_QUEUE);
+
+    BackgroundWorkerBuilder::new("pgl feedback writer")
+        .set_function("pgl_feedback_worker_main")
+        .set_library("pgl")
+        .enable_spi_access()
+        .load();
+
+    FEEDBACK_SHARED = true;
+}
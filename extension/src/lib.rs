@@ -4,9 +4,19 @@ use pgrx::{GucContext, GucFlags, GucRegistry};
 
 mod cardinality;
 mod datasets;
+mod executor;
+mod experiment;
 mod explain;
+mod featurizer;
+mod feedback;
+mod models;
 mod planner;
+mod policy;
+mod regression;
 mod rpc;
+mod shadow;
+mod stats;
+mod trivial;
 mod utils;
 
 pg_module_magic!(name, version);
@@ -52,6 +62,78 @@ pub extern "C-unwind" fn _PG_init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_string_guc(
+        c"pgl.remote_planner_url",
+        c"The remote server url for plan selection",
+        c"Overrides pgl.remote_server_url for ChoosePlan and ReportExecution",
+        &rpc::PGL_REMOTE_PLANNER_URL,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"pgl.remote_cardinality_url",
+        c"The remote server url for cardinality estimation",
+        c"Overrides pgl.remote_server_url for CardinalityEstimate",
+        &rpc::PGL_REMOTE_CARDINALITY_URL,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        c"pgl.remote_transport",
+        c"How backends reach the remote server",
+        c"Connect from each backend (direct) or through the remote worker's shared connection (worker)",
+        &rpc::PGL_REMOTE_TRANSPORT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"pgl.remote_tls_ca",
+        c"CA certificate for https remote servers",
+        c"PEM file used to verify https:// remote servers instead of the bundled web PKI roots",
+        &rpc::PGL_REMOTE_TLS_CA,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"pgl.remote_tls_cert",
+        c"Client certificate for https remote servers",
+        c"PEM file presented to https:// remote servers together with pgl.remote_tls_key",
+        &rpc::PGL_REMOTE_TLS_CERT,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"pgl.remote_tls_key",
+        c"Client key for https remote servers",
+        c"PEM file with the private key of pgl.remote_tls_cert",
+        &rpc::PGL_REMOTE_TLS_KEY,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.remote_worker",
+        c"Start the remote worker",
+        c"Starts a background worker that keeps one connection per remote server for pgl.remote_transport = worker",
+        &rpc::PGL_REMOTE_WORKER,
+        GucContext::Postmaster,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"pgl.traceparent",
+        c"W3C traceparent sent with requests to the model servers",
+        c"A traceparent='...' in a comment of the query takes precedence; invalid values are not sent",
+        &rpc::metadata::PGL_TRACEPARENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
     GucRegistry::define_bool_guc(
         c"pgl.enable_remote_cardinality",
         c"Enable remote cardinality estimation",
@@ -61,6 +143,86 @@ pub extern "C-unwind" fn _PG_init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_enum_guc(
+        c"pgl.cardinality_partition_mode",
+        c"How partitioned tables are estimated",
+        c"Estimate each surviving partition (children) or only the partitioned parent (parent)",
+        &cardinality::PGL_CARDINALITY_PARTITION_MODE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        c"pgl.cardinality_source",
+        c"Where injected cardinalities come from",
+        c"Ask the remote server (remote) or read pgl.cardinality_overrides (table)",
+        &cardinality::PGL_CARDINALITY_SOURCE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"pgl.cardinality_model",
+        c"The model in pgl.models used by cardinality sources model and remote",
+        c"Its active version is used; a remote model is asked at its own endpoint, if it has one",
+        &models::PGL_CARDINALITY_MODEL,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"pgl.cardinality_min_rows",
+        c"Smallest remote cardinality applied",
+        c"Remote estimates below this many rows are raised to it",
+        &cardinality::PGL_CARDINALITY_MIN_ROWS,
+        0.0,
+        f64::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.cardinality_clamp_to_cartesian",
+        c"Cap remote cardinalities at the largest possible result",
+        c"Caps base relations at their tuple count and joins at the product of their base relations' tuple counts",
+        &cardinality::PGL_CARDINALITY_CLAMP_TO_CARTESIAN,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"pgl.cardinality_max_ratio",
+        c"Largest accepted ratio between remote and native cardinalities",
+        c"Remote estimates further than this factor from PostgreSQL's are rejected; 0 disables the check",
+        &cardinality::PGL_CARDINALITY_MAX_RATIO,
+        0.0,
+        f64::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"pgl.cardinality_blend",
+        c"Weight of remote cardinalities in a geometric blend",
+        c"1 applies remote estimates as is, 0 keeps PostgreSQL's, values between blend them geometrically",
+        &cardinality::PGL_CARDINALITY_BLEND,
+        0.0,
+        1.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"pgl.cardinality_cache_size",
+        c"Remote cardinalities cached per backend",
+        c"Caches remote estimates by query hash and state key across planning runs; 0 disables the cache",
+        &cardinality::PGL_CARDINALITY_CACHE_SIZE,
+        0,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
     GucRegistry::define_enum_guc(
         c"pgl.planner_mode",
         c"The planner mode",
@@ -70,9 +232,208 @@ pub extern "C-unwind" fn _PG_init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_bool_guc(
+        c"pgl.planner_fallback",
+        c"Use the native plan when the learned planner fails",
+        c"When off, a failing remote server or planner model raises an ERROR; shadow mode always falls back",
+        &planner::PGL_PLANNER_FALLBACK,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"pgl.planner_model",
+        c"The model in pgl.models used by planner modes model and remote",
+        c"Its active version is used; a remote model is asked at its own endpoint, if it has one",
+        &models::PGL_PLANNER_MODEL,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"pgl.min_relations",
+        c"Fewest base relations a statement needs for the learned components",
+        c"Statements scanning fewer relations are planned by PostgreSQL alone; 0 disables the check",
+        &trivial::PGL_MIN_RELATIONS,
+        0,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"pgl.min_native_cost",
+        c"Lowest native plan cost a statement needs for the learned components",
+        c"Statements whose native plan is cheaper keep it; 0 disables the check, which costs an extra native planning run",
+        &trivial::PGL_MIN_NATIVE_COST,
+        0.0,
+        f64::MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.skip_catalog_queries",
+        c"Leave statements scanning only system catalogs to PostgreSQL",
+        c"Covers relations in pg_catalog and information_schema, e.g. from psql's \\d or pg_dump",
+        &trivial::PGL_SKIP_CATALOG_QUERIES,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.skip_utility_statements",
+        c"Leave queries planned by utility statements to PostgreSQL",
+        c"Covers e.g. CREATE TABLE AS, COPY and REFRESH MATERIALIZED VIEW, but not EXPLAIN, EXECUTE, DECLARE, DO or CALL",
+        &trivial::PGL_SKIP_UTILITY_STATEMENTS,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.plan_features",
+        c"Send pgl_plan_features of every candidate to the remote planner",
+        c"Fills the features field of ChoosePlan requests",
+        &featurizer::PGL_PLAN_FEATURES,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.shadow",
+        c"Ask the learned components without acting on their output",
+        c"Remote plan choices and cardinality estimates are recorded in EXPLAIN and pgl.shadow_decisions, but PostgreSQL's plan and estimates are used",
+        &shadow::PGL_SHADOW,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"pgl.experiment_fraction",
+        c"Fraction of statements planned with the learned components",
+        c"The rest are planned by PostgreSQL alone; 1 runs no experiment",
+        &experiment::PGL_EXPERIMENT_FRACTION,
+        0.0,
+        1.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        c"pgl.experiment_assignment",
+        c"How statements are assigned to an experiment group",
+        c"hash keeps each query in one group by its query id; random draws a group for every statement",
+        &experiment::PGL_EXPERIMENT_ASSIGNMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.regression_guard",
+        c"Pin queries whose learned plans got slower than the native plan",
+        c"Needs pgl.feedback; pinned queries are planned with their fastest observed arm until the pin expires",
+        &regression::PGL_REGRESSION_GUARD,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        c"pgl.regression_factor",
+        c"How much slower than native a learned plan may be before its query is pinned",
+        c"Compared on the mean execution time of each arm",
+        &regression::PGL_REGRESSION_FACTOR,
+        1.0,
+        f64::MAX,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"pgl.regression_min_executions",
+        c"Executions of an arm needed before its latency is compared",
+        c"Both the learned and the native arm need this many executions",
+        &regression::PGL_REGRESSION_MIN_EXECUTIONS,
+        1,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"pgl.regression_pin_duration",
+        c"How long a regressed query stays pinned",
+        c"After this the learned planner is asked again",
+        &regression::PGL_REGRESSION_PIN_DURATION,
+        1,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_S,
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.feedback",
+        c"Record executed plans to pgl.feedback_log",
+        c"Queues the planner decision, applied estimates and per-node actuals of each top-level statement",
+        &feedback::PGL_FEEDBACK,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        c"pgl.feedback_database",
+        c"Database the feedback writer connects to",
+        c"The feedback log of every database is written to pgl.feedback_log in this database",
+        &feedback::PGL_FEEDBACK_DATABASE,
+        GucContext::Postmaster,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        c"pgl.report_execution",
+        c"Report executed queries to the remote server",
+        c"Sends the arm, planning and execution time and per-node actual rows of each top-level statement via ReportExecution",
+        &rpc::report::PGL_REPORT_EXECUTION,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        c"pgl.metrics_port",
+        c"Port of the OpenMetrics endpoint on localhost",
+        c"Needs pgl in shared_preload_libraries; 0 disables the exporter, pgl_metrics() returns the same text",
+        &stats::metrics::PGL_METRICS_PORT,
+        0,
+        65535,
+        GucContext::Postmaster,
+        GucFlags::default(),
+    );
+
     unsafe {
+        // Override lookups are keyed by the query id, which is only computed
+        // when something asks for it.
+        pg_sys::EnableQueryId();
+
         explain::register();
         planner::register();
+        policy::register();
+        trivial::register();
         cardinality::register();
+        executor::register();
+        feedback::register();
+        rpc::register();
+        shadow::register();
+        stats::register();
+    }
+}
+
+/// Required by `cargo pgrx test`.
+#[cfg(test)]
+pub mod pg_test {
+    pub fn setup(_options: Vec<&str>) {}
+
+    /// The remote worker needs the library preloaded, which the regression
+    /// tests cannot do.
+    pub fn postgresql_conf_options() -> Vec<&'static str> {
+        vec!["shared_preload_libraries = 'pgl'", "pgl.remote_worker = on"]
     }
 }
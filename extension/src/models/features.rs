@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+/// Named model inputs. Models look features up by name, so adding one does
+/// not invalidate models trained before it existed.
+pub type Features = BTreeMap<String, f64>;
+
+fn log_value(value: Option<&Value>) -> f64 {
+    value
+        .and_then(Value::as_f64)
+        .unwrap_or(0.0)
+        .max(0.0)
+        .ln_1p()
+}
+
+fn add(features: &mut Features, name: String, value: f64) {
+    *features.entry(name).or_insert(0.0) += value;
+}
+
+/// Features of a relation subset, from its cardinality payload: PostgreSQL's
+/// log estimate, the size of the subset and the tables it covers.
+pub fn cardinality_features(payload: &Value) -> Features {
+    let mut features = Features::new();
+    let count = |key: &str| {
+        payload
+            .get(key)
+            .and_then(Value::as_array)
+            .map_or(0.0, |items| items.len() as f64)
+    };
+
+    features.insert("native_rows".into(), log_value(payload.get("rows")));
+    features.insert("tuples".into(), log_value(payload.get("tuples")));
+    features.insert("relations".into(), count("relids"));
+    features.insert("filters".into(), count("filters"));
+    features.insert("joins".into(), count("joins"));
+
+    if let Some(kind) = payload.get("kind").and_then(Value::as_str) {
+        features.insert(format!("kind.{kind}"), 1.0);
+    }
+    if let Some(join_type) = payload.get("join_type").and_then(Value::as_str) {
+        features.insert(format!("join_type.{join_type}"), 1.0);
+    }
+    for name in payload
+        .get("relation_names")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        add(&mut features, format!("table.{name}"), 1.0);
+    }
+
+    features
+}
@@ -0,0 +1,82 @@
+use serde::Deserialize;
+
+use super::features::Features;
+
+/// Gradient-boosted regression trees: `base_score` plus the leaf each tree
+/// reaches.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GbdtModel {
+    #[serde(default)]
+    base_score: f64,
+    trees: Vec<Tree>,
+}
+
+/// Nodes are stored flat with the root first. Splits send features below
+/// `threshold` to `left`; absent features count as 0.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Tree {
+    nodes: Vec<TreeNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum TreeNode {
+    Split {
+        feature: String,
+        threshold: f64,
+        left: usize,
+        right: usize,
+    },
+    Leaf {
+        leaf: f64,
+    },
+}
+
+impl GbdtModel {
+    /// Rejects trees whose walk could leave the node list or loop.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (t, tree) in self.trees.iter().enumerate() {
+            if tree.nodes.is_empty() {
+                anyhow::bail!("tree {t} has no nodes");
+            }
+            for (i, node) in tree.nodes.iter().enumerate() {
+                if let TreeNode::Split { left, right, .. } = node {
+                    for child in [*left, *right] {
+                        if child <= i || child >= tree.nodes.len() {
+                            anyhow::bail!("node {i} of tree {t} has an invalid child {child}");
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn predict(&self, features: &Features) -> f64 {
+        self.trees
+            .iter()
+            .map(|tree| tree.predict(features))
+            .sum::<f64>()
+            + self.base_score
+    }
+}
+
+impl Tree {
+    fn predict(&self, features: &Features) -> f64 {
+        let mut i = 0;
+        loop {
+            match &self.nodes[i] {
+                TreeNode::Leaf { leaf } => return *leaf,
+                TreeNode::Split {
+                    feature,
+                    threshold,
+                    left,
+                    right,
+                } => {
+                    let value = features.get(feature).copied().unwrap_or(0.0);
+                    i = if value < *threshold { *left } else { *right };
+                }
+            }
+        }
+    }
+}
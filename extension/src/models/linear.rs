@@ -0,0 +1,23 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::features::Features;
+
+/// `bias + sum(weight * feature)`; features without a weight are ignored.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LinearModel {
+    #[serde(default)]
+    bias: f64,
+    weights: HashMap<String, f64>,
+}
+
+impl LinearModel {
+    pub fn predict(&self, features: &Features) -> f64 {
+        self.weights
+            .iter()
+            .map(|(name, weight)| weight * features.get(name).copied().unwrap_or(0.0))
+            .sum::<f64>()
+            + self.bias
+    }
+}
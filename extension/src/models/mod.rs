@@ -0,0 +1,302 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::rc::Rc;
+
+use pgrx::prelude::*;
+use pgrx::{pg_sys, GucSetting, JsonB};
+use serde::Serialize;
+
+use crate::utils::internal_lookup;
+
+use self::features::{cardinality_features, Features};
+use self::gbdt::GbdtModel;
+use self::linear::LinearModel;
+
+mod features;
+mod gbdt;
+mod linear;
+mod registry;
+
+pub static PGL_PLANNER_MODEL: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(None);
+pub static PGL_CARDINALITY_MODEL: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(None);
+
+/// A model from `pgl.models`, evaluated in the backend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Model {
+    Linear(LinearModel),
+    Gbdt(GbdtModel),
+}
+
+impl Model {
+    fn parse(kind: &str, model: serde_json::Value) -> anyhow::Result<Self> {
+        Ok(match kind {
+            "linear" => Model::Linear(serde_json::from_value(model)?),
+            "gbdt" => {
+                let model: GbdtModel = serde_json::from_value(model)?;
+                model.validate()?;
+                Model::Gbdt(model)
+            }
+            _ => anyhow::bail!("unknown model kind {kind}"),
+        })
+    }
+
+    pub fn predict(&self, features: &Features) -> f64 {
+        match self {
+            Model::Linear(model) => model.predict(features),
+            Model::Gbdt(model) => model.predict(features),
+        }
+    }
+}
+
+/// Identifies one version of a registered model, e.g. in feedback.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ModelVersion {
+    pub name: String,
+    pub version: i32,
+}
+
+/// The active version of a model in `pgl.models`.
+#[derive(Debug)]
+pub struct ActiveModel {
+    pub name: String,
+    pub version: i32,
+    pub component: String,
+    /// Where a remote model is served, when not at the configured URL.
+    endpoint: Option<String>,
+    /// The parsed artifact of a local model.
+    model: Option<Model>,
+}
+
+impl ActiveModel {
+    pub fn version(&self) -> ModelVersion {
+        ModelVersion {
+            name: self.name.clone(),
+            version: self.version,
+        }
+    }
+
+    /// The model to evaluate in the backend.
+    pub fn local(&self) -> anyhow::Result<&Model> {
+        self.model.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "model {} version {} is a remote model, not a local one",
+                self.name,
+                self.version
+            )
+        })
+    }
+
+    /// The endpoint of a remote model, if it has its own.
+    pub fn remote(&self) -> anyhow::Result<Option<&str>> {
+        if self.model.is_some() {
+            anyhow::bail!(
+                "model {} version {} is a local model, not a remote one",
+                self.name,
+                self.version
+            );
+        }
+        Ok(self.endpoint.as_deref())
+    }
+}
+
+struct LoadedModel {
+    /// Identifies the row the model was parsed from.
+    ident: String,
+    /// Start of the statement that last checked `ident`.
+    checked_at: pg_sys::TimestampTz,
+    model: Rc<ActiveModel>,
+}
+
+thread_local! {
+    static MODELS: RefCell<HashMap<String, LoadedModel>> = RefCell::new(HashMap::new());
+}
+
+fn active_ident(name: &str) -> anyhow::Result<Option<String>> {
+    internal_lookup(|| {
+        Spi::connect(|client| {
+            let args = vec![pgrx::datum::DatumWithOid::from(name)];
+            let res = client.select(
+                "SELECT xmin::text || ':' || ctid::text FROM pgl.models WHERE name = $1 AND active",
+                Some(1),
+                &args,
+            )?;
+            if res.is_empty() {
+                return Ok(None);
+            }
+            res.first().get_one::<String>()
+        })
+    })
+    .map_err(anyhow::Error::from)
+}
+
+struct ModelRow {
+    ident: String,
+    version: i32,
+    component: String,
+    kind: String,
+    model: serde_json::Value,
+    endpoint: Option<String>,
+}
+
+fn read_active(name: &str) -> anyhow::Result<Option<ModelRow>> {
+    internal_lookup(|| {
+        Spi::connect(|client| {
+            let args = vec![pgrx::datum::DatumWithOid::from(name)];
+            let res = client.select(
+                "SELECT xmin::text || ':' || ctid::text AS ident, version, component, kind, model,
+                        endpoint
+                 FROM pgl.models WHERE name = $1 AND active",
+                Some(1),
+                &args,
+            )?;
+            let Some(row) = res.into_iter().next() else {
+                return Ok(None);
+            };
+            Ok(Some(ModelRow {
+                ident: row.get_by_name::<String, _>("ident")?.unwrap_or_default(),
+                version: row.get_by_name::<i32, _>("version")?.unwrap_or_default(),
+                component: row
+                    .get_by_name::<String, _>("component")?
+                    .unwrap_or_default(),
+                kind: row.get_by_name::<String, _>("kind")?.unwrap_or_default(),
+                model: row
+                    .get_by_name::<JsonB, _>("model")?
+                    .map_or(serde_json::Value::Null, |model| model.0),
+                endpoint: row.get_by_name::<String, _>("endpoint")?,
+            }))
+        })
+    })
+    .map_err(anyhow::Error::from)
+}
+
+/// Loads the active version of the model `name`. Parsed models are kept per
+/// backend and checked against `pgl.models` once per statement.
+fn load_any(name: &str) -> anyhow::Result<Rc<ActiveModel>> {
+    let now = unsafe { pg_sys::GetCurrentStatementStartTimestamp() };
+
+    let cached = MODELS.with(|models| {
+        models
+            .borrow()
+            .get(name)
+            .map(|loaded| (loaded.checked_at == now, loaded.ident.clone()))
+    });
+
+    let reuse = match cached {
+        Some((true, _)) => true,
+        Some((false, ident)) => active_ident(name)?.as_deref() == Some(ident.as_str()),
+        None => false,
+    };
+
+    if !reuse {
+        let Some(row) = read_active(name)? else {
+            MODELS.with(|models| models.borrow_mut().remove(name));
+            anyhow::bail!("model {name} has no active version in pgl.models");
+        };
+        let model = match row.kind.as_str() {
+            "remote" => None,
+            kind => Some(Model::parse(kind, row.model).map_err(|e| {
+                anyhow::anyhow!("failed to load model {name} version {}: {e}", row.version)
+            })?),
+        };
+        MODELS.with(|models| {
+            models.borrow_mut().insert(
+                name.to_string(),
+                LoadedModel {
+                    ident: row.ident,
+                    checked_at: now,
+                    model: Rc::new(ActiveModel {
+                        name: name.to_string(),
+                        version: row.version,
+                        component: row.component,
+                        endpoint: row.endpoint,
+                        model,
+                    }),
+                },
+            )
+        });
+    }
+
+    MODELS.with(|models| {
+        let mut models = models.borrow_mut();
+        let loaded = models.get_mut(name).expect("model was just loaded");
+        loaded.checked_at = now;
+        Ok(loaded.model.clone())
+    })
+}
+
+/// Loads the active version of the model `name`, which must be a
+/// `component` model.
+pub fn load(name: &str, component: &str) -> anyhow::Result<Rc<ActiveModel>> {
+    let model = load_any(name)?;
+    if model.component != component {
+        anyhow::bail!(
+            "model {name} is a {} model, not a {component} model",
+            model.component
+        );
+    }
+    Ok(model)
+}
+
+fn guc_name(setting: &GucSetting<Option<CString>>) -> Option<String> {
+    setting
+        .get()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| !name.is_empty())
+}
+
+/// The active version of `pgl.planner_model`, if it is set.
+pub fn planner_model() -> anyhow::Result<Option<Rc<ActiveModel>>> {
+    guc_name(&PGL_PLANNER_MODEL)
+        .map(|name| load(&name, "planner"))
+        .transpose()
+}
+
+/// The active version of `pgl.cardinality_model`, if it is set.
+pub fn cardinality_model() -> anyhow::Result<Option<Rc<ActiveModel>>> {
+    guc_name(&PGL_CARDINALITY_MODEL)
+        .map(|name| load(&name, "cardinality"))
+        .transpose()
+}
+
+/// Picks the candidate plan with the lowest predicted cost.
+pub fn choose_plan(model: &Model, plans: &[String]) -> anyhow::Result<i32> {
+    let mut best: Option<(usize, f64)> = None;
+    for (i, plan) in plans.iter().enumerate() {
+        let plan = serde_json::from_str(plan)?;
+        // Relation OIDs are not model inputs.
+        let tensor = crate::featurizer::featurize(&plan, &|_, _| 0)?;
+        let mut features = crate::featurizer::named_features(&tensor);
+        for (name, enabled) in crate::planner::arm_settings(i as i32) {
+            features.insert(format!("arm.{name}"), if enabled { 1.0 } else { 0.0 });
+        }
+
+        let score = model.predict(&features);
+        if !score.is_finite() {
+            anyhow::bail!("model predicted {score} for plan {i}");
+        }
+        if best.is_none_or(|(_, best)| score < best) {
+            best = Some((i, score));
+        }
+    }
+
+    best.map(|(i, _)| i as i32)
+        .ok_or_else(|| anyhow::anyhow!("no candidate plans"))
+}
+
+/// Rows predicted for a cardinality payload; models predict the natural log
+/// of the row count.
+pub fn estimate_rows(model: &Model, payload: &serde_json::Value) -> Option<f64> {
+    let rows = model.predict(&cardinality_features(payload)).exp();
+    rows.is_finite().then_some(rows)
+}
+
+/// Evaluates the active version of a model on the given features, e.g. to
+/// check it after registering it.
+#[pg_extern]
+fn pgl_model_predict(name: &str, features: JsonB) -> anyhow::Result<f64> {
+    let features: Features = serde_json::from_value(features.0)?;
+    Ok(load_any(name)?.local()?.predict(&features))
+}
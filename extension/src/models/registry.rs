@@ -0,0 +1,179 @@
+use pgrx::prelude::*;
+use pgrx::JsonB;
+
+use super::Model;
+
+extension_sql!(
+    "CREATE TABLE IF NOT EXISTS pgl.models (
+         name TEXT NOT NULL,
+         version INT NOT NULL,
+         component TEXT NOT NULL CHECK (component IN ('planner', 'cardinality')),
+         kind TEXT NOT NULL CHECK (kind IN ('linear', 'gbdt', 'remote')),
+         model JSONB,
+         endpoint TEXT,
+         active BOOLEAN NOT NULL DEFAULT false,
+         created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+         PRIMARY KEY (name, version),
+         CHECK ((kind = 'remote') = (model IS NULL)),
+         CHECK (kind = 'remote' OR endpoint IS NULL)
+     );
+     CREATE UNIQUE INDEX IF NOT EXISTS models_active_idx ON pgl.models (name) WHERE active;
+     CREATE TABLE IF NOT EXISTS pgl.model_activations (
+         id BIGSERIAL PRIMARY KEY,
+         name TEXT NOT NULL,
+         version INT NOT NULL,
+         activated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+         FOREIGN KEY (name, version) REFERENCES pgl.models ON DELETE CASCADE
+     );",
+    name = "create_models",
+    requires = ["create_schema_pglearned"]
+);
+
+fn registered_component(name: &str) -> anyhow::Result<Option<String>> {
+    Spi::connect(|client| {
+        let args = vec![pgrx::datum::DatumWithOid::from(name)];
+        let res = client.select(
+            "SELECT component FROM pgl.models WHERE name = $1",
+            Some(1),
+            &args,
+        )?;
+        if res.is_empty() {
+            return Ok(None);
+        }
+        res.first().get_one::<String>()
+    })
+    .map_err(anyhow::Error::from)
+}
+
+/// Makes `version` the only active version of `name`, without recording it.
+fn set_active(name: &str, version: i32) -> anyhow::Result<()> {
+    let args = [
+        pgrx::datum::DatumWithOid::from(name),
+        pgrx::datum::DatumWithOid::from(version),
+    ];
+    // Deactivate first: the unique index on active versions is not deferred.
+    Spi::run_with_args(
+        "UPDATE pgl.models SET active = false WHERE name = $1 AND active AND version <> $2",
+        &args,
+    )?;
+    Spi::run_with_args(
+        "UPDATE pgl.models SET active = true WHERE name = $1 AND version = $2",
+        &args,
+    )?;
+    Ok(())
+}
+
+/// Adds a new version of the model `name` and returns its number. Local
+/// models (`linear`, `gbdt`) carry their artifact in `model`; `remote`
+/// models are served by `endpoint`, or by the configured server when it is
+/// not given.
+#[pg_extern]
+fn pgl_model_register(
+    name: &str,
+    component: &str,
+    kind: &str,
+    model: default!(Option<JsonB>, "NULL"),
+    endpoint: default!(Option<&str>, "NULL"),
+    activate: default!(bool, "true"),
+) -> anyhow::Result<i32> {
+    match (kind, &model) {
+        ("remote", Some(_)) => anyhow::bail!("remote models take an endpoint, not a model"),
+        ("remote", None) => {}
+        (_, None) => anyhow::bail!("{kind} models need a model"),
+        (_, Some(model)) => {
+            if endpoint.is_some() {
+                anyhow::bail!("only remote models have an endpoint");
+            }
+            Model::parse(kind, model.0.clone())
+                .map_err(|e| anyhow::anyhow!("invalid {kind} model: {e}"))?;
+        }
+    }
+
+    if let Some(registered) = registered_component(name)? {
+        if registered != component {
+            anyhow::bail!("model {name} is a {registered} model, not a {component} model");
+        }
+    }
+
+    let version: Option<i32> = Spi::get_one_with_args(
+        "INSERT INTO pgl.models (name, version, component, kind, model, endpoint)
+         SELECT $1, coalesce(max(version), 0) + 1, $2, $3, $4, $5
+         FROM pgl.models WHERE name = $1
+         RETURNING version",
+        &[
+            pgrx::datum::DatumWithOid::from(name),
+            pgrx::datum::DatumWithOid::from(component),
+            pgrx::datum::DatumWithOid::from(kind),
+            pgrx::datum::DatumWithOid::from(model),
+            pgrx::datum::DatumWithOid::from(endpoint),
+        ],
+    )?;
+    let version = version.ok_or_else(|| anyhow::anyhow!("failed to register model {name}"))?;
+
+    if activate {
+        pgl_model_activate(name, version)?;
+    }
+    Ok(version)
+}
+
+/// Makes `version` the active version of `name`.
+#[pg_extern]
+fn pgl_model_activate(name: &str, version: i32) -> anyhow::Result<()> {
+    let exists: Option<bool> = Spi::get_one_with_args(
+        "SELECT EXISTS (SELECT 1 FROM pgl.models WHERE name = $1 AND version = $2)",
+        &[
+            pgrx::datum::DatumWithOid::from(name),
+            pgrx::datum::DatumWithOid::from(version),
+        ],
+    )?;
+    if exists != Some(true) {
+        anyhow::bail!("model {name} has no version {version}");
+    }
+
+    set_active(name, version)?;
+    Spi::run_with_args(
+        "INSERT INTO pgl.model_activations (name, version) VALUES ($1, $2)",
+        &[
+            pgrx::datum::DatumWithOid::from(name),
+            pgrx::datum::DatumWithOid::from(version),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Undoes the last activation of `name`, reactivating the version that was
+/// active before it, and returns that version.
+#[pg_extern]
+fn pgl_model_rollback(name: &str) -> anyhow::Result<i32> {
+    let activations = Spi::connect(|client| {
+        let args = vec![pgrx::datum::DatumWithOid::from(name)];
+        let res = client.select(
+            "SELECT id, version FROM pgl.model_activations WHERE name = $1
+             ORDER BY id DESC LIMIT 2",
+            None,
+            &args,
+        )?;
+
+        let mut activations = Vec::new();
+        for row in res {
+            if let (Some(id), Some(version)) = (
+                row.get_by_name::<i64, _>("id")?,
+                row.get_by_name::<i32, _>("version")?,
+            ) {
+                activations.push((id, version));
+            }
+        }
+        Ok::<_, pgrx::spi::SpiError>(activations)
+    })?;
+
+    let [(current, _), (_, previous)] = activations[..] else {
+        anyhow::bail!("model {name} has no earlier version to roll back to");
+    };
+
+    Spi::run_with_args(
+        "DELETE FROM pgl.model_activations WHERE id = $1",
+        &[pgrx::datum::DatumWithOid::from(current)],
+    )?;
+    set_active(name, previous)?;
+    Ok(previous)
+}
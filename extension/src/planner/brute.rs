@@ -1,20 +1,103 @@
-use super::types::{PglPlannerMode, BRUTE_POSSIBLE_ARMS};
-use super::{PGL_PLANNER_ARM, PGL_PLANNER_MODE};
-use crate::rpc::PGL_REMOTE_SERVER_URL;
+use super::decision::{self, PlannerDecision};
+use super::types::{PglPlannerMethod, PglPlannerMode, BRUTE_ARM_GUCS, BRUTE_POSSIBLE_ARMS};
+use super::{PGL_PLANNER_ARM, PGL_PLANNER_FALLBACK, PGL_PLANNER_MODE};
+use crate::featurizer;
+use crate::models::ActiveModel;
+use crate::rpc::metadata::RequestMetadata;
+use crate::rpc::remote_planner_url;
+use crate::stats::{Call, Component, Outcome};
 use crate::utils::set_config_local;
 use pgrx::pg_sys;
+use std::collections::HashSet;
 use std::ffi::CStr;
+use std::time::Instant;
+
+/// The arm with every plan type enabled, i.e. PostgreSQL's own choice.
+pub const NATIVE_ARM: i32 = BRUTE_POSSIBLE_ARMS;
+
+/// Decodes an arm into the planner GUCs it sets.
+pub fn arm_settings(arm: i32) -> Vec<(&'static str, bool)> {
+    BRUTE_ARM_GUCS
+        .iter()
+        .enumerate()
+        .map(|(bit, name)| (*name, (arm & (1 << bit)) != 0))
+        .collect()
+}
 
 fn set_brute_planner_arm(arm: i32) -> anyhow::Result<()> {
-    set_config_local("enable_hashjoin", &((arm & 1) != 0).to_string())?;
-    set_config_local("enable_mergejoin", &((arm & 2) != 0).to_string())?;
-    set_config_local("enable_nestloop", &((arm & 4) != 0).to_string())?;
-    set_config_local("enable_indexscan", &((arm & 8) != 0).to_string())?;
-    set_config_local("enable_seqscan", &((arm & 16) != 0).to_string())?;
-    set_config_local("enable_indexonlyscan", &((arm & 32) != 0).to_string())?;
+    for (name, enabled) in arm_settings(arm) {
+        set_config_local(name, &enabled.to_string())?;
+    }
     Ok(())
 }
 
+/// Featurizes every candidate for the server; a plan that cannot be
+/// featurized gets an empty string so the lists stay aligned.
+fn candidate_features(plans: &[String]) -> Vec<String> {
+    plans
+        .iter()
+        .map(|plan| {
+            serde_json::from_str(plan)
+                .map_err(anyhow::Error::from)
+                .and_then(|plan| featurizer::plan_features_json(&plan))
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn choose_remote_plan(
+    model: Option<&ActiveModel>,
+    plans: Vec<String>,
+    features: Vec<String>,
+    metadata: RequestMetadata,
+    call: &mut Call,
+) -> anyhow::Result<i32> {
+    let endpoint = model.map(ActiveModel::remote).transpose()?.flatten();
+    let Some(url) = endpoint.map(str::to_string).or_else(remote_planner_url) else {
+        anyhow::bail!("neither pgl.remote_planner_url nor pgl.remote_server_url is set");
+    };
+    call.endpoint = Some(url.clone());
+
+    let candidates = plans.len();
+    let started = Instant::now();
+    let chosen_idx = crate::rpc::client::PglRemoteSyncClient::connect(url)
+        .map_err(|e| anyhow::anyhow!("Failed to connect to remote planner: {}", e))
+        .and_then(|mut client| {
+            client.set_metadata(metadata);
+            let chosen_idx = client
+                .choose_plan(plans, features, model.map(ActiveModel::version).as_ref())
+                .map_err(|e| anyhow::anyhow!("Failed to choose plan from remote: {}", e));
+            call.bytes_sent = client.bytes_sent();
+            chosen_idx
+        });
+    call.latency = Some(started.elapsed());
+    let chosen_idx = chosen_idx?;
+
+    if chosen_idx < 0 || chosen_idx as usize >= candidates {
+        anyhow::bail!("Remote returned invalid arm index: {}", chosen_idx);
+    }
+
+    Ok(chosen_idx)
+}
+
+fn choose_model_plan(
+    model: Option<&ActiveModel>,
+    plans: &[String],
+    call: &mut Call,
+) -> anyhow::Result<i32> {
+    let Some(model) = model else {
+        anyhow::bail!("pgl.planner_model is not set");
+    };
+    call.endpoint = Some(model.name.clone());
+
+    let started = Instant::now();
+    let chosen = model
+        .local()
+        .and_then(|local| crate::models::choose_plan(local, plans));
+    call.latency = Some(started.elapsed());
+    chosen
+}
+
 unsafe fn planned_stmt_to_json(
     planned_stmt: *mut pg_sys::PlannedStmt,
     query_string: *const std::os::raw::c_char,
@@ -46,6 +129,22 @@ unsafe fn planned_stmt_to_json(
     c_str.to_string_lossy().to_string()
 }
 
+unsafe fn plan_with_arm(
+    parse: *mut pg_sys::Query,
+    query_string: *const std::os::raw::c_char,
+    cursor_options: i32,
+    bound_params: pg_sys::ParamListInfo,
+    arm: i32,
+) -> *mut pg_sys::PlannedStmt {
+    if let Err(e) = set_brute_planner_arm(arm) {
+        pgrx::error!("failed to set planner arm: {}", e);
+    }
+
+    let planned_stmt = pg_sys::standard_planner(parse, query_string, cursor_options, bound_params);
+    crate::cardinality::attach_estimates(planned_stmt);
+    planned_stmt
+}
+
 pub unsafe fn pgl_brute_planner(
     parse: *mut pg_sys::Query,
     query_string: *const std::os::raw::c_char,
@@ -60,13 +159,31 @@ pub unsafe fn pgl_brute_planner(
                 pgrx::error!("wrong arm value, possible values: 0 -> {BRUTE_POSSIBLE_ARMS}");
             }
 
-            if let Err(e) = set_brute_planner_arm(arm) {
-                pgrx::error!("failed to set planner arm: {}", e);
-            }
+            let planned_stmt =
+                plan_with_arm(parse, query_string, cursor_options, bound_params, arm);
 
-            pg_sys::standard_planner(parse, query_string, cursor_options, bound_params)
+            let mut decision = PlannerDecision::new(PglPlannerMethod::Brute);
+            decision.mode = Some(mode);
+            decision.arm = Some(arm);
+            decision::record(planned_stmt, decision);
+
+            planned_stmt
         }
-        PglPlannerMode::Remote => {
+        PglPlannerMode::Remote | PglPlannerMode::Model => {
+            if let Some(arm) = crate::regression::pinned_arm(parse) {
+                let planned_stmt =
+                    plan_with_arm(parse, query_string, cursor_options, bound_params, arm);
+
+                let mut decision = PlannerDecision::new(PglPlannerMethod::Brute);
+                decision.mode = Some(mode);
+                decision.arm = Some(arm);
+                decision.pinned = true;
+                decision::record(planned_stmt, decision);
+
+                return planned_stmt;
+            }
+
+            let planning_started = Instant::now();
             let mut plans = Vec::new();
             let mut candidate_stmts = Vec::new();
 
@@ -85,6 +202,7 @@ pub unsafe fn pgl_brute_planner(
                     cursor_options,
                     bound_params,
                 );
+                crate::cardinality::attach_estimates(planned_stmt);
 
                 candidate_stmts.push(planned_stmt);
 
@@ -101,29 +219,70 @@ pub unsafe fn pgl_brute_planner(
                 plans.push(json_str);
             }
 
-            let url = if let Some(url_cstr) = PGL_REMOTE_SERVER_URL.get() {
-                url_cstr.to_string_lossy().to_string()
-            } else {
-                pgrx::error!("pgl.remote_server_url is not set");
-            };
+            let mut decision = PlannerDecision::new(PglPlannerMethod::Brute);
+            decision.mode = Some(mode);
+            decision.candidates = plans.len();
+            decision.distinct_candidates = plans.iter().collect::<HashSet<_>>().len();
 
-            let chosen_idx = match crate::rpc::client::PglRemoteSyncClient::connect(url) {
-                Ok(mut client) => match client.choose_plan(plans) {
-                    Ok(idx) => idx,
-                    Err(e) => {
-                        pgrx::error!("Failed to choose plan from remote: {}", e);
-                    }
-                },
+            let mut call = Call::new(Component::Planner);
+            let chosen = crate::models::planner_model().and_then(|model| {
+                decision.model = model.as_ref().map(|model| model.version());
+                if mode == PglPlannerMode::Model {
+                    return choose_model_plan(model.as_deref(), &plans, &mut call);
+                }
+
+                let started = Instant::now();
+                let features = if featurizer::PGL_PLAN_FEATURES.get() {
+                    candidate_features(&plans)
+                } else {
+                    Vec::new()
+                };
+                let metadata = RequestMetadata::planned();
+                let chosen = choose_remote_plan(
+                    model.as_deref(),
+                    plans.clone(),
+                    features,
+                    metadata,
+                    &mut call,
+                );
+                decision.remote_latency = Some(started.elapsed());
+                chosen
+            });
+
+            let mut chosen_idx = match chosen {
+                Ok(chosen_idx) => chosen_idx,
+                // PostgreSQL's own plan is always among the candidates.
+                Err(e) if PGL_PLANNER_FALLBACK.get() || crate::shadow::enabled() => {
+                    pgrx::warning!("{}; falling back to the native plan", e);
+                    decision.fallback = Some(e.to_string());
+                    NATIVE_ARM
+                }
                 Err(e) => {
-                    pgrx::error!("Failed to connect to remote planner: {}", e);
+                    call.record(Outcome::Failed, planning_started.elapsed());
+                    pgrx::error!("{}", e);
                 }
             };
+            call.arm = Some(chosen_idx);
 
-            if chosen_idx < 0 || chosen_idx as usize >= candidate_stmts.len() {
-                pgrx::error!("Remote returned invalid arm index: {}", chosen_idx);
+            if crate::shadow::enabled() && decision.fallback.is_none() {
+                let query_id = ((*parse).queryId != 0).then_some((*parse).queryId as i64);
+                crate::shadow::record_plan_choice(query_id, NATIVE_ARM, chosen_idx);
+                decision.shadow_arm = Some(chosen_idx);
+                chosen_idx = NATIVE_ARM;
             }
+            decision.arm = Some(chosen_idx);
+
+            decision.plan = plans.get(chosen_idx as usize).cloned();
+
+            let outcome = match decision.fallback {
+                Some(_) => Outcome::Failed,
+                None => Outcome::Applied,
+            };
+            call.record(outcome, planning_started.elapsed());
 
-            candidate_stmts[chosen_idx as usize]
+            let planned_stmt = candidate_stmts[chosen_idx as usize];
+            decision::record(planned_stmt, decision);
+            planned_stmt
         }
     }
 }
@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use pgrx::pg_sys;
+
+use crate::experiment::ExperimentGroup;
+use crate::models::ModelVersion;
+use crate::trivial::TrivialReason;
+
+use super::types::{PglPlannerMethod, PglPlannerMode};
+
+/// How many planned statements keep their decision for EXPLAIN.
+const MAX_DECISIONS: usize = 32;
+
+/// How the planner hook arrived at a plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannerDecision {
+    pub method: PglPlannerMethod,
+    pub mode: Option<PglPlannerMode>,
+    pub arm: Option<i32>,
+    pub candidates: usize,
+    pub distinct_candidates: usize,
+    pub remote_latency: Option<Duration>,
+    /// Why the remote choice was not used, if it was not.
+    pub fallback: Option<String>,
+    /// The arm the server chose in shadow mode, where `arm` is the native one.
+    pub shadow_arm: Option<i32>,
+    /// The arm was pinned by the regression guard instead of chosen remotely.
+    pub pinned: bool,
+    /// The planner model version asked for the choice.
+    pub model: Option<ModelVersion>,
+    /// JSON of the chosen plan, when the planner rendered it for the server.
+    pub plan: Option<String>,
+    pub planning_time: Option<Duration>,
+    pub experiment_group: Option<ExperimentGroup>,
+    /// The `pgl(...)` comment hints applied while planning.
+    pub hints: Option<String>,
+    /// The `pgl.policy` rule that disabled learned planning.
+    pub policy_rule: Option<i32>,
+    /// Why the statement was too trivial for the learned components.
+    pub trivial: Option<TrivialReason>,
+}
+
+impl PlannerDecision {
+    pub fn new(method: PglPlannerMethod) -> Self {
+        Self {
+            method,
+            mode: None,
+            arm: None,
+            candidates: 1,
+            distinct_candidates: 1,
+            remote_latency: None,
+            fallback: None,
+            shadow_arm: None,
+            pinned: false,
+            model: None,
+            plan: None,
+            planning_time: None,
+            experiment_group: None,
+            hints: None,
+            policy_rule: None,
+            trivial: None,
+        }
+    }
+}
+
+thread_local! {
+    static DECISIONS: RefCell<VecDeque<(*mut pg_sys::PlannedStmt, PlannerDecision)>> =
+        const { RefCell::new(VecDeque::new()) };
+}
+
+pub fn record(stmt: *mut pg_sys::PlannedStmt, decision: PlannerDecision) {
+    DECISIONS.with(|decisions| {
+        let mut decisions = decisions.borrow_mut();
+        decisions.retain(|(planned, _)| *planned != stmt);
+        if decisions.len() >= MAX_DECISIONS {
+            decisions.pop_front();
+        }
+        decisions.push_back((stmt, decision));
+    });
+}
+
+/// What the planner hook itself measured and applied around a planner.
+pub struct PlanningContext {
+    pub planning_time: Duration,
+    pub experiment_group: Option<ExperimentGroup>,
+    pub hints: Option<String>,
+    pub policy_rule: Option<i32>,
+    pub trivial: Option<TrivialReason>,
+}
+
+/// Adds the planner hook's context to the decision for `stmt`.
+pub fn record_planning(stmt: *mut pg_sys::PlannedStmt, context: PlanningContext) {
+    DECISIONS.with(|decisions| {
+        if let Some((_, decision)) = decisions
+            .borrow_mut()
+            .iter_mut()
+            .find(|(planned, _)| *planned == stmt)
+        {
+            decision.planning_time = Some(context.planning_time);
+            decision.experiment_group = context.experiment_group;
+            decision.hints = context.hints;
+            decision.policy_rule = context.policy_rule;
+            decision.trivial = context.trivial;
+        }
+    });
+}
+
+pub fn lookup(stmt: *mut pg_sys::PlannedStmt) -> Option<PlannerDecision> {
+    DECISIONS.with(|decisions| {
+        decisions
+            .borrow()
+            .iter()
+            .find(|(planned, _)| *planned == stmt)
+            .map(|(_, decision)| decision.clone())
+    })
+}
@@ -1,10 +1,19 @@
 use pgrx::pg_sys;
 
+use super::decision::{self, PlannerDecision};
+use super::types::PglPlannerMethod;
+
 pub unsafe fn pgl_default_planner(
     parse: *mut pg_sys::Query,
     query_string: *const std::os::raw::c_char,
     cursor_options: i32,
     bound_params: pg_sys::ParamListInfo,
 ) -> *mut pg_sys::PlannedStmt {
-    pg_sys::standard_planner(parse, query_string, cursor_options, bound_params)
+    let planned_stmt = pg_sys::standard_planner(parse, query_string, cursor_options, bound_params);
+    crate::cardinality::attach_estimates(planned_stmt);
+    decision::record(
+        planned_stmt,
+        PlannerDecision::new(PglPlannerMethod::Default),
+    );
+    planned_stmt
 }
@@ -0,0 +1,157 @@
+use pgrx::pg_sys;
+
+use super::types::BRUTE_POSSIBLE_ARMS;
+use crate::utils::set_config_internal;
+
+/// Skips `keyword` at the head of `text`, ignoring case.
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = text.get(..keyword.len())?;
+    let rest = &text[keyword.len()..];
+    (head.eq_ignore_ascii_case(keyword)
+        && !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+    .then_some(rest)
+}
+
+/// Skips a parenthesized EXPLAIN option list.
+fn strip_options(text: &str) -> Option<&str> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => return Some(&text[i + 1..]),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The body of the `/*+ ... */` comment at the head of `query`, which may
+/// follow other comments and an EXPLAIN with its options.
+fn hint_comment(query: &str) -> Option<&str> {
+    let mut rest = query;
+    let mut explain = false;
+    loop {
+        rest = rest.trim_start();
+        if let Some(body) = rest.strip_prefix("/*+") {
+            return body.split_once("*/").map(|(body, _)| body);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/")?.1;
+        } else if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(after) = strip_keyword(rest, "explain").filter(|_| !explain) {
+            explain = true;
+            rest = after;
+        } else if explain && rest.starts_with('(') {
+            rest = strip_options(rest)?;
+        } else if let Some(after) = ["analyze", "analyse", "verbose"]
+            .iter()
+            .find_map(|keyword| strip_keyword(rest, keyword))
+            .filter(|_| explain)
+        {
+            rest = after;
+        } else {
+            return None;
+        }
+    }
+}
+
+/// One `key=value` of a `pgl(...)` hint and the GUC it sets.
+#[derive(Debug, Clone, PartialEq)]
+struct Hint {
+    key: String,
+    guc: &'static str,
+    value: String,
+}
+
+fn hint(key: &str, value: &str) -> Result<Hint, String> {
+    let key = key.to_ascii_lowercase();
+    let value = value.to_ascii_lowercase();
+    let valid = match key.as_str() {
+        "method" => ["default", "brute"].contains(&value.as_str()),
+        "mode" => ["local", "remote", "model"].contains(&value.as_str()),
+        "arm" => value
+            .parse::<i32>()
+            .is_ok_and(|arm| (0..=BRUTE_POSSIBLE_ARMS).contains(&arm)),
+        "cardinality" => ["on", "off", "true", "false"].contains(&value.as_str()),
+        _ => return Err(format!("unknown hint {key}")),
+    };
+    if !valid {
+        return Err(format!("invalid value {value} for hint {key}"));
+    }
+
+    let guc = match key.as_str() {
+        "method" => "pgl.planner_method",
+        "mode" => "pgl.planner_mode",
+        "arm" => "pgl.planner_arm",
+        _ => "pgl.enable_remote_cardinality",
+    };
+    Ok(Hint { key, guc, value })
+}
+
+/// Parses `pgl(key=value, ...)` out of the hint comment of `query`. Other
+/// hints in the same comment are left to whoever reads them.
+fn parse(query: &str) -> Result<Vec<Hint>, String> {
+    let Some(comment) = hint_comment(query) else {
+        return Ok(Vec::new());
+    };
+
+    let lower = comment.to_ascii_lowercase();
+    let Some(start) = lower.match_indices("pgl").find_map(|(i, _)| {
+        let before = lower[..i].chars().next_back();
+        let after = lower[i + 3..].trim_start();
+        (before.is_none_or(|c| !c.is_alphanumeric() && c != '_') && after.starts_with('('))
+            .then(|| comment.len() - after.len() + 1)
+    }) else {
+        return Ok(Vec::new());
+    };
+    let Some((arguments, _)) = comment[start..].split_once(')') else {
+        return Err("unterminated pgl hint".to_string());
+    };
+
+    arguments
+        .split(',')
+        .map(str::trim)
+        .filter(|argument| !argument.is_empty())
+        .map(|argument| {
+            let (key, value) = argument
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got {argument}"))?;
+            hint(key.trim(), value.trim())
+        })
+        .collect()
+}
+
+/// Runs `f` with the settings hinted at the head of the statement `parse`
+/// came from, and returns the hints that were applied. Settings are restored
+/// afterwards; an invalid hint is ignored with a warning.
+pub unsafe fn with_hints<T>(
+    parse: *mut pg_sys::Query,
+    query_string: *const std::os::raw::c_char,
+    f: impl FnOnce() -> T,
+) -> (T, Option<String>) {
+    let query = crate::utils::statement_text(parse, query_string);
+    let hints = match parse(&query) {
+        Ok(hints) => hints,
+        Err(err) => {
+            pgrx::warning!("ignoring pgl hint: {err}");
+            Vec::new()
+        }
+    };
+    if hints.is_empty() {
+        return (f(), None);
+    }
+
+    let nest_level = pg_sys::NewGUCNestLevel();
+    let mut applied = Vec::new();
+    for hint in &hints {
+        match set_config_internal(hint.guc, &hint.value, pg_sys::GucAction::GUC_ACTION_SAVE) {
+            Ok(()) => applied.push(format!("{}={}", hint.key, hint.value)),
+            Err(err) => pgrx::warning!("ignoring pgl hint: {err}"),
+        }
+    }
+
+    let result = f();
+    pg_sys::AtEOXact_GUC(true, nest_level);
+    (result, Some(applied.join(", ")))
+}
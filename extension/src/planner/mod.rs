@@ -1,16 +1,30 @@
 mod brute;
+mod decision;
 mod default;
 pub mod explain;
+mod hints;
 mod types;
 
+use std::time::Instant;
+
 use pgrx::pg_sys;
 use pgrx::prelude::*;
 use pgrx::GucSetting;
 
+use crate::cardinality;
+use crate::experiment::{self, ExperimentGroup};
+use crate::policy;
+use crate::rpc::metadata;
+use crate::trivial::{self, TrivialReason};
+use crate::utils::in_internal_lookup;
+
 use self::brute::pgl_brute_planner;
+use self::decision::PlanningContext;
 use self::default::pgl_default_planner;
 use self::types::{PglPlannerMethod, PglPlannerMode};
 
+pub use self::brute::{arm_settings, NATIVE_ARM};
+pub use self::decision::lookup as planner_decision;
 pub use self::explain::EXPLAIN_PLANNER_MAP;
 
 static mut PREV_PLANNER_HOOK: pg_sys::planner_hook_type = None;
@@ -20,6 +34,7 @@ pub static PGL_PLANNER_METHOD: GucSetting<PglPlannerMethod> =
 pub static PGL_PLANNER_ARM: GucSetting<i32> = GucSetting::<i32>::new(-1);
 pub static PGL_PLANNER_MODE: GucSetting<PglPlannerMode> =
     GucSetting::<PglPlannerMode>::new(PglPlannerMode::Local);
+pub static PGL_PLANNER_FALLBACK: GucSetting<bool> = GucSetting::<bool>::new(false);
 
 #[pg_guard]
 pub extern "C-unwind" fn pgl_planner(
@@ -33,19 +48,106 @@ pub extern "C-unwind" fn pgl_planner(
             prev(parse, query_string, cursor_options, bound_params);
         }
 
-        let method = PGL_PLANNER_METHOD.get();
-
-        match method {
-            PglPlannerMethod::Default => {
-                pgl_default_planner(parse, query_string, cursor_options, bound_params)
-            }
-            PglPlannerMethod::Brute => {
-                pgl_brute_planner(parse, query_string, cursor_options, bound_params)
-            }
+        if in_internal_lookup() {
+            return pg_sys::standard_planner(parse, query_string, cursor_options, bound_params);
         }
+
+        trivial::nested(|| {
+            metadata::planning(parse, query_string, || {
+                pgl_plan(parse, query_string, cursor_options, bound_params)
+            })
+        })
     }
 }
 
+unsafe fn pgl_plan(
+    parse: *mut pg_sys::Query,
+    query_string: *const std::os::raw::c_char,
+    cursor_options: i32,
+    bound_params: pg_sys::ParamListInfo,
+) -> *mut pg_sys::PlannedStmt {
+    let started = Instant::now();
+    let ((planned_stmt, group, policy_rule, trivial), hints) =
+        hints::with_hints(parse, query_string, || {
+            let method = PGL_PLANNER_METHOD.get();
+            let group = experiment::assign(parse, query_string);
+            let trivial = match group {
+                Some(ExperimentGroup::Native) => None,
+                _ => plan_trivially(method, parse, query_string, cursor_options, bound_params),
+            };
+            let policy_rule = match (method, trivial) {
+                (PglPlannerMethod::Default, _) | (_, Some(_)) => None,
+                _ => policy::planner_disabled_by(parse),
+            };
+            let planned_stmt = match (method, group, policy_rule, trivial) {
+                (_, Some(ExperimentGroup::Native), _, _) => experiment::plan_natively(|| {
+                    pgl_default_planner(parse, query_string, cursor_options, bound_params)
+                }),
+                (_, _, _, Some((planned_stmt, _))) => planned_stmt,
+                (PglPlannerMethod::Default, _, _, _) | (_, _, Some(_), _) => {
+                    pgl_default_planner(parse, query_string, cursor_options, bound_params)
+                }
+                (PglPlannerMethod::Brute, _, None, None) => {
+                    pgl_brute_planner(parse, query_string, cursor_options, bound_params)
+                }
+            };
+            (
+                planned_stmt,
+                group,
+                policy_rule,
+                trivial.map(|(_, reason)| reason),
+            )
+        });
+
+    decision::record_planning(
+        planned_stmt,
+        PlanningContext {
+            planning_time: started.elapsed(),
+            experiment_group: group,
+            hints,
+            policy_rule,
+            trivial,
+        },
+    );
+    planned_stmt
+}
+
+/// Plans `parse` natively, without learned estimates, when it is too trivial
+/// for the learned components that are enabled.
+unsafe fn plan_trivially(
+    method: PglPlannerMethod,
+    parse: *mut pg_sys::Query,
+    query_string: *const std::os::raw::c_char,
+    cursor_options: i32,
+    bound_params: pg_sys::ParamListInfo,
+) -> Option<(*mut pg_sys::PlannedStmt, TrivialReason)> {
+    if method == PglPlannerMethod::Default && !cardinality::PGL_ENABLE_REMOTE_CARDINALITY.get() {
+        return None;
+    }
+
+    if let Some(reason) = trivial::reason(parse) {
+        let planned_stmt = experiment::plan_natively(|| {
+            pgl_default_planner(parse, query_string, cursor_options, bound_params)
+        });
+        return Some((planned_stmt, reason));
+    }
+    // A fixed arm costs one planning run, like the native plan the check
+    // needs, so the check would only add to it.
+    let fixed_arm =
+        method == PglPlannerMethod::Default || PGL_PLANNER_MODE.get() == PglPlannerMode::Local;
+    if !trivial::checks_cost() || (fixed_arm && !cardinality::PGL_ENABLE_REMOTE_CARDINALITY.get()) {
+        return None;
+    }
+
+    // Planning modifies the query tree, which is planned again unless the
+    // native plan turns out cheap enough.
+    let parse_copy = pg_sys::copyObjectImpl(parse as *const _) as *mut pg_sys::Query;
+    let planned_stmt = experiment::plan_natively(|| {
+        pgl_default_planner(parse_copy, query_string, cursor_options, bound_params)
+    });
+    trivial::too_cheap(planned_stmt).then_some((planned_stmt, TrivialReason::Cost))
+}
+
 pub unsafe fn register() {
     PREV_PLANNER_HOOK = pg_sys::planner_hook;
     pg_sys::planner_hook = Some(pgl_planner);
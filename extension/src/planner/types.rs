@@ -1,9 +1,20 @@
 pub const BRUTE_POSSIBLE_ARMS: i32 = (1 << 6) - 1;
 
+/// The planner GUCs a brute-force arm switches, by bit position.
+pub const BRUTE_ARM_GUCS: [&str; 6] = [
+    "enable_hashjoin",
+    "enable_mergejoin",
+    "enable_nestloop",
+    "enable_indexscan",
+    "enable_seqscan",
+    "enable_indexonlyscan",
+];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, pgrx::PostgresGucEnum)]
 pub enum PglPlannerMode {
     Local,
     Remote,
+    Model,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, pgrx::PostgresGucEnum)]
@@ -0,0 +1,286 @@
+use std::cell::{Cell, RefCell};
+use std::ffi::CStr;
+
+use pgrx::pg_sys;
+use pgrx::prelude::*;
+
+use crate::utils::{bitmapset_members, cstr_to_string, internal_lookup, query_relations};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Component {
+    Planner,
+    Cardinality,
+}
+
+/// A row of `pgl.policy` for the current database.
+struct Rule {
+    id: i32,
+    component: Component,
+    enabled: bool,
+    /// `InvalidOid` for a role that does not exist, which nobody is a member of.
+    role: Option<pg_sys::Oid>,
+    schema: Option<String>,
+    relation: Option<String>,
+    max_pages: Option<i32>,
+}
+
+struct RelationInfo {
+    schema: String,
+    name: String,
+    pages: i32,
+}
+
+impl Rule {
+    fn targets_relations(&self) -> bool {
+        self.schema.is_some() || self.relation.is_some() || self.max_pages.is_some()
+    }
+
+    unsafe fn matches(&self, relation: Option<&RelationInfo>) -> bool {
+        if let Some(role) = self.role {
+            // Superusers are members of every role, but only match their own rules.
+            if !pg_sys::is_member_of_role_nosuper(pg_sys::GetUserId(), role) {
+                return false;
+            }
+        }
+
+        if !self.targets_relations() {
+            return true;
+        }
+        let Some(relation) = relation else {
+            return false;
+        };
+        self.schema
+            .as_ref()
+            .is_none_or(|schema| *schema == relation.schema)
+            && self
+                .relation
+                .as_ref()
+                .is_none_or(|name| *name == relation.name)
+            && self.max_pages.is_none_or(|pages| relation.pages <= pages)
+    }
+}
+
+thread_local! {
+    /// Rules in the order they are tried: priority, then the most specific.
+    static RULES: RefCell<Vec<Rule>> = const { RefCell::new(Vec::new()) };
+    static RULES_TABLE: Cell<pg_sys::Oid> = const { Cell::new(pg_sys::InvalidOid) };
+    static STALE: Cell<bool> = const { Cell::new(true) };
+}
+
+const LOAD_RULES_SQL: &str = "SELECT id, component, enabled,
+            CASE WHEN role IS NOT NULL
+                 THEN coalesce((SELECT oid FROM pg_roles WHERE rolname = role), 0::oid)
+            END AS role_oid,
+            schema::text, relation::text, max_pages
+     FROM pgl.policy
+     WHERE database IS NULL OR database = current_database()
+     ORDER BY priority DESC,
+              (database IS NOT NULL)::int + (role IS NOT NULL)::int
+                + (schema IS NOT NULL)::int + (relation IS NOT NULL)::int
+                + (max_pages IS NOT NULL)::int DESC,
+              id DESC";
+
+fn load_rules() -> Result<Vec<Rule>, pgrx::spi::SpiError> {
+    internal_lookup(|| {
+        Spi::connect(|client| {
+            let mut rules = Vec::new();
+            for row in client.select(LOAD_RULES_SQL, None, &[])? {
+                let component = match row.get_by_name::<String, _>("component")?.as_deref() {
+                    Some("planner") => Component::Planner,
+                    Some("cardinality") => Component::Cardinality,
+                    _ => continue,
+                };
+                rules.push(Rule {
+                    id: row.get_by_name::<i32, _>("id")?.unwrap_or_default(),
+                    component,
+                    enabled: row.get_by_name::<bool, _>("enabled")?.unwrap_or(true),
+                    role: row.get_by_name::<pg_sys::Oid, _>("role_oid")?,
+                    schema: row.get_by_name::<String, _>("schema")?,
+                    relation: row.get_by_name::<String, _>("relation")?,
+                    max_pages: row.get_by_name::<i32, _>("max_pages")?,
+                });
+            }
+            Ok(rules)
+        })
+    })
+}
+
+unsafe fn policy_table() -> pg_sys::Oid {
+    let namespace = pg_sys::get_namespace_oid(c"pgl".as_ptr(), true);
+    if namespace == pg_sys::InvalidOid {
+        return pg_sys::InvalidOid;
+    }
+    pg_sys::get_relname_relid(c"policy".as_ptr(), namespace)
+}
+
+/// Runs `f` on the rules, reading `pgl.policy` again after it changed.
+unsafe fn with_rules<T>(f: impl FnOnce(&[Rule]) -> T) -> T {
+    if STALE.with(Cell::get) {
+        let table = policy_table();
+        // Without the table, stay stale to notice when it is created.
+        let rules = if table == pg_sys::InvalidOid {
+            Vec::new()
+        } else {
+            STALE.with(|stale| stale.set(false));
+            load_rules().unwrap_or_else(|err| {
+                pgrx::warning!("failed to read pgl.policy: {err}");
+                Vec::new()
+            })
+        };
+        RULES_TABLE.with(|cached| cached.set(table));
+        RULES.with(|cached| *cached.borrow_mut() = rules);
+    }
+
+    RULES.with(|rules| f(&rules.borrow()))
+}
+
+unsafe fn relation_info(relid: pg_sys::Oid) -> Option<RelationInfo> {
+    let relation = pg_sys::RelationIdGetRelation(relid);
+    if relation.is_null() {
+        return None;
+    }
+
+    let form = (*relation).rd_rel;
+    let info = RelationInfo {
+        schema: cstr_to_string(pg_sys::get_namespace_name((*form).relnamespace))
+            .unwrap_or_default(),
+        name: CStr::from_ptr((*form).relname.data.as_ptr())
+            .to_string_lossy()
+            .into_owned(),
+        pages: (*form).relpages,
+    };
+    pg_sys::RelationClose(relation);
+    Some(info)
+}
+
+/// The rule deciding `component` for `relation`, or for the statement as a
+/// whole when there is none.
+unsafe fn deciding_rule<'a>(
+    rules: &'a [Rule],
+    component: Component,
+    relation: Option<pg_sys::Oid>,
+) -> Option<&'a Rule> {
+    let relation = relation.and_then(|relid| relation_info(relid));
+    rules
+        .iter()
+        .filter(|rule| rule.component == component)
+        .find(|rule| rule.matches(relation.as_ref()))
+}
+
+/// The rule that disables learned planning for `parse`, if any. Planning
+/// is disabled when the rule deciding any of its relations disables it.
+pub unsafe fn planner_disabled_by(parse: *mut pg_sys::Query) -> Option<i32> {
+    with_rules(|rules| {
+        if !rules
+            .iter()
+            .any(|rule| rule.component == Component::Planner)
+        {
+            return None;
+        }
+
+        let mut relations = if rules
+            .iter()
+            .any(|rule| rule.component == Component::Planner && rule.targets_relations())
+        {
+            query_relations(parse)
+        } else {
+            Vec::new()
+        };
+        relations.sort_unstable();
+        relations.dedup();
+        if relations.is_empty() {
+            return deciding_rule(rules, Component::Planner, None)
+                .filter(|rule| !rule.enabled)
+                .map(|rule| rule.id);
+        }
+
+        relations.into_iter().find_map(|relid| {
+            deciding_rule(rules, Component::Planner, Some(relid))
+                .filter(|rule| !rule.enabled)
+                .map(|rule| rule.id)
+        })
+    })
+}
+
+/// Whether learned estimates may replace PostgreSQL's for `relids`, which
+/// requires every base relation in it to be enabled.
+pub unsafe fn cardinality_enabled(root: *mut pg_sys::PlannerInfo, relids: pg_sys::Relids) -> bool {
+    with_rules(|rules| {
+        if !rules
+            .iter()
+            .any(|rule| rule.component == Component::Cardinality)
+        {
+            return true;
+        }
+        if !rules
+            .iter()
+            .any(|rule| rule.component == Component::Cardinality && rule.targets_relations())
+        {
+            return deciding_rule(rules, Component::Cardinality, None)
+                .is_none_or(|rule| rule.enabled);
+        }
+
+        bitmapset_members(relids).into_iter().all(|rt_index| {
+            if (*root).simple_rte_array.is_null()
+                || rt_index as usize >= (*root).simple_rel_array_size as usize
+            {
+                return true;
+            }
+            let rte = *(*root).simple_rte_array.add(rt_index as usize);
+            if rte.is_null() || (*rte).rtekind != pg_sys::RTEKind::RTE_RELATION {
+                return true;
+            }
+            deciding_rule(rules, Component::Cardinality, Some((*rte).relid))
+                .is_none_or(|rule| rule.enabled)
+        })
+    })
+}
+
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_policy_relcache_callback(_arg: pg_sys::Datum, relid: pg_sys::Oid) {
+    if relid == pg_sys::InvalidOid || RULES_TABLE.with(Cell::get) == relid {
+        STALE.with(|stale| stale.set(true));
+    }
+}
+
+pub unsafe fn register() {
+    pg_sys::CacheRegisterRelcacheCallback(
+        Some(pgl_policy_relcache_callback),
+        pg_sys::Datum::from(0usize),
+    );
+}
+
+/// Row changes do not invalidate a table's relcache entry, so every change
+/// to `pgl.policy` does it explicitly for the backends caching its rules.
+#[pg_trigger]
+fn pgl_policy_changed<'a>(
+    trigger: &'a PgTrigger<'a>,
+) -> Result<Option<PgHeapTuple<'a, AllocatedByPostgres>>, PgTriggerError> {
+    let relation = trigger.relation()?;
+    unsafe { pg_sys::CacheInvalidateRelcacheByRelid(relation.oid()) };
+    Ok(None)
+}
+
+extension_sql!(
+    "CREATE TABLE IF NOT EXISTS pgl.policy (
+         id SERIAL PRIMARY KEY,
+         component TEXT NOT NULL CHECK (component IN ('planner', 'cardinality')),
+         enabled BOOLEAN NOT NULL,
+         database NAME,
+         role NAME,
+         schema NAME,
+         relation NAME,
+         max_pages INT,
+         priority INT NOT NULL DEFAULT 0
+     );",
+    name = "create_policy",
+    requires = ["create_schema_pglearned"]
+);
+
+extension_sql!(
+    "CREATE TRIGGER policy_changed
+         AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON pgl.policy
+         FOR EACH STATEMENT EXECUTE FUNCTION pgl_policy_changed();",
+    name = "create_policy_trigger",
+    requires = ["create_policy", pgl_policy_changed]
+);
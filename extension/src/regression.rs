@@ -0,0 +1,223 @@
+use pgrx::datum::TimestampWithTimeZone;
+use pgrx::prelude::*;
+use pgrx::{pg_sys, GucSetting};
+
+use crate::utils::internal_lookup;
+
+pub static PGL_REGRESSION_GUARD: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static PGL_REGRESSION_FACTOR: GucSetting<f64> = GucSetting::<f64>::new(2.0);
+pub static PGL_REGRESSION_MIN_EXECUTIONS: GucSetting<i32> = GucSetting::<i32>::new(5);
+pub static PGL_REGRESSION_PIN_DURATION: GucSetting<i32> = GucSetting::<i32>::new(3600);
+
+/// The arm a query is pinned to after its learned plans regressed.
+pub unsafe fn pinned_arm(parse: *mut pg_sys::Query) -> Option<i32> {
+    if !PGL_REGRESSION_GUARD.get() || (*parse).queryId == 0 {
+        return None;
+    }
+
+    let query_id = (*parse).queryId as i64;
+    let arm = internal_lookup(|| {
+        Spi::connect(|client| {
+            // Missing where pgl is loaded but not installed, e.g. by LOAD.
+            let installed = client
+                .select(
+                    "SELECT to_regclass('pgl.regression_pins') IS NOT NULL",
+                    Some(1),
+                    &[],
+                )?
+                .first()
+                .get_one::<bool>()?;
+            if installed != Some(true) {
+                return Ok(None);
+            }
+
+            let args = vec![pgrx::datum::DatumWithOid::from(query_id)];
+            let res = client.select(
+                "SELECT arm FROM pgl.regression_pins WHERE query_id = $1 AND pinned_until > now()",
+                Some(1),
+                &args,
+            )?;
+            if res.is_empty() {
+                return Ok(None);
+            }
+            res.first().get_one::<i32>()
+        })
+    });
+
+    match arm {
+        Ok(arm) => arm,
+        Err(err) => {
+            pgrx::warning!("failed to read regression pin: {err}");
+            None
+        }
+    }
+}
+
+extension_sql!(
+    "CREATE TABLE IF NOT EXISTS pgl.plan_latencies (
+         query_id BIGINT NOT NULL,
+         arm INT NOT NULL,
+         executions BIGINT NOT NULL,
+         mean_ms FLOAT8 NOT NULL,
+         updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+         PRIMARY KEY (query_id, arm)
+     );
+     CREATE TABLE IF NOT EXISTS pgl.regression_pins (
+         query_id BIGINT PRIMARY KEY,
+         arm INT NOT NULL,
+         learned_arm INT NOT NULL,
+         learned_ms FLOAT8 NOT NULL,
+         native_ms FLOAT8 NOT NULL,
+         pinned_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+         pinned_until TIMESTAMPTZ NOT NULL
+     );",
+    name = "create_regression_pins",
+    requires = ["create_schema_pglearned"]
+);
+
+/// Folds a feedback record's execution time into the mean latency of the
+/// arm it ran. Default planner executions count as the native arm.
+const OBSERVE_LATENCY_SQL: &str = "INSERT INTO pgl.plan_latencies AS l
+         (query_id, arm, executions, mean_ms)
+     SELECT (r->>'query_id')::bigint, coalesce((r->>'arm')::int, $2), 1,
+            (r->>'execution_ms')::float8
+     FROM (SELECT $1::jsonb AS r) AS record
+     WHERE (r->>'query_id')::bigint <> 0
+     ON CONFLICT (query_id, arm) DO UPDATE
+     SET executions = l.executions + 1,
+         mean_ms = l.mean_ms + (excluded.mean_ms - l.mean_ms) / (l.executions + 1),
+         updated_at = now()";
+
+/// Pins the query of a learned execution to its fastest observed arm when
+/// the learned arm is slower than the native one by more than the factor.
+const PIN_REGRESSION_SQL: &str = "INSERT INTO pgl.regression_pins
+         (query_id, arm, learned_arm, learned_ms, native_ms, pinned_until)
+     SELECT learned.query_id, best.arm, learned.arm, learned.mean_ms, native.mean_ms,
+            now() + make_interval(secs => $5)
+     FROM (SELECT $1::jsonb AS r) AS record
+     JOIN pgl.plan_latencies learned
+       ON learned.query_id = (r->>'query_id')::bigint AND learned.arm = (r->>'arm')::int
+     JOIN pgl.plan_latencies native
+       ON native.query_id = learned.query_id AND native.arm = $2
+     CROSS JOIN LATERAL (
+         SELECT arm FROM pgl.plan_latencies b
+         WHERE b.query_id = learned.query_id AND b.executions >= $4
+         ORDER BY b.mean_ms LIMIT 1
+     ) AS best
+     WHERE r->>'planner_mode' IN ('remote', 'model')
+       AND NOT (r->>'fallback')::boolean
+       AND NOT coalesce((r->>'pinned')::boolean, false)
+       AND r->>'shadow_arm' IS NULL
+       AND learned.arm <> $2
+       AND learned.executions >= $4
+       AND native.executions >= $4
+       AND learned.mean_ms > $3 * native.mean_ms
+     ON CONFLICT (query_id) DO UPDATE
+     SET arm = excluded.arm,
+         learned_arm = excluded.learned_arm,
+         learned_ms = excluded.learned_ms,
+         native_ms = excluded.native_ms,
+         pinned_at = now(),
+         pinned_until = excluded.pinned_until";
+
+/// Called for every feedback record written to `pgl.feedback_log`.
+pub fn observe(record: &str, native_arm: i32) -> Result<(), pgrx::spi::SpiError> {
+    Spi::run_with_args(
+        OBSERVE_LATENCY_SQL,
+        &[
+            pgrx::datum::DatumWithOid::from(record),
+            pgrx::datum::DatumWithOid::from(native_arm),
+        ],
+    )?;
+
+    if !PGL_REGRESSION_GUARD.get() {
+        return Ok(());
+    }
+
+    Spi::run_with_args(
+        PIN_REGRESSION_SQL,
+        &[
+            pgrx::datum::DatumWithOid::from(record),
+            pgrx::datum::DatumWithOid::from(native_arm),
+            pgrx::datum::DatumWithOid::from(PGL_REGRESSION_FACTOR.get()),
+            pgrx::datum::DatumWithOid::from(PGL_REGRESSION_MIN_EXECUTIONS.get() as i64),
+            pgrx::datum::DatumWithOid::from(PGL_REGRESSION_PIN_DURATION.get() as f64),
+        ],
+    )
+}
+
+#[pg_extern]
+fn pgl_regression_pins() -> anyhow::Result<
+    TableIterator<
+        'static,
+        (
+            name!(query_id, i64),
+            name!(arm, i32),
+            name!(learned_arm, i32),
+            name!(learned_ms, f64),
+            name!(native_ms, f64),
+            name!(pinned_at, TimestampWithTimeZone),
+            name!(pinned_until, TimestampWithTimeZone),
+        ),
+    >,
+> {
+    let pins = Spi::connect(|client| {
+        let res = client.select(
+            "SELECT query_id, arm, learned_arm, learned_ms, native_ms, pinned_at, pinned_until
+             FROM pgl.regression_pins
+             WHERE pinned_until > now()
+             ORDER BY pinned_at DESC",
+            None,
+            &[],
+        )?;
+
+        let mut pins = Vec::new();
+        for row in res {
+            if let (
+                Some(query_id),
+                Some(arm),
+                Some(learned_arm),
+                Some(learned_ms),
+                Some(native_ms),
+                Some(pinned_at),
+                Some(pinned_until),
+            ) = (
+                row.get_by_name::<i64, _>("query_id")?,
+                row.get_by_name::<i32, _>("arm")?,
+                row.get_by_name::<i32, _>("learned_arm")?,
+                row.get_by_name::<f64, _>("learned_ms")?,
+                row.get_by_name::<f64, _>("native_ms")?,
+                row.get_by_name::<TimestampWithTimeZone, _>("pinned_at")?,
+                row.get_by_name::<TimestampWithTimeZone, _>("pinned_until")?,
+            ) {
+                pins.push((
+                    query_id,
+                    arm,
+                    learned_arm,
+                    learned_ms,
+                    native_ms,
+                    pinned_at,
+                    pinned_until,
+                ));
+            }
+        }
+        Ok::<_, pgrx::spi::SpiError>(pins)
+    })?;
+
+    Ok(TableIterator::new(pins))
+}
+
+/// Removes the pin of one query, or of every query when none is given, and
+/// returns how many pins were removed.
+#[pg_extern]
+fn pgl_regression_pins_clear(query_id: default!(Option<i64>, "NULL")) -> anyhow::Result<i64> {
+    let cleared: Option<i64> = Spi::get_one_with_args(
+        "WITH cleared AS (
+             DELETE FROM pgl.regression_pins WHERE $1 IS NULL OR query_id = $1 RETURNING 1
+         )
+         SELECT count(*) FROM cleared",
+        &vec![pgrx::datum::DatumWithOid::from(query_id)],
+    )?;
+
+    Ok(cleared.unwrap_or(0))
+}
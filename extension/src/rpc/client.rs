@@ -3,36 +3,128 @@ pub mod pgl_rpc {
 }
 
 use anyhow::{bail, ensure};
-use pgl_rpc::{pgl_remote_client::PglRemoteClient, CardinalityEstimateRequest, ChoosePlanRequest};
+use pgl_rpc::{
+    pgl_remote_client::PglRemoteClient, CardinalityEstimateRequest, CardinalityEstimateResponse,
+    ChoosePlanRequest, ChoosePlanResponse, ModelVersion, ReportExecutionRequest,
+};
+use prost::Message;
 use tonic::transport::Channel;
 
+use super::metadata::RequestMetadata;
+use super::worker::{self, RemoteMethod};
+use super::{PglRemoteTransport, RemoteServer, PGL_REMOTE_TRANSPORT};
+use crate::models;
+
+impl From<&models::ModelVersion> for ModelVersion {
+    fn from(model: &models::ModelVersion) -> Self {
+        Self {
+            name: model.name.clone(),
+            version: model.version,
+        }
+    }
+}
+
+enum Transport {
+    /// This backend's own connection.
+    Direct {
+        runtime: tokio::runtime::Runtime,
+        client: PglRemoteClient<Channel>,
+    },
+    /// The remote worker's shared connection.
+    Worker { url: String },
+}
+
 pub struct PglRemoteSyncClient {
-    runtime: tokio::runtime::Runtime,
-    client: PglRemoteClient<Channel>,
+    transport: Transport,
+    /// Encoded size of the requests sent so far.
+    bytes_sent: usize,
+    /// Attached to every request.
+    metadata: RequestMetadata,
 }
 
 impl PglRemoteSyncClient {
+    /// Connects through the transport `pgl.remote_transport` selects.
+    /// `addr` is an `http://`, `https://` or `unix://` URL.
     pub fn connect(addr: String) -> anyhow::Result<Self> {
+        match PGL_REMOTE_TRANSPORT.get() {
+            PglRemoteTransport::Direct => Self::connect_direct(RemoteServer::new(addr)),
+            PglRemoteTransport::Worker => Ok(Self {
+                transport: Transport::Worker { url: addr },
+                bytes_sent: 0,
+                metadata: RequestMetadata::default(),
+            }),
+        }
+    }
+
+    /// Connects from this process; the only transport usable from threads
+    /// other than the backend's main one.
+    pub fn connect_direct(server: RemoteServer) -> anyhow::Result<Self> {
         let runtime = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()?;
-        let client = runtime.block_on(PglRemoteClient::connect(addr))?;
-        Ok(Self { runtime, client })
+        let client = PglRemoteClient::new(runtime.block_on(server.connect())?);
+        Ok(Self {
+            transport: Transport::Direct { runtime, client },
+            bytes_sent: 0,
+            metadata: RequestMetadata::default(),
+        })
+    }
+
+    pub fn bytes_sent(&self) -> usize {
+        self.bytes_sent
     }
 
-    pub fn choose_plan(&mut self, plans: Vec<String>) -> anyhow::Result<i32> {
-        let request = ChoosePlanRequest { plans };
-        let response = self.runtime.block_on(self.client.choose_plan(request))?;
-        Ok(response.into_inner().chosen_plan_index)
+    /// Sets the metadata sent with the following requests.
+    pub fn set_metadata(&mut self, metadata: RequestMetadata) {
+        self.metadata = metadata;
     }
 
-    pub fn cardinality_estimate(&mut self, rel_opts: Vec<String>) -> anyhow::Result<Vec<i64>> {
+    pub fn choose_plan(
+        &mut self,
+        plans: Vec<String>,
+        features: Vec<String>,
+        model: Option<&models::ModelVersion>,
+    ) -> anyhow::Result<i32> {
+        let request = ChoosePlanRequest {
+            plans,
+            features,
+            model: model.map(ModelVersion::from),
+        };
+        self.bytes_sent += request.encoded_len();
+        let response: ChoosePlanResponse = match &mut self.transport {
+            Transport::Direct { runtime, client } => runtime
+                .block_on(client.choose_plan(self.metadata.request(request)))?
+                .into_inner(),
+            Transport::Worker { url } => {
+                worker::call(url, &self.metadata, RemoteMethod::ChoosePlan, &request)?
+            }
+        };
+        Ok(response.chosen_plan_index)
+    }
+
+    pub fn cardinality_estimate(
+        &mut self,
+        rel_opts: Vec<String>,
+        model: Option<&models::ModelVersion>,
+    ) -> anyhow::Result<Vec<i64>> {
         let expected_len = rel_opts.len();
-        let request = CardinalityEstimateRequest { rel_opts };
-        let response = self
-            .runtime
-            .block_on(self.client.cardinality_estimate(request))?;
-        let estimates = response.into_inner().cardinality_estimates;
+        let request = CardinalityEstimateRequest {
+            rel_opts,
+            model: model.map(ModelVersion::from),
+        };
+        self.bytes_sent += request.encoded_len();
+        let response: CardinalityEstimateResponse = match &mut self.transport {
+            Transport::Direct { runtime, client } => runtime
+                .block_on(client.cardinality_estimate(self.metadata.request(request)))?
+                .into_inner(),
+            Transport::Worker { url } => worker::call(
+                url,
+                &self.metadata,
+                RemoteMethod::CardinalityEstimate,
+                &request,
+            )?,
+        };
+        let estimates = response.cardinality_estimates;
 
         ensure!(
             estimates.len() == expected_len,
@@ -46,4 +138,15 @@ impl PglRemoteSyncClient {
 
         Ok(estimates)
     }
+
+    /// Execution reports are sent from the reporter thread, which can only
+    /// use a direct connection.
+    pub fn report_execution(&mut self, request: ReportExecutionRequest) -> anyhow::Result<()> {
+        let Transport::Direct { runtime, client } = &mut self.transport else {
+            bail!("execution reports need a direct connection");
+        };
+        self.bytes_sent += request.encoded_len();
+        runtime.block_on(client.report_execution(self.metadata.request(request)))?;
+        Ok(())
+    }
 }
@@ -0,0 +1,238 @@
+use std::cell::RefCell;
+use std::ffi::CString;
+
+use pgrx::prelude::*;
+use pgrx::{pg_sys, GucSetting};
+use tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue};
+
+use crate::utils::{cstr_to_string, list_ptrs, statement_at, statement_text};
+
+pub static PGL_TRACEPARENT: GucSetting<Option<CString>> = GucSetting::<Option<CString>>::new(None);
+
+/// A statement being planned, and the metadata of its requests once the
+/// first one is made.
+struct Planning {
+    parse: *mut pg_sys::Query,
+    query_string: *const std::os::raw::c_char,
+    metadata: Option<RequestMetadata>,
+}
+
+thread_local! {
+    static PLANNING: RefCell<Option<Planning>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, which plans `parse`, so that its requests share the metadata
+/// built for the first of them.
+pub fn planning<T>(
+    parse: *mut pg_sys::Query,
+    query_string: *const std::os::raw::c_char,
+    f: impl FnOnce() -> T,
+) -> T {
+    struct Reset(Option<Planning>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            PLANNING.with(|planning| *planning.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _reset = Reset(PLANNING.with(|planning| {
+        planning.borrow_mut().replace(Planning {
+            parse,
+            query_string,
+            metadata: None,
+        })
+    }));
+    f()
+}
+
+/// gRPC metadata identifying the backend and statement a request is made
+/// for, so server-side traces can be linked back to the query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestMetadata(Vec<(String, String)>);
+
+/// Whether `value` is a W3C trace context `traceparent` header.
+fn valid_traceparent(value: &str) -> bool {
+    let fields = value.split('-').collect::<Vec<_>>();
+    let hex = |field: &str, len: usize| {
+        field.len() == len
+            && field
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+    let zero = |field: &str| field.bytes().all(|b| b == b'0');
+
+    match fields.as_slice() {
+        [version, trace_id, parent_id, flags] => {
+            hex(version, 2)
+                && *version != "ff"
+                && hex(trace_id, 32)
+                && !zero(trace_id)
+                && hex(parent_id, 16)
+                && !zero(parent_id)
+                && hex(flags, 2)
+        }
+        _ => false,
+    }
+}
+
+/// The bodies of the comments in `query`, skipping quoted literals and
+/// identifiers.
+fn comments(query: &str) -> Vec<&str> {
+    let mut comments = Vec::new();
+    let mut rest = query;
+    while let Some(start) = rest.find(['\'', '"', '-', '/']) {
+        let tail = &rest[start..];
+        rest = if let Some(quoted) = tail.strip_prefix(['\'', '"']) {
+            let quote = tail.as_bytes()[0] as char;
+            quoted.split_once(quote).map_or("", |(_, rest)| rest)
+        } else if let Some(comment) = tail.strip_prefix("--") {
+            comment.split_once('\n').map_or("", |(_, rest)| rest)
+        } else if let Some(comment) = tail.strip_prefix("/*") {
+            let (body, rest) = comment.split_once("*/").unwrap_or((comment, ""));
+            comments.push(body);
+            rest
+        } else {
+            &tail[1..]
+        };
+    }
+    comments
+}
+
+/// The `traceparent='...'` of a SQLCommenter style comment in `query`.
+fn comment_traceparent(query: &str) -> Option<String> {
+    comments(query).into_iter().find_map(|comment| {
+        comment.split(',').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            let value = value.trim().trim_matches('\'');
+            (key.trim() == "traceparent" && valid_traceparent(value)).then(|| value.to_string())
+        })
+    })
+}
+
+/// Percent-encodes what gRPC metadata values cannot carry, e.g. non-ASCII
+/// database and role names.
+fn encode_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte == b'%' || !(0x20..0x7f).contains(&byte) {
+            encoded.push_str(&format!("%{byte:02X}"));
+        } else {
+            encoded.push(byte as char);
+        }
+    }
+    encoded
+}
+
+impl RequestMetadata {
+    /// The metadata of the statement being planned.
+    pub unsafe fn planned() -> Self {
+        PLANNING.with(|planning| match planning.borrow_mut().as_mut() {
+            Some(planning) => planning
+                .metadata
+                .get_or_insert_with(|| {
+                    Self::current(
+                        Some((*planning.parse).queryId as i64),
+                        &statement_text(planning.parse, planning.query_string),
+                    )
+                })
+                .clone(),
+            None => Self::current(None, &executing_statement()),
+        })
+    }
+
+    /// Describes a statement of this backend, with the text `query`. The
+    /// `traceparent` of a comment in it takes precedence over
+    /// `pgl.traceparent`.
+    pub unsafe fn current(query_id: Option<i64>, query: &str) -> Self {
+        let mut metadata = vec![
+            ("pgl-backend-pid".to_string(), pg_sys::MyProcPid.to_string()),
+            // Formatted like %c in log_line_prefix.
+            (
+                "pgl-session-id".to_string(),
+                format!("{:x}.{:x}", pg_sys::MyStartTime, pg_sys::MyProcPid),
+            ),
+        ];
+        if let Some(query_id) = query_id.filter(|query_id| *query_id != 0) {
+            metadata.push(("pgl-query-id".to_string(), query_id.to_string()));
+        }
+        if let Some(database) = cstr_to_string(pg_sys::get_database_name(pg_sys::MyDatabaseId)) {
+            metadata.push(("pgl-database".to_string(), encode_value(&database)));
+        }
+        if let Some(user) = cstr_to_string(pg_sys::GetUserNameFromId(pg_sys::GetUserId(), true)) {
+            metadata.push(("pgl-user".to_string(), encode_value(&user)));
+        }
+
+        let traceparent = comment_traceparent(query).or_else(|| {
+            PGL_TRACEPARENT
+                .get()
+                .map(|traceparent| traceparent.to_string_lossy().trim().to_string())
+                .filter(|traceparent| valid_traceparent(traceparent))
+        });
+        if let Some(traceparent) = traceparent {
+            metadata.push(("traceparent".to_string(), traceparent));
+        }
+
+        Self(metadata)
+    }
+
+    /// Wraps `message` in a request carrying the metadata.
+    pub fn request<T>(&self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        for (key, value) in &self.0 {
+            if let (Ok(key), Ok(value)) = (
+                AsciiMetadataKey::from_bytes(key.as_bytes()),
+                AsciiMetadataValue::try_from(value.as_str()),
+            ) {
+                request.metadata_mut().insert(key, value);
+            }
+        }
+        request
+    }
+
+    /// One `key=value` line per entry, for the remote worker's queues.
+    pub fn encode(&self) -> String {
+        self.0
+            .iter()
+            .map(|(key, value)| format!("{key}={value}\n"))
+            .collect()
+    }
+
+    pub fn decode(encoded: &str) -> Self {
+        Self(
+            encoded
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+}
+
+/// The text of the statement the active portal runs, out of a query string
+/// that may hold several.
+unsafe fn executing_statement() -> String {
+    let portal = pg_sys::ActivePortal;
+    if portal.is_null() {
+        return cstr_to_string(pg_sys::debug_query_string as *mut _).unwrap_or_default();
+    }
+
+    match list_ptrs::<pg_sys::Node>((*portal).stmts).first() {
+        Some(&stmt) if (*stmt).type_ == pg_sys::NodeTag::T_PlannedStmt => {
+            let stmt = stmt.cast::<pg_sys::PlannedStmt>();
+            statement_at(
+                (*portal).sourceText,
+                (*stmt).stmt_location,
+                (*stmt).stmt_len,
+            )
+        }
+        _ => statement_at((*portal).sourceText, -1, 0),
+    }
+}
+
+/// The metadata requests made by the current statement carry, without the
+/// query id.
+#[pg_extern]
+fn pgl_request_metadata() -> TableIterator<'static, (name!(key, String), name!(value, String))> {
+    TableIterator::new(unsafe { RequestMetadata::current(None, &executing_statement()) }.0)
+}
@@ -3,13 +3,68 @@ use std::ffi::CString;
 use pgrx::GucSetting;
 
 pub mod client;
+pub mod metadata;
+pub mod report;
+pub mod transport;
+mod types;
+pub mod worker;
+
+pub use transport::RemoteServer;
+pub use types::PglRemoteTransport;
 
 pub static PGL_REMOTE_SERVER_URL: GucSetting<Option<CString>> =
     GucSetting::<Option<CString>>::new(None);
+pub static PGL_REMOTE_PLANNER_URL: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(None);
+pub static PGL_REMOTE_CARDINALITY_URL: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(None);
+pub static PGL_REMOTE_TRANSPORT: GucSetting<PglRemoteTransport> =
+    GucSetting::<PglRemoteTransport>::new(PglRemoteTransport::Direct);
+pub static PGL_REMOTE_TLS_CA: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(None);
+pub static PGL_REMOTE_TLS_CERT: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(None);
+pub static PGL_REMOTE_TLS_KEY: GucSetting<Option<CString>> =
+    GucSetting::<Option<CString>>::new(None);
+pub static PGL_REMOTE_WORKER: GucSetting<bool> = GucSetting::<bool>::new(false);
 
-pub fn remote_server_url() -> Option<String> {
-    PGL_REMOTE_SERVER_URL
+fn guc_url(setting: &GucSetting<Option<CString>>) -> Option<String> {
+    setting
         .get()
         .map(|url| url.to_string_lossy().into_owned())
         .filter(|url| !url.trim().is_empty())
 }
+
+pub fn remote_server_url() -> Option<String> {
+    guc_url(&PGL_REMOTE_SERVER_URL)
+}
+
+/// The server plan choices and execution reports go to.
+pub fn remote_planner_url() -> Option<String> {
+    guc_url(&PGL_REMOTE_PLANNER_URL).or_else(remote_server_url)
+}
+
+/// The server cardinality estimates come from.
+pub fn remote_cardinality_url() -> Option<String> {
+    guc_url(&PGL_REMOTE_CARDINALITY_URL).or_else(remote_server_url)
+}
+
+/// Runs `f` with every signal blocked, so threads it starts never run
+/// PostgreSQL's signal handlers.
+pub fn with_signals_blocked<R>(f: impl FnOnce() -> R) -> R {
+    unsafe {
+        let mut blocked: libc::sigset_t = std::mem::zeroed();
+        let mut previous: libc::sigset_t = std::mem::zeroed();
+        libc::sigfillset(&mut blocked);
+        libc::pthread_sigmask(libc::SIG_SETMASK, &blocked, &mut previous);
+
+        let result = f();
+
+        libc::pthread_sigmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+        result
+    }
+}
+
+pub unsafe fn register() {
+    worker::register();
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use pgrx::prelude::*;
+use pgrx::GucSetting;
+
+use super::client::pgl_rpc::ReportExecutionRequest;
+use super::client::PglRemoteSyncClient;
+use super::metadata::RequestMetadata;
+use super::RemoteServer;
+
+/// Reports waiting for the sender thread before new ones are dropped.
+const REPORT_QUEUE_LEN: usize = 64;
+/// Longest the sender thread waits to connect or for one report, so an
+/// unresponsive server cannot hold up the reports queued behind it.
+const REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A report, the server it goes to and the metadata sent with it.
+type Report = (RemoteServer, RequestMetadata, ReportExecutionRequest);
+
+pub static PGL_REPORT_EXECUTION: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+static REPORTER: OnceLock<Option<SyncSender<Report>>> = OnceLock::new();
+
+/// This backend's reports, by what became of them.
+static QUEUED: AtomicU64 = AtomicU64::new(0);
+static SENT: AtomicU64 = AtomicU64::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static FAILED: AtomicU64 = AtomicU64::new(0);
+
+pub fn enabled() -> bool {
+    PGL_REPORT_EXECUTION.get() && super::remote_planner_url().is_some()
+}
+
+/// Hands `request` about the statement `query` to this backend's sender
+/// thread. Never waits: the report is dropped when the queue is full or the
+/// thread could not be started.
+pub fn report(request: ReportExecutionRequest, query: &str) {
+    let Some(url) = super::remote_planner_url() else {
+        return;
+    };
+
+    let Some(sender) = REPORTER.get_or_init(start_reporter) else {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+        return;
+    };
+
+    // Gathered here, since the sender thread cannot call into PostgreSQL.
+    let metadata = unsafe { RequestMetadata::current(Some(request.query_id), query) };
+    let server = RemoteServer::new(url).with_timeout(REPORT_TIMEOUT);
+    match sender.try_send((server, metadata, request)) {
+        Ok(()) => {
+            QUEUED.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(TrySendError::Full(_)) => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            pgrx::debug1!("pgl: execution report queue is full, dropping report");
+        }
+        Err(TrySendError::Disconnected(_)) => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            pgrx::debug1!("pgl: execution reporter has stopped, dropping report");
+        }
+    }
+}
+
+/// This backend's execution reports: queued for the sender thread, sent,
+/// dropped without being queued, and lost because sending failed.
+#[pg_extern]
+fn pgl_stat_reports() -> TableIterator<
+    'static,
+    (
+        name!(queued, i64),
+        name!(sent, i64),
+        name!(dropped, i64),
+        name!(failed, i64),
+    ),
+> {
+    TableIterator::once((
+        QUEUED.load(Ordering::Relaxed) as i64,
+        SENT.load(Ordering::Relaxed) as i64,
+        DROPPED.load(Ordering::Relaxed) as i64,
+        FAILED.load(Ordering::Relaxed) as i64,
+    ))
+}
+
+fn start_reporter() -> Option<SyncSender<Report>> {
+    let (sender, receiver) = mpsc::sync_channel(REPORT_QUEUE_LEN);
+
+    let spawned = super::with_signals_blocked(|| {
+        std::thread::Builder::new()
+            .name("pgl reporter".to_string())
+            .spawn(move || send_reports(receiver))
+    });
+
+    match spawned {
+        Ok(_) => Some(sender),
+        Err(err) => {
+            pgrx::warning!("pgl: failed to start the execution reporter: {err}");
+            None
+        }
+    }
+}
+
+/// Runs on the sender thread; must not call into PostgreSQL.
+fn send_reports(receiver: Receiver<Report>) {
+    let mut client: Option<(RemoteServer, PglRemoteSyncClient)> = None;
+
+    for (url, metadata, request) in receiver {
+        if client
+            .as_ref()
+            .is_none_or(|(connected, _)| *connected != url)
+        {
+            client = PglRemoteSyncClient::connect_direct(url.clone())
+                .ok()
+                .map(|connected| (url, connected));
+        }
+
+        let Some((_, connected)) = client.as_mut() else {
+            FAILED.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+
+        connected.set_metadata(metadata);
+        // Reconnect with the next report; this one is lost.
+        if connected.report_execution(request).is_err() {
+            FAILED.fetch_add(1, Ordering::Relaxed);
+            client = None;
+        } else {
+            SENT.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::ffi::CString;
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use hyper_util::rt::TokioIo;
+use pgrx::GucSetting;
+use tokio::net::UnixStream;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity, Uri};
+use tower::service_fn;
+
+use super::{PGL_REMOTE_TLS_CA, PGL_REMOTE_TLS_CERT, PGL_REMOTE_TLS_KEY};
+
+fn guc_path(setting: &GucSetting<Option<CString>>) -> Option<String> {
+    setting
+        .get()
+        .map(|path| path.to_string_lossy().into_owned())
+        .filter(|path| !path.trim().is_empty())
+}
+
+fn unix_connector(
+    path: String,
+) -> impl tower::Service<
+    Uri,
+    Response = TokioIo<UnixStream>,
+    Error = std::io::Error,
+    Future = impl Future<Output = std::io::Result<TokioIo<UnixStream>>> + Send,
+> + Clone
+       + Send
+       + 'static {
+    service_fn(move |_: Uri| {
+        let path = path.clone();
+        async move { Ok(TokioIo::new(UnixStream::connect(path).await?)) }
+    })
+}
+
+/// Where and how to reach a remote server. Built on a backend's main
+/// thread, since GUCs must not be read from other threads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteServer {
+    pub url: String,
+    tls_ca: Option<String>,
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+    /// Bounds connecting and every request; unbounded when not set.
+    timeout: Option<Duration>,
+}
+
+impl RemoteServer {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            tls_ca: guc_path(&PGL_REMOTE_TLS_CA),
+            tls_cert: guc_path(&PGL_REMOTE_TLS_CERT),
+            tls_key: guc_path(&PGL_REMOTE_TLS_KEY),
+            timeout: None,
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    fn socket_path(&self) -> Option<String> {
+        self.url.strip_prefix("unix://").map(str::to_string)
+    }
+
+    fn tls_config(&self) -> anyhow::Result<ClientTlsConfig> {
+        let mut config = ClientTlsConfig::new();
+
+        config = match &self.tls_ca {
+            Some(ca) => {
+                let pem = std::fs::read(ca).with_context(|| format!("reading {ca}"))?;
+                config.ca_certificate(Certificate::from_pem(pem))
+            }
+            None => config.with_webpki_roots(),
+        };
+
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => {
+                let cert = std::fs::read(cert).with_context(|| format!("reading {cert}"))?;
+                let key = std::fs::read(key).with_context(|| format!("reading {key}"))?;
+                config = config.identity(Identity::from_pem(cert, key));
+            }
+            (None, None) => {}
+            _ => bail!("pgl.remote_tls_cert and pgl.remote_tls_key must be set together"),
+        }
+
+        Ok(config)
+    }
+
+    fn endpoint(&self) -> anyhow::Result<Endpoint> {
+        let mut endpoint = if self.socket_path().is_some() {
+            // The authority is unused; the connector picks the socket.
+            Endpoint::from_static("http://localhost")
+        } else if self.url.starts_with("https://") {
+            Endpoint::from_shared(self.url.clone())?.tls_config(self.tls_config()?)?
+        } else {
+            Endpoint::from_shared(self.url.clone())?
+        };
+
+        if let Some(timeout) = self.timeout {
+            endpoint = endpoint.connect_timeout(timeout).timeout(timeout);
+        }
+
+        Ok(endpoint)
+    }
+
+    pub async fn connect(&self) -> anyhow::Result<Channel> {
+        let endpoint = self.endpoint()?;
+        let channel = match self.socket_path() {
+            Some(path) => {
+                endpoint
+                    .connect_with_connector(unix_connector(path))
+                    .await?
+            }
+            None => endpoint.connect().await?,
+        };
+        Ok(channel)
+    }
+
+    /// Like `connect`, but connects on first use; must be called within a
+    /// tokio runtime.
+    pub fn connect_lazy(&self) -> anyhow::Result<Channel> {
+        let endpoint = self.endpoint()?;
+        let channel = match self.socket_path() {
+            Some(path) => endpoint.connect_with_connector_lazy(unix_connector(path)),
+            None => endpoint.connect_lazy(),
+        };
+        Ok(channel)
+    }
+}
@@ -0,0 +1,5 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, pgrx::PostgresGucEnum)]
+pub enum PglRemoteTransport {
+    Direct,
+    Worker,
+}
@@ -0,0 +1,716 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, ensure};
+use pgrx::bgworkers::*;
+use pgrx::lwlock::PgLwLock;
+use pgrx::prelude::*;
+use pgrx::shmem::*;
+use pgrx::{pg_shmem_init, pg_sys};
+use prost::Message;
+use tonic::transport::Channel;
+
+use super::client::pgl_rpc::pgl_remote_client::PglRemoteClient;
+use super::client::pgl_rpc::{CardinalityEstimateRequest, ChoosePlanRequest};
+use super::metadata::RequestMetadata;
+use super::RemoteServer;
+
+/// Requests the worker serves at the same time.
+const REMOTE_WORKER_SLOTS: usize = 16;
+/// Size of each direction's queue; longer messages are passed in chunks.
+const REMOTE_QUEUE_SIZE: usize = 64 * 1024;
+/// How long a backend waits for the worker before giving up.
+const REMOTE_WORKER_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteMethod {
+    ChoosePlan = 1,
+    CardinalityEstimate = 2,
+}
+
+impl RemoteMethod {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(RemoteMethod::ChoosePlan),
+            2 => Some(RemoteMethod::CardinalityEstimate),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SlotState {
+    #[default]
+    Free,
+    /// A backend is setting up the queues.
+    Claimed,
+    /// The queues are ready for the worker to attach.
+    Requested,
+    Serving,
+    /// The worker has sent the response and detached.
+    Done,
+    /// The backend left before the worker detached.
+    Abandoned,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct RemoteSlot {
+    state: SlotState,
+    owner: i32,
+}
+
+#[derive(Clone, Copy)]
+pub struct RemoteSlots {
+    worker_pid: i32,
+    worker: *mut pg_sys::PGPROC,
+    slots: [RemoteSlot; REMOTE_WORKER_SLOTS],
+}
+
+impl Default for RemoteSlots {
+    fn default() -> Self {
+        Self {
+            worker_pid: 0,
+            worker: std::ptr::null_mut(),
+            slots: [RemoteSlot::default(); REMOTE_WORKER_SLOTS],
+        }
+    }
+}
+
+unsafe impl PGRXSharedMemory for RemoteSlots {}
+
+static REMOTE_SLOTS: PgLwLock<RemoteSlots> = unsafe { PgLwLock::new(c"pgl_remote_slots") };
+
+static mut PREV_SHMEM_REQUEST_HOOK: pg_sys::shmem_request_hook_type = None;
+static mut PREV_SHMEM_STARTUP_HOOK: pg_sys::shmem_startup_hook_type = None;
+
+/// Two queues per slot, the request followed by the response.
+static mut REMOTE_QUEUES: *mut u8 = std::ptr::null_mut();
+
+/// Whether the worker and its queues were registered, i.e. the library was
+/// preloaded with `pgl.remote_worker` on. The worker is only available once
+/// it has attached, see `worker_pid`.
+static mut REMOTE_WORKER_REGISTERED: bool = false;
+
+const REMOTE_QUEUES_SIZE: usize = REMOTE_WORKER_SLOTS * 2 * REMOTE_QUEUE_SIZE;
+
+unsafe fn queue_address(slot: usize, response: bool) -> *mut std::os::raw::c_void {
+    REMOTE_QUEUES
+        .add((slot * 2 + usize::from(response)) * REMOTE_QUEUE_SIZE)
+        .cast()
+}
+
+/// The method, then the URL and the metadata, each preceded by its length,
+/// then the encoded request.
+fn encode_request(
+    url: &str,
+    metadata: &RequestMetadata,
+    method: RemoteMethod,
+    body: &[u8],
+) -> Vec<u8> {
+    let metadata = metadata.encode();
+    let mut message = Vec::with_capacity(9 + url.len() + metadata.len() + body.len());
+    message.push(method as u8);
+    for field in [url, metadata.as_str()] {
+        message.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        message.extend_from_slice(field.as_bytes());
+    }
+    message.extend_from_slice(body);
+    message
+}
+
+/// Splits the length-prefixed field at the head of `message` off.
+fn decode_field(message: &[u8]) -> anyhow::Result<(String, &[u8])> {
+    ensure!(message.len() >= 4, "truncated request");
+    let len = u32::from_le_bytes(message[..4].try_into()?) as usize;
+    ensure!(message.len() >= 4 + len, "truncated request");
+    let field = String::from_utf8(message[4..4 + len].to_vec())?;
+    Ok((field, &message[4 + len..]))
+}
+
+fn decode_request(
+    message: &[u8],
+) -> anyhow::Result<(String, RequestMetadata, RemoteMethod, Vec<u8>)> {
+    let (&method, rest) = message
+        .split_first()
+        .ok_or_else(|| anyhow!("truncated request"))?;
+    let method = RemoteMethod::from_u8(method).ok_or_else(|| anyhow!("unknown method {method}"))?;
+    let (url, rest) = decode_field(rest)?;
+    let (metadata, body) = decode_field(rest)?;
+    Ok((
+        url,
+        RequestMetadata::decode(&metadata),
+        method,
+        body.to_vec(),
+    ))
+}
+
+fn encode_response(result: anyhow::Result<Vec<u8>>) -> Vec<u8> {
+    match result {
+        Ok(body) => [&[0u8][..], &body].concat(),
+        Err(err) => [&[1u8][..], err.to_string().as_bytes()].concat(),
+    }
+}
+
+fn decode_response(message: &[u8]) -> anyhow::Result<Vec<u8>> {
+    match message.split_first() {
+        Some((0, body)) => Ok(body.to_vec()),
+        Some((_, err)) => bail!("{}", String::from_utf8_lossy(err)),
+        None => bail!("empty response from the remote worker"),
+    }
+}
+
+/// Detaches from the slot's queues and gives the slot back when the call
+/// ends, including when it is cancelled.
+struct SlotGuard {
+    slot: usize,
+    request: *mut pg_sys::shm_mq_handle,
+    response: *mut pg_sys::shm_mq_handle,
+}
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        unsafe {
+            pg_sys::shm_mq_detach(self.request);
+            pg_sys::shm_mq_detach(self.response);
+        }
+
+        let mut slots = REMOTE_SLOTS.exclusive();
+        let slot = &mut slots.slots[self.slot];
+        if slot.state == SlotState::Serving {
+            slot.state = SlotState::Abandoned;
+        } else {
+            *slot = RemoteSlot::default();
+        }
+    }
+}
+
+unsafe fn wait_for_worker(deadline: Instant) -> anyhow::Result<()> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        bail!(
+            "remote worker did not answer within {}s",
+            REMOTE_WORKER_TIMEOUT.as_secs()
+        );
+    }
+
+    pg_sys::WaitLatch(
+        pg_sys::MyLatch,
+        (pg_sys::WL_LATCH_SET | pg_sys::WL_TIMEOUT | pg_sys::WL_EXIT_ON_PM_DEATH) as i32,
+        remaining.as_millis() as _,
+        pg_sys::PG_WAIT_EXTENSION,
+    );
+    pg_sys::ResetLatch(pg_sys::MyLatch);
+    pgrx::check_for_interrupts!();
+    Ok(())
+}
+
+unsafe fn exchange(message: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if !REMOTE_WORKER_REGISTERED {
+        bail!("pgl.remote_transport = 'worker' requires pgl in shared_preload_libraries and pgl.remote_worker = on");
+    }
+
+    let (slot, worker) = {
+        let mut slots = REMOTE_SLOTS.exclusive();
+        let worker = slots.worker;
+        if slots.worker_pid == 0 || worker.is_null() || (*worker).pid != slots.worker_pid {
+            bail!("the remote worker is not running");
+        }
+
+        let Some(slot) = slots
+            .slots
+            .iter()
+            .position(|slot| slot.state == SlotState::Free)
+        else {
+            bail!("all {REMOTE_WORKER_SLOTS} remote worker slots are busy");
+        };
+
+        slots.slots[slot] = RemoteSlot {
+            state: SlotState::Claimed,
+            owner: pg_sys::MyProcPid,
+        };
+        (slot, worker)
+    };
+
+    let request = pg_sys::shm_mq_create(queue_address(slot, false), REMOTE_QUEUE_SIZE);
+    let response = pg_sys::shm_mq_create(queue_address(slot, true), REMOTE_QUEUE_SIZE);
+    pg_sys::shm_mq_set_sender(request, pg_sys::MyProc);
+    pg_sys::shm_mq_set_receiver(response, pg_sys::MyProc);
+
+    let guard = SlotGuard {
+        slot,
+        request: pg_sys::shm_mq_attach(request, std::ptr::null_mut(), std::ptr::null_mut()),
+        response: pg_sys::shm_mq_attach(response, std::ptr::null_mut(), std::ptr::null_mut()),
+    };
+
+    REMOTE_SLOTS.exclusive().slots[slot].state = SlotState::Requested;
+    pg_sys::SetLatch(&mut (*worker).procLatch);
+
+    let deadline = Instant::now() + REMOTE_WORKER_TIMEOUT;
+    loop {
+        match pg_sys::shm_mq_send(
+            guard.request,
+            message.len(),
+            message.as_ptr().cast(),
+            true,
+            true,
+        ) {
+            pg_sys::shm_mq_result::SHM_MQ_SUCCESS => break,
+            pg_sys::shm_mq_result::SHM_MQ_WOULD_BLOCK => wait_for_worker(deadline)?,
+            _ => bail!("the remote worker dropped the request"),
+        }
+    }
+
+    loop {
+        let mut len = 0;
+        let mut data = std::ptr::null_mut();
+        match pg_sys::shm_mq_receive(guard.response, &mut len, &mut data, true) {
+            pg_sys::shm_mq_result::SHM_MQ_SUCCESS => {
+                return Ok(std::slice::from_raw_parts(data as *const u8, len).to_vec());
+            }
+            pg_sys::shm_mq_result::SHM_MQ_WOULD_BLOCK => wait_for_worker(deadline)?,
+            _ => bail!("the remote worker dropped the request"),
+        }
+    }
+}
+
+/// Sends `request` to `url` through the remote worker's connection.
+pub fn call<Resp: Message + Default>(
+    url: &str,
+    metadata: &RequestMetadata,
+    method: RemoteMethod,
+    request: &impl Message,
+) -> anyhow::Result<Resp> {
+    let message = encode_request(url, metadata, method, &request.encode_to_vec());
+    let response = unsafe { exchange(&message)? };
+    Ok(Resp::decode(decode_response(&response)?.as_slice())?)
+}
+
+enum Stage {
+    Receiving,
+    InFlight(Receiver<Vec<u8>>),
+    Sending(Vec<u8>),
+}
+
+struct Serving {
+    request: *mut pg_sys::shm_mq_handle,
+    response: *mut pg_sys::shm_mq_handle,
+    stage: Stage,
+}
+
+impl Serving {
+    unsafe fn attach(slot: usize) -> Self {
+        let request = queue_address(slot, false).cast::<pg_sys::shm_mq>();
+        let response = queue_address(slot, true).cast::<pg_sys::shm_mq>();
+        pg_sys::shm_mq_set_receiver(request, pg_sys::MyProc);
+        pg_sys::shm_mq_set_sender(response, pg_sys::MyProc);
+
+        Self {
+            request: pg_sys::shm_mq_attach(request, std::ptr::null_mut(), std::ptr::null_mut()),
+            response: pg_sys::shm_mq_attach(response, std::ptr::null_mut(), std::ptr::null_mut()),
+            stage: Stage::Receiving,
+        }
+    }
+
+    unsafe fn detach(self) {
+        pg_sys::shm_mq_detach(self.request);
+        pg_sys::shm_mq_detach(self.response);
+    }
+}
+
+async fn dispatch(
+    mut client: PglRemoteClient<Channel>,
+    metadata: RequestMetadata,
+    method: RemoteMethod,
+    body: Vec<u8>,
+) -> anyhow::Result<Vec<u8>> {
+    let body = body.as_slice();
+    Ok(match method {
+        RemoteMethod::ChoosePlan => client
+            .choose_plan(metadata.request(ChoosePlanRequest::decode(body)?))
+            .await?
+            .into_inner()
+            .encode_to_vec(),
+        RemoteMethod::CardinalityEstimate => client
+            .cardinality_estimate(metadata.request(CardinalityEstimateRequest::decode(body)?))
+            .await?
+            .into_inner()
+            .encode_to_vec(),
+    })
+}
+
+/// A pipe the runtime's threads write to when a response is ready, since
+/// they cannot set the worker's latch; the worker waits on both.
+struct Wakeup {
+    read: libc::c_int,
+    write: libc::c_int,
+}
+
+impl Wakeup {
+    fn new() -> std::io::Result<Self> {
+        let mut fds = [0; 2];
+        unsafe {
+            if libc::pipe(fds.as_mut_ptr()) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            for fd in fds {
+                libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK);
+            }
+        }
+        Ok(Self {
+            read: fds[0],
+            write: fds[1],
+        })
+    }
+
+    /// Safe from any thread; when the pipe is full the worker is woken
+    /// already.
+    fn notify(write: libc::c_int) {
+        unsafe { libc::write(write, [1u8].as_ptr().cast(), 1) };
+    }
+
+    /// Waits for the latch, a response or `timeout`; returns false once the
+    /// worker is asked to terminate.
+    unsafe fn wait(&self, timeout: Duration) -> bool {
+        let events = pg_sys::WaitLatchOrSocket(
+            pg_sys::MyLatch,
+            (pg_sys::WL_LATCH_SET
+                | pg_sys::WL_SOCKET_READABLE
+                | pg_sys::WL_TIMEOUT
+                | pg_sys::WL_EXIT_ON_PM_DEATH) as i32,
+            self.read,
+            timeout.as_millis() as _,
+            pg_sys::PG_WAIT_EXTENSION,
+        );
+        pg_sys::ResetLatch(pg_sys::MyLatch);
+
+        if events & pg_sys::WL_SOCKET_READABLE as i32 != 0 {
+            let mut buf = [0u8; 64];
+            while libc::read(self.read, buf.as_mut_ptr().cast(), buf.len()) > 0 {}
+        }
+
+        pgrx::check_for_interrupts!();
+        !BackgroundWorker::sigterm_received()
+    }
+}
+
+/// The worker's state: one channel per server, multiplexing every request
+/// sent to it.
+struct RemoteWorker {
+    runtime: tokio::runtime::Runtime,
+    wakeup: Wakeup,
+    clients: HashMap<String, PglRemoteClient<Channel>>,
+    serving: HashMap<usize, Serving>,
+}
+
+impl RemoteWorker {
+    fn client(&mut self, url: String) -> anyhow::Result<PglRemoteClient<Channel>> {
+        if let Some(client) = self.clients.get(&url) {
+            return Ok(client.clone());
+        }
+
+        let _runtime = self.runtime.enter();
+        let client = PglRemoteClient::new(RemoteServer::new(url.clone()).connect_lazy()?);
+        self.clients.insert(url, client.clone());
+        Ok(client)
+    }
+
+    fn start(&mut self, message: &[u8]) -> Stage {
+        let started = decode_request(message).and_then(|(url, metadata, method, body)| {
+            Ok((self.client(url)?, metadata, method, body))
+        });
+
+        match started {
+            Ok((client, metadata, method, body)) => {
+                let (sender, receiver) = mpsc::sync_channel(1);
+                let wakeup = self.wakeup.write;
+                self.runtime.spawn(async move {
+                    let response = dispatch(client, metadata, method, body).await;
+                    let _ = sender.send(encode_response(response));
+                    Wakeup::notify(wakeup);
+                });
+                Stage::InFlight(receiver)
+            }
+            Err(err) => Stage::Sending(encode_response(Err(err))),
+        }
+    }
+
+    /// Moves a request along; returns whether it is finished.
+    unsafe fn progress(&mut self, serving: &mut Serving) -> bool {
+        if let Stage::Receiving = serving.stage {
+            let mut len = 0;
+            let mut data = std::ptr::null_mut();
+            match pg_sys::shm_mq_receive(serving.request, &mut len, &mut data, true) {
+                pg_sys::shm_mq_result::SHM_MQ_SUCCESS => {
+                    let message = std::slice::from_raw_parts(data as *const u8, len);
+                    serving.stage = self.start(message);
+                }
+                pg_sys::shm_mq_result::SHM_MQ_WOULD_BLOCK => return false,
+                _ => return true,
+            }
+        }
+
+        if let Stage::InFlight(receiver) = &serving.stage {
+            serving.stage = match receiver.try_recv() {
+                Ok(response) => Stage::Sending(response),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => {
+                    Stage::Sending(encode_response(Err(anyhow!("the request was lost"))))
+                }
+            };
+        }
+
+        if let Stage::Sending(response) = &serving.stage {
+            return pg_sys::shm_mq_send(
+                serving.response,
+                response.len(),
+                response.as_ptr().cast(),
+                true,
+                true,
+            ) != pg_sys::shm_mq_result::SHM_MQ_WOULD_BLOCK;
+        }
+
+        false
+    }
+
+    unsafe fn accept_requests(&mut self) {
+        let requested = {
+            let mut slots = REMOTE_SLOTS.exclusive();
+            let mut requested = Vec::new();
+            for (index, slot) in slots.slots.iter_mut().enumerate() {
+                if slot.state == SlotState::Requested {
+                    slot.state = SlotState::Serving;
+                    requested.push(index);
+                }
+            }
+            requested
+        };
+
+        for slot in requested {
+            self.serving.insert(slot, Serving::attach(slot));
+        }
+    }
+
+    unsafe fn serve(&mut self) {
+        let slots = self.serving.keys().copied().collect::<Vec<_>>();
+        for slot in slots {
+            let Some(mut serving) = self.serving.remove(&slot) else {
+                continue;
+            };
+
+            if !self.progress(&mut serving) {
+                self.serving.insert(slot, serving);
+                continue;
+            }
+
+            serving.detach();
+            let mut slots = REMOTE_SLOTS.exclusive();
+            let state = &mut slots.slots[slot];
+            if state.state == SlotState::Abandoned {
+                *state = RemoteSlot::default();
+            } else {
+                state.state = SlotState::Done;
+            }
+        }
+    }
+
+    /// Frees the slots of backends that exited without giving them back.
+    unsafe fn reclaim_slots(&mut self) {
+        let mut slots = REMOTE_SLOTS.exclusive();
+        for (index, slot) in slots.slots.iter_mut().enumerate() {
+            if slot.state == SlotState::Free || !pg_sys::BackendPidGetProc(slot.owner).is_null() {
+                continue;
+            }
+
+            if let Some(serving) = self.serving.remove(&index) {
+                serving.detach();
+            }
+            *slot = RemoteSlot::default();
+        }
+    }
+}
+
+/// Keeps one connection per model server and serves the requests backends
+/// hand over through the slot queues.
+#[pg_guard]
+#[no_mangle]
+pub extern "C-unwind" fn pgl_remote_worker_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+
+    let runtime = super::with_signals_blocked(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+    });
+    let runtime = match runtime {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            pgrx::log!("pgl: failed to start the remote worker runtime: {err}");
+            return;
+        }
+    };
+
+    let wakeup = match Wakeup::new() {
+        Ok(wakeup) => wakeup,
+        Err(err) => {
+            pgrx::log!("pgl: failed to set up the remote worker: {err}");
+            return;
+        }
+    };
+
+    let mut worker = RemoteWorker {
+        runtime,
+        wakeup,
+        clients: HashMap::new(),
+        serving: HashMap::new(),
+    };
+
+    unsafe {
+        let mut slots = REMOTE_SLOTS.exclusive();
+        slots.worker_pid = pg_sys::MyProcPid;
+        slots.worker = pg_sys::MyProc;
+        // Requests a previous worker was serving are never answered; their
+        // backends time out and give the slots back.
+        for slot in slots.slots.iter_mut() {
+            match slot.state {
+                SlotState::Serving => slot.state = SlotState::Done,
+                SlotState::Abandoned => *slot = RemoteSlot::default(),
+                _ => {}
+            }
+        }
+    }
+
+    let mut reclaimed = Instant::now();
+    loop {
+        if !unsafe { worker.wakeup.wait(Duration::from_secs(1)) } {
+            break;
+        }
+
+        if BackgroundWorker::sighup_received() {
+            unsafe { pg_sys::ProcessConfigFile(pg_sys::GucContext::PGC_SIGHUP) };
+            // Reconnect with the reloaded TLS settings.
+            worker.clients.clear();
+        }
+
+        unsafe {
+            worker.accept_requests();
+            worker.serve();
+            if reclaimed.elapsed() >= Duration::from_secs(1) {
+                worker.reclaim_slots();
+                reclaimed = Instant::now();
+            }
+        }
+    }
+
+    let mut slots = REMOTE_SLOTS.exclusive();
+    slots.worker_pid = 0;
+    slots.worker = std::ptr::null_mut();
+}
+
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_remote_shmem_request() {
+    if let Some(prev) = PREV_SHMEM_REQUEST_HOOK {
+        prev();
+    }
+
+    pg_sys::RequestAddinShmemSpace(REMOTE_QUEUES_SIZE);
+}
+
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_remote_shmem_startup() {
+    if let Some(prev) = PREV_SHMEM_STARTUP_HOOK {
+        prev();
+    }
+
+    let mut found = false;
+    REMOTE_QUEUES = pg_sys::ShmemInitStruct(
+        c"pgl remote queues".as_ptr(),
+        REMOTE_QUEUES_SIZE,
+        &mut found,
+    )
+    .cast();
+}
+
+/// Registers the worker and its queues; only possible while the library is
+/// being preloaded.
+pub unsafe fn register() {
+    if !pg_sys::process_shared_preload_libraries_in_progress || !super::PGL_REMOTE_WORKER.get() {
+        return;
+    }
+
+    pg_shmem_init!(REMOTE_SLOTS);
+
+    PREV_SHMEM_REQUEST_HOOK = pg_sys::shmem_request_hook;
+    pg_sys::shmem_request_hook = Some(pgl_remote_shmem_request);
+
+    PREV_SHMEM_STARTUP_HOOK = pg_sys::shmem_startup_hook;
+    pg_sys::shmem_startup_hook = Some(pgl_remote_shmem_startup);
+
+    BackgroundWorkerBuilder::new("pgl remote worker")
+        .set_function("pgl_remote_worker_main")
+        .set_library("pgl")
+        .enable_shmem_access(None)
+        .set_restart_time(Some(Duration::from_secs(5)))
+        .load();
+
+    REMOTE_WORKER_REGISTERED = true;
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use pgrx::prelude::*;
+
+    use super::{call, RemoteMethod, REMOTE_SLOTS};
+    use crate::rpc::client::pgl_rpc::{ChoosePlanRequest, ChoosePlanResponse};
+    use crate::rpc::metadata::RequestMetadata;
+
+    /// The worker's pid once it has attached; it may start after the test
+    /// backend.
+    fn worker_pid() -> i32 {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let pid = REMOTE_SLOTS.share().worker_pid;
+            if pid != 0 || Instant::now() >= deadline {
+                return pid;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    #[pg_test]
+    fn remote_worker_attaches() -> Result<(), pgrx::spi::SpiError> {
+        let pid = worker_pid();
+        assert_ne!(pid, 0, "the remote worker did not attach");
+
+        let backend_type = Spi::get_one_with_args::<String>(
+            "SELECT backend_type FROM pg_stat_activity WHERE pid = $1",
+            &[pgrx::datum::DatumWithOid::from(pid)],
+        )?;
+        assert_eq!(backend_type.as_deref(), Some("pgl remote worker"));
+        Ok(())
+    }
+
+    #[pg_test]
+    fn remote_worker_serves_requests() {
+        assert_ne!(worker_pid(), 0, "the remote worker did not attach");
+
+        // The worker takes the request; only its connection to the server fails.
+        let response: anyhow::Result<ChoosePlanResponse> = call(
+            "unix:///nonexistent/pgl.sock",
+            &RequestMetadata::default(),
+            RemoteMethod::ChoosePlan,
+            &ChoosePlanRequest::default(),
+        );
+        let err = response
+            .expect_err("no server listens on the socket")
+            .to_string();
+        assert!(
+            !err.contains("shared_preload_libraries") && !err.contains("not running"),
+            "the request did not reach the worker: {err}"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::cell::RefCell;
+
+use pgrx::lwlock::PgLwLock;
+use pgrx::prelude::*;
+use pgrx::shmem::*;
+use pgrx::{pg_shmem_init, pg_sys, GucSetting};
+
+/// Shadow decisions kept for `pgl.shadow_decisions`; older ones are dropped.
+const MAX_SHADOW_DECISIONS: usize = 1000;
+/// Longest state key kept; longer ones are truncated.
+const STATE_KEY_LEN: usize = 256;
+
+pub static PGL_SHADOW: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShadowComponent {
+    Planner,
+    Cardinality,
+}
+
+impl ShadowComponent {
+    fn as_str(self) -> &'static str {
+        match self {
+            ShadowComponent::Planner => "planner",
+            ShadowComponent::Cardinality => "cardinality",
+        }
+    }
+}
+
+/// What a learned component would have done, next to what PostgreSQL did.
+/// Fixed-size, so it can be kept in shared memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ShadowDecision {
+    component: ShadowComponent,
+    query_id: Option<i64>,
+    state_key_len: Option<usize>,
+    state_key: [u8; STATE_KEY_LEN],
+    native_rows: Option<f64>,
+    shadow_rows: Option<f64>,
+    native_arm: Option<i32>,
+    shadow_arm: Option<i32>,
+}
+
+impl ShadowDecision {
+    fn new(component: ShadowComponent, query_id: Option<i64>, state_key: Option<&str>) -> Self {
+        let mut decision = Self {
+            component,
+            query_id,
+            state_key_len: None,
+            state_key: [0; STATE_KEY_LEN],
+            native_rows: None,
+            shadow_rows: None,
+            native_arm: None,
+            shadow_arm: None,
+        };
+        if let Some(state_key) = state_key {
+            let mut end = state_key.len().min(STATE_KEY_LEN);
+            while !state_key.is_char_boundary(end) {
+                end -= 1;
+            }
+            decision.state_key[..end].copy_from_slice(&state_key.as_bytes()[..end]);
+            decision.state_key_len = Some(end);
+        }
+        decision
+    }
+
+    fn state_key(&self) -> Option<String> {
+        self.state_key_len
+            .map(|len| String::from_utf8_lossy(&self.state_key[..len]).into_owned())
+    }
+}
+
+pub struct ShadowDecisions {
+    decisions: heapless::Deque<ShadowDecision, MAX_SHADOW_DECISIONS>,
+}
+
+impl Default for ShadowDecisions {
+    fn default() -> Self {
+        Self {
+            decisions: heapless::Deque::new(),
+        }
+    }
+}
+
+unsafe impl PGRXSharedMemory for ShadowDecisions {}
+
+static SHADOW_DECISIONS: PgLwLock<ShadowDecisions> =
+    unsafe { PgLwLock::new(c"pgl_shadow_decisions") };
+
+/// Whether the decisions are shared, i.e. the library was preloaded.
+static mut SHADOW_SHARED: bool = false;
+
+thread_local! {
+    /// This backend's decisions when they cannot be shared.
+    static LOCAL_DECISIONS: RefCell<Box<ShadowDecisions>> = RefCell::new(Box::default());
+}
+
+fn with_decisions<T>(f: impl FnOnce(&mut ShadowDecisions) -> T) -> T {
+    if unsafe { SHADOW_SHARED } {
+        f(&mut SHADOW_DECISIONS.exclusive())
+    } else {
+        LOCAL_DECISIONS.with(|decisions| f(&mut decisions.borrow_mut()))
+    }
+}
+
+pub fn enabled() -> bool {
+    PGL_SHADOW.get()
+}
+
+fn record(decision: ShadowDecision) {
+    with_decisions(|shadow| {
+        if shadow.decisions.is_full() {
+            shadow.decisions.pop_front();
+        }
+        let _ = shadow.decisions.push_back(decision);
+    });
+}
+
+pub fn record_estimate(
+    query_id: Option<i64>,
+    state_key: Option<String>,
+    native_rows: f64,
+    shadow_rows: f64,
+) {
+    record(ShadowDecision {
+        native_rows: Some(native_rows),
+        shadow_rows: Some(shadow_rows),
+        ..ShadowDecision::new(ShadowComponent::Cardinality, query_id, state_key.as_deref())
+    });
+}
+
+pub fn record_plan_choice(query_id: Option<i64>, native_arm: i32, shadow_arm: i32) {
+    record(ShadowDecision {
+        native_arm: Some(native_arm),
+        shadow_arm: Some(shadow_arm),
+        ..ShadowDecision::new(ShadowComponent::Planner, query_id, None)
+    });
+}
+
+/// The latest shadow decisions of all backends when pgl is preloaded, and of
+/// this backend otherwise.
+#[pg_extern]
+fn pgl_shadow_decisions() -> TableIterator<
+    'static,
+    (
+        name!(component, String),
+        name!(query_id, Option<i64>),
+        name!(state_key, Option<String>),
+        name!(native_rows, Option<f64>),
+        name!(shadow_rows, Option<f64>),
+        name!(native_arm, Option<i32>),
+        name!(shadow_arm, Option<i32>),
+    ),
+> {
+    // Copied out under the lock; the strings are built after releasing it.
+    let decisions = with_decisions(|shadow| shadow.decisions.iter().copied().collect::<Vec<_>>());
+
+    TableIterator::new(decisions.into_iter().map(|decision| {
+        (
+            decision.component.as_str().to_string(),
+            decision.query_id,
+            decision.state_key(),
+            decision.native_rows,
+            decision.shadow_rows,
+            decision.native_arm,
+            decision.shadow_arm,
+        )
+    }))
+}
+
+#[pg_extern]
+fn pgl_shadow_decisions_reset() {
+    with_decisions(|shadow| shadow.decisions.clear());
+}
+
+extension_sql!(
+    "CREATE VIEW pgl.shadow_decisions AS SELECT * FROM pgl_shadow_decisions();",
+    name = "create_shadow_decisions_view",
+    requires = ["create_schema_pglearned", pgl_shadow_decisions]
+);
+
+// Like pgl_stat_reset(), since the decisions may be shared by all backends.
+extension_sql!(
+    "REVOKE EXECUTE ON FUNCTION pgl_shadow_decisions_reset() FROM PUBLIC;",
+    name = "revoke_shadow_decisions_reset",
+    requires = [pgl_shadow_decisions_reset]
+);
+
+/// Sets up the shared decisions; only possible while the library is being
+/// preloaded.
+pub unsafe fn register() {
+    if !pg_sys::process_shared_preload_libraries_in_progress {
+        return;
+    }
+
+    pg_shmem_init!(SHADOW_DECISIONS);
+    SHADOW_SHARED = true;
+}
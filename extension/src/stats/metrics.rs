@@ -0,0 +1,272 @@
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use pgrx::bgworkers::*;
+use pgrx::prelude::*;
+use pgrx::{pg_sys, GucSetting};
+
+use super::{
+    bucket_bound_us, read_stats, ComponentStats, StatsEntry, BUCKETS_PER_DOUBLING, Q_ERROR_BOUNDS,
+};
+
+pub static PGL_METRICS_PORT: GucSetting<i32> = GucSetting::<i32>::new(0);
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(entry: &StatsEntry) -> String {
+    format!(
+        "component=\"{}\",endpoint=\"{}\"",
+        entry.component.as_str(),
+        escape_label(&entry.endpoint().unwrap_or_default())
+    )
+}
+
+fn family(out: &mut String, name: &str, kind: &str, unit: Option<&str>, help: &str) {
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    if let Some(unit) = unit {
+        let _ = writeln!(out, "# UNIT {name} {unit}");
+    }
+    let _ = writeln!(out, "# HELP {name} {help}");
+}
+
+fn counter(
+    out: &mut String,
+    entries: &[StatsEntry],
+    name: &str,
+    unit: Option<&str>,
+    help: &str,
+    value: impl Fn(&StatsEntry) -> f64,
+) {
+    family(out, name, "counter", unit, help);
+    for entry in entries {
+        let _ = writeln!(out, "{name}_total{{{}}} {}", labels(entry), value(entry));
+    }
+}
+
+/// The statistics in the OpenMetrics text format.
+pub fn exposition() -> String {
+    let (entries, q_errors) = read_stats(ComponentStats::snapshot);
+
+    let mut out = String::new();
+    counter(
+        &mut out,
+        &entries,
+        "pgl_requests",
+        None,
+        "Plan choices and estimates requested.",
+        |entry| entry.calls as f64,
+    );
+    counter(
+        &mut out,
+        &entries,
+        "pgl_request_failures",
+        None,
+        "Requests that failed.",
+        |entry| entry.failures as f64,
+    );
+    counter(
+        &mut out,
+        &entries,
+        "pgl_fallbacks",
+        None,
+        "Requests whose answer was not used, so PostgreSQL's was kept.",
+        |entry| entry.fallbacks as f64,
+    );
+    counter(
+        &mut out,
+        &entries,
+        "pgl_cache_hits",
+        None,
+        "Estimates served from the backend's estimate cache.",
+        |entry| entry.cache_hits as f64,
+    );
+    counter(
+        &mut out,
+        &entries,
+        "pgl_sent_bytes",
+        Some("bytes"),
+        "Size of the encoded gRPC requests.",
+        |entry| entry.bytes_sent as f64,
+    );
+    counter(
+        &mut out,
+        &entries,
+        "pgl_planning_overhead_seconds",
+        Some("seconds"),
+        "Planning time added by the learned components.",
+        |entry| entry.overhead_us as f64 / 1e6,
+    );
+
+    family(
+        &mut out,
+        "pgl_request_duration_seconds",
+        "histogram",
+        Some("seconds"),
+        "Latency of the requests.",
+    );
+    let per_doubling = BUCKETS_PER_DOUBLING as usize;
+    for entry in &entries {
+        let labels = labels(entry);
+        let mut cumulative = 0;
+        for (bucket, count) in entry.latency.iter().enumerate() {
+            cumulative += count;
+            if (bucket + 1) % per_doubling == 0 {
+                let bound = bucket_bound_us(bucket) / 1e6;
+                let _ = writeln!(
+                    out,
+                    "pgl_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {cumulative}"
+                );
+            }
+        }
+        let samples = entry.latency_samples();
+        let _ = writeln!(
+            out,
+            "pgl_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {samples}"
+        );
+        let _ = writeln!(
+            out,
+            "pgl_request_duration_seconds_count{{{labels}}} {samples}"
+        );
+        let _ = writeln!(
+            out,
+            "pgl_request_duration_seconds_sum{{{labels}}} {}",
+            entry.latency_sum_us as f64 / 1e6
+        );
+    }
+
+    family(
+        &mut out,
+        "pgl_arm_choices",
+        "counter",
+        None,
+        "Arms chosen by the planner.",
+    );
+    for entry in &entries {
+        let labels = labels(entry);
+        for (arm, count) in entry.arms.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            // The arm with every plan type enabled is PostgreSQL's own plan.
+            let arm = if arm as i32 == crate::planner::NATIVE_ARM {
+                "native".to_string()
+            } else {
+                arm.to_string()
+            };
+            let _ = writeln!(
+                out,
+                "pgl_arm_choices_total{{{labels},arm=\"{arm}\"}} {count}"
+            );
+        }
+    }
+
+    family(
+        &mut out,
+        "pgl_cardinality_q_error",
+        "histogram",
+        None,
+        "Q-error of learned estimates against the rows executed.",
+    );
+    let mut cumulative = 0;
+    for (bound, count) in Q_ERROR_BOUNDS.iter().zip(q_errors.buckets) {
+        cumulative += count;
+        let _ = writeln!(
+            out,
+            "pgl_cardinality_q_error_bucket{{le=\"{bound:?}\"}} {cumulative}"
+        );
+    }
+    let samples: u64 = q_errors.buckets.iter().sum();
+    let _ = writeln!(
+        out,
+        "pgl_cardinality_q_error_bucket{{le=\"+Inf\"}} {samples}"
+    );
+    let _ = writeln!(out, "pgl_cardinality_q_error_count {samples}");
+    let _ = writeln!(out, "pgl_cardinality_q_error_sum {}", q_errors.sum);
+
+    out.push_str("# EOF\n");
+    out
+}
+
+/// The statistics in the OpenMetrics text format, as served by the metrics
+/// endpoint.
+#[pg_extern]
+fn pgl_metrics() -> String {
+    exposition()
+}
+
+/// Answers one scrape; anything but `GET /metrics` (or `/`) is not found.
+fn serve(mut stream: TcpStream) -> std::io::Result<()> {
+    // A slow client must not stall the exporter for long.
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+
+    let mut request = [0u8; 1024];
+    let len = stream.read(&mut request)?;
+    let request = String::from_utf8_lossy(&request[..len]);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+
+    let (status, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics" | "/")) => ("200 OK", CONTENT_TYPE, exposition()),
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Serves the statistics on `pgl.metrics_port` of localhost.
+#[pg_guard]
+#[no_mangle]
+pub extern "C-unwind" fn pgl_metrics_worker_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+
+    let port = PGL_METRICS_PORT.get();
+    let listener = TcpListener::bind(("127.0.0.1", port as u16)).and_then(|listener| {
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    });
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(err) => {
+            pgrx::log!("pgl: failed to serve metrics on port {port}: {err}");
+            return;
+        }
+    };
+
+    while BackgroundWorker::wait_latch(Some(Duration::from_millis(100))) {
+        if BackgroundWorker::sighup_received() {
+            unsafe { pg_sys::ProcessConfigFile(pg_sys::GucContext::PGC_SIGHUP) };
+        }
+
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(err) = serve(stream) {
+                        pgrx::log!("pgl: failed to answer a metrics scrape: {err}");
+                    }
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    pgrx::log!("pgl: failed to accept a metrics scrape: {err}");
+                    break;
+                }
+            }
+        }
+    }
+}
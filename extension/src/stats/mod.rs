@@ -0,0 +1,449 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use pgrx::bgworkers::*;
+use pgrx::lwlock::PgLwLock;
+use pgrx::prelude::*;
+use pgrx::shmem::*;
+use pgrx::{pg_shmem_init, pg_sys};
+
+pub mod metrics;
+
+/// Component and endpoint pairs tracked; calls to further endpoints are not
+/// counted.
+const STATS_ENTRIES: usize = 64;
+/// Longest endpoint kept; longer ones are truncated.
+const ENDPOINT_LEN: usize = 128;
+/// Latency buckets per doubling, which bounds percentiles within 19%.
+const BUCKETS_PER_DOUBLING: f64 = 4.0;
+/// Buckets from 1 µs; the last also takes everything slower than ~17 s.
+const LATENCY_BUCKETS: usize = 96;
+
+/// Arms a planner choice can name.
+const ARMS: usize = crate::planner::NATIVE_ARM as usize + 1;
+/// Upper bounds of the q-error buckets; the last bucket takes the rest.
+const Q_ERROR_BOUNDS: [f64; 9] = [1.5, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 1024.0];
+
+/// The endpoint of estimates read from `pgl.cardinality_overrides`.
+pub const OVERRIDES_ENDPOINT: &str = "pgl.cardinality_overrides";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Planner,
+    Cardinality,
+}
+
+impl Component {
+    fn as_str(self) -> &'static str {
+        match self {
+            Component::Planner => "planner",
+            Component::Cardinality => "cardinality",
+        }
+    }
+}
+
+/// How a request to a learned component ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The component's answer was used.
+    Applied,
+    /// An earlier answer was reused without asking the endpoint.
+    Cached,
+    /// The answer was discarded, e.g. by the estimate guards, and
+    /// PostgreSQL's own was kept.
+    Fallback,
+    /// The request failed and PostgreSQL's own answer was kept.
+    Failed,
+}
+
+/// One request to a learned component, filled in while it is made.
+pub struct Call {
+    component: Component,
+    /// The server URL, or the model or table answering in the backend.
+    pub endpoint: Option<String>,
+    /// Time spent waiting on the endpoint, if it was asked.
+    pub latency: Option<Duration>,
+    pub bytes_sent: usize,
+    /// The arm a planner chose.
+    pub arm: Option<i32>,
+}
+
+impl Call {
+    pub fn new(component: Component) -> Self {
+        Self {
+            component,
+            endpoint: None,
+            latency: None,
+            bytes_sent: 0,
+            arm: None,
+        }
+    }
+
+    /// Counts the call, and `overhead`, the planning time it added in all.
+    pub fn record(self, outcome: Outcome, overhead: Duration) {
+        with_entry(self.component, self.endpoint.as_deref(), |entry| {
+            match outcome {
+                Outcome::Cached => add(&entry.cache_hits, 1),
+                _ => add(&entry.calls, 1),
+            }
+            if matches!(outcome, Outcome::Fallback | Outcome::Failed) {
+                add(&entry.fallbacks, 1);
+            }
+            if outcome == Outcome::Failed {
+                add(&entry.failures, 1);
+            }
+            if let Some(latency) = self.latency {
+                add(&entry.latency[latency_bucket(latency)], 1);
+                add(&entry.latency_sum_us, latency.as_micros() as u64);
+            }
+            if let Some(arm) = self.arm.and_then(|arm| entry.arms.get(arm as usize)) {
+                add(arm, 1);
+            }
+            add(&entry.bytes_sent, self.bytes_sent as u64);
+            add(&entry.overhead_us, overhead.as_micros() as u64);
+        });
+    }
+}
+
+fn add(counter: &AtomicU64, value: u64) {
+    counter.fetch_add(value, Ordering::Relaxed);
+}
+
+fn latency_bucket(latency: Duration) -> usize {
+    let micros = latency.as_secs_f64() * 1e6;
+    if micros <= 1.0 {
+        return 0;
+    }
+    ((micros.log2() * BUCKETS_PER_DOUBLING) as usize).min(LATENCY_BUCKETS - 1)
+}
+
+/// A copy of one entry's counters, as readers see them.
+#[derive(Clone, Copy)]
+struct StatsEntry {
+    component: Component,
+    endpoint_len: usize,
+    endpoint: [u8; ENDPOINT_LEN],
+    calls: u64,
+    failures: u64,
+    fallbacks: u64,
+    cache_hits: u64,
+    bytes_sent: u64,
+    overhead_us: u64,
+    latency: [u64; LATENCY_BUCKETS],
+    latency_sum_us: u64,
+    arms: [u64; ARMS],
+}
+
+impl StatsEntry {
+    fn endpoint(&self) -> Option<String> {
+        (self.endpoint_len > 0)
+            .then(|| String::from_utf8_lossy(&self.endpoint[..self.endpoint_len]).into_owned())
+    }
+
+    fn latency_samples(&self) -> u64 {
+        self.latency.iter().sum()
+    }
+
+    /// The upper bound of the bucket holding the `quantile` latency, in ms.
+    fn latency_ms(&self, quantile: f64) -> Option<f64> {
+        let samples = self.latency_samples();
+        if samples == 0 {
+            return None;
+        }
+
+        let rank = ((samples as f64 * quantile).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bucket = self.latency.iter().position(|count| {
+            seen += count;
+            seen >= rank
+        })?;
+        Some(bucket_bound_us(bucket) / 1000.0)
+    }
+}
+
+/// The upper bound of latency bucket `bucket`, in µs.
+fn bucket_bound_us(bucket: usize) -> f64 {
+    ((bucket + 1) as f64 / BUCKETS_PER_DOUBLING).exp2()
+}
+
+/// Q-errors of executed learned estimates.
+#[derive(Clone, Copy, Default)]
+struct QErrors {
+    buckets: [u64; Q_ERROR_BOUNDS.len() + 1],
+    sum: f64,
+}
+
+/// The counters of one component and endpoint. The counters are atomics,
+/// so calls are recorded under the shared lock; only adding an entry takes
+/// the exclusive one.
+struct SharedEntry {
+    component: Component,
+    endpoint_len: usize,
+    endpoint: [u8; ENDPOINT_LEN],
+    calls: AtomicU64,
+    failures: AtomicU64,
+    fallbacks: AtomicU64,
+    cache_hits: AtomicU64,
+    bytes_sent: AtomicU64,
+    overhead_us: AtomicU64,
+    latency: [AtomicU64; LATENCY_BUCKETS],
+    latency_sum_us: AtomicU64,
+    arms: [AtomicU64; ARMS],
+}
+
+impl SharedEntry {
+    fn new(component: Component, endpoint: &[u8]) -> Self {
+        let mut entry = Self {
+            component,
+            endpoint_len: endpoint.len(),
+            endpoint: [0; ENDPOINT_LEN],
+            calls: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            fallbacks: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            overhead_us: AtomicU64::new(0),
+            latency: std::array::from_fn(|_| AtomicU64::new(0)),
+            latency_sum_us: AtomicU64::new(0),
+            arms: std::array::from_fn(|_| AtomicU64::new(0)),
+        };
+        entry.endpoint[..endpoint.len()].copy_from_slice(endpoint);
+        entry
+    }
+
+    fn is(&self, component: Component, endpoint: &str) -> bool {
+        self.component == component && self.endpoint[..self.endpoint_len] == *endpoint.as_bytes()
+    }
+
+    fn load(&self) -> StatsEntry {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        StatsEntry {
+            component: self.component,
+            endpoint_len: self.endpoint_len,
+            endpoint: self.endpoint,
+            calls: load(&self.calls),
+            failures: load(&self.failures),
+            fallbacks: load(&self.fallbacks),
+            cache_hits: load(&self.cache_hits),
+            bytes_sent: load(&self.bytes_sent),
+            overhead_us: load(&self.overhead_us),
+            latency: self.latency.each_ref().map(load),
+            latency_sum_us: load(&self.latency_sum_us),
+            arms: self.arms.each_ref().map(load),
+        }
+    }
+}
+
+struct SharedQErrors {
+    buckets: [AtomicU64; Q_ERROR_BOUNDS.len() + 1],
+    /// The bits of the `f64` sum.
+    sum: AtomicU64,
+}
+
+impl SharedQErrors {
+    fn load(&self) -> QErrors {
+        QErrors {
+            buckets: self
+                .buckets
+                .each_ref()
+                .map(|bucket| bucket.load(Ordering::Relaxed)),
+            sum: f64::from_bits(self.sum.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+pub struct ComponentStats {
+    entries: [Option<SharedEntry>; STATS_ENTRIES],
+    q_errors: SharedQErrors,
+}
+
+impl Default for ComponentStats {
+    fn default() -> Self {
+        Self {
+            entries: std::array::from_fn(|_| None),
+            q_errors: SharedQErrors {
+                buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+                sum: AtomicU64::new(0f64.to_bits()),
+            },
+        }
+    }
+}
+
+unsafe impl PGRXSharedMemory for ComponentStats {}
+
+impl ComponentStats {
+    fn find(&self, component: Component, endpoint: &str) -> Option<&SharedEntry> {
+        self.entries
+            .iter()
+            .flatten()
+            .find(|entry| entry.is(component, endpoint))
+    }
+
+    fn insert(&mut self, component: Component, endpoint: &str) -> Option<&SharedEntry> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| {
+                entry
+                    .as_ref()
+                    .is_some_and(|entry| entry.is(component, endpoint))
+            })
+            .or_else(|| self.entries.iter().position(Option::is_none))?;
+        Some(
+            self.entries[index]
+                .get_or_insert_with(|| SharedEntry::new(component, endpoint.as_bytes())),
+        )
+    }
+
+    fn snapshot(&self) -> (Vec<StatsEntry>, QErrors) {
+        (
+            self.entries
+                .iter()
+                .flatten()
+                .map(SharedEntry::load)
+                .collect(),
+            self.q_errors.load(),
+        )
+    }
+}
+
+static COMPONENT_STATS: PgLwLock<ComponentStats> = unsafe { PgLwLock::new(c"pgl_component_stats") };
+
+/// Whether the statistics are shared, i.e. the library was preloaded.
+static mut STATS_SHARED: bool = false;
+
+thread_local! {
+    /// This backend's statistics when they cannot be shared.
+    static LOCAL_STATS: RefCell<Box<ComponentStats>> = RefCell::new(Box::default());
+}
+
+/// Runs `f` on the entry of `component` and `endpoint`, adding it if there is
+/// room; calls to further endpoints are not counted.
+fn with_entry(component: Component, endpoint: Option<&str>, f: impl Fn(&SharedEntry)) {
+    let mut endpoint = endpoint.unwrap_or_default();
+    if endpoint.len() > ENDPOINT_LEN {
+        let mut end = ENDPOINT_LEN;
+        while !endpoint.is_char_boundary(end) {
+            end -= 1;
+        }
+        endpoint = &endpoint[..end];
+    }
+
+    let found = read_stats(|stats| stats.find(component, endpoint).map(&f).is_some());
+    if !found {
+        with_stats(|stats| stats.insert(component, endpoint).map(&f));
+    }
+}
+
+fn with_stats<T>(f: impl FnOnce(&mut ComponentStats) -> T) -> T {
+    if unsafe { STATS_SHARED } {
+        f(&mut COMPONENT_STATS.exclusive())
+    } else {
+        LOCAL_STATS.with(|stats| f(&mut stats.borrow_mut()))
+    }
+}
+
+fn read_stats<T>(f: impl FnOnce(&ComponentStats) -> T) -> T {
+    if unsafe { STATS_SHARED } {
+        f(&COMPONENT_STATS.share())
+    } else {
+        LOCAL_STATS.with(|stats| f(&stats.borrow()))
+    }
+}
+
+/// Counts the q-errors of learned estimates measured at execution.
+pub fn record_q_errors(q_errors: &[f64]) {
+    if q_errors.is_empty() {
+        return;
+    }
+
+    read_stats(|stats| {
+        for q_error in q_errors {
+            let bucket = Q_ERROR_BOUNDS
+                .iter()
+                .position(|bound| q_error <= bound)
+                .unwrap_or(Q_ERROR_BOUNDS.len());
+            add(&stats.q_errors.buckets[bucket], 1);
+            let _ = stats
+                .q_errors
+                .sum
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+                    Some((f64::from_bits(sum) + q_error).to_bits())
+                });
+        }
+    });
+}
+
+/// Statistics of the learned components per endpoint, for all backends when
+/// pgl is preloaded and for this backend otherwise.
+#[pg_extern]
+fn pgl_stat_components() -> TableIterator<
+    'static,
+    (
+        name!(component, String),
+        name!(endpoint, Option<String>),
+        name!(calls, i64),
+        name!(failures, i64),
+        name!(fallbacks, i64),
+        name!(cache_hits, i64),
+        name!(p50_ms, Option<f64>),
+        name!(p99_ms, Option<f64>),
+        name!(overhead_ms, f64),
+        name!(bytes_sent, i64),
+    ),
+> {
+    let (entries, _) = read_stats(ComponentStats::snapshot);
+    TableIterator::new(entries.into_iter().map(|entry| {
+        (
+            entry.component.as_str().to_string(),
+            entry.endpoint(),
+            entry.calls as i64,
+            entry.failures as i64,
+            entry.fallbacks as i64,
+            entry.cache_hits as i64,
+            entry.latency_ms(0.5),
+            entry.latency_ms(0.99),
+            entry.overhead_us as f64 / 1000.0,
+            entry.bytes_sent as i64,
+        )
+    }))
+}
+
+#[pg_extern]
+fn pgl_stat_reset() {
+    with_stats(|stats| *stats = ComponentStats::default());
+}
+
+extension_sql!(
+    "CREATE VIEW pgl.stat_components AS SELECT * FROM pgl_stat_components();",
+    name = "create_stat_components_view",
+    requires = ["create_schema_pglearned", pgl_stat_components]
+);
+
+// Like pg_stat_reset(), since the statistics may be shared by all backends.
+extension_sql!(
+    "REVOKE EXECUTE ON FUNCTION pgl_stat_reset() FROM PUBLIC;",
+    name = "revoke_stat_reset",
+    requires = [pgl_stat_reset]
+);
+
+/// Sets up the shared statistics and the metrics endpoint; only possible
+/// while the library is being preloaded.
+pub unsafe fn register() {
+    if !pg_sys::process_shared_preload_libraries_in_progress {
+        return;
+    }
+
+    pg_shmem_init!(COMPONENT_STATS);
+    STATS_SHARED = true;
+
+    if metrics::PGL_METRICS_PORT.get() > 0 {
+        BackgroundWorkerBuilder::new("pgl metrics exporter")
+            .set_function("pgl_metrics_worker_main")
+            .set_library("pgl")
+            .enable_shmem_access(None)
+            .set_restart_time(Some(Duration::from_secs(5)))
+            .load();
+    }
+}
@@ -0,0 +1,198 @@
+use std::cell::Cell;
+
+use pgrx::prelude::*;
+use pgrx::{pg_sys, GucSetting};
+
+use crate::utils::{cstr_to_string, query_relations};
+
+pub static PGL_MIN_RELATIONS: GucSetting<i32> = GucSetting::<i32>::new(0);
+pub static PGL_MIN_NATIVE_COST: GucSetting<f64> = GucSetting::<f64>::new(0.0);
+pub static PGL_SKIP_CATALOG_QUERIES: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static PGL_SKIP_UTILITY_STATEMENTS: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+static mut PREV_PROCESS_UTILITY_HOOK: pg_sys::ProcessUtility_hook_type = None;
+static mut PREV_EXECUTOR_RUN_HOOK: pg_sys::ExecutorRun_hook_type = None;
+static mut PREV_EXECUTOR_FINISH_HOOK: pg_sys::ExecutorFinish_hook_type = None;
+
+thread_local! {
+    /// How deeply planning and execution are nested in this backend.
+    static LEVEL: Cell<u32> = const { Cell::new(0) };
+    /// The level at which a utility statement plans queries on its own
+    /// behalf. Queries nested deeper, e.g. run by functions it calls, are
+    /// not its own.
+    static UTILITY_LEVEL: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Runs `f` one level deeper.
+pub fn nested<T>(f: impl FnOnce() -> T) -> T {
+    struct Reset;
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            LEVEL.with(|level| level.set(level.get() - 1));
+        }
+    }
+
+    LEVEL.with(|level| level.set(level.get() + 1));
+    let _reset = Reset;
+    f()
+}
+
+fn in_utility() -> bool {
+    UTILITY_LEVEL.with(Cell::get) == Some(LEVEL.with(Cell::get))
+}
+
+/// Why a statement was left to PostgreSQL alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrivialReason {
+    Utility,
+    Catalog,
+    Relations,
+    Cost,
+}
+
+impl TrivialReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TrivialReason::Utility => "utility",
+            TrivialReason::Catalog => "catalog",
+            TrivialReason::Relations => "relations",
+            TrivialReason::Cost => "cost",
+        }
+    }
+}
+
+unsafe fn is_catalog_relation(relid: pg_sys::Oid) -> bool {
+    let namespace = cstr_to_string(pg_sys::get_namespace_name(pg_sys::get_rel_namespace(relid)));
+    matches!(
+        namespace.as_deref(),
+        Some("pg_catalog" | "information_schema")
+    )
+}
+
+/// Why `parse` is too trivial for the learned components, judged from the
+/// query tree alone.
+pub unsafe fn reason(parse: *mut pg_sys::Query) -> Option<TrivialReason> {
+    if PGL_SKIP_UTILITY_STATEMENTS.get() && in_utility() {
+        return Some(TrivialReason::Utility);
+    }
+
+    let skip_catalog = PGL_SKIP_CATALOG_QUERIES.get();
+    let min_relations = PGL_MIN_RELATIONS.get();
+    if !skip_catalog && min_relations <= 0 {
+        return None;
+    }
+
+    let relations = query_relations(parse);
+    if skip_catalog
+        && !relations.is_empty()
+        && relations.iter().all(|relid| is_catalog_relation(*relid))
+    {
+        Some(TrivialReason::Catalog)
+    } else if relations.len() < min_relations.max(0) as usize {
+        Some(TrivialReason::Relations)
+    } else {
+        None
+    }
+}
+
+/// Whether statements need a native plan to be judged by its cost.
+pub fn checks_cost() -> bool {
+    PGL_MIN_NATIVE_COST.get() > 0.0
+}
+
+/// Whether the native plan `stmt` is too cheap for the learned components.
+pub unsafe fn too_cheap(stmt: *mut pg_sys::PlannedStmt) -> bool {
+    let plan = (*stmt).planTree;
+    !plan.is_null() && (*plan).total_cost < PGL_MIN_NATIVE_COST.get()
+}
+
+#[pg_guard]
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C-unwind" fn pgl_process_utility(
+    pstmt: *mut pg_sys::PlannedStmt,
+    query_string: *const std::os::raw::c_char,
+    read_only_tree: bool,
+    context: pg_sys::ProcessUtilityContext::Type,
+    params: pg_sys::ParamListInfo,
+    query_env: *mut pg_sys::QueryEnvironment,
+    dest: *mut pg_sys::DestReceiver,
+    qc: *mut pg_sys::QueryCompletion,
+) {
+    struct Reset(Option<u32>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            UTILITY_LEVEL.with(|utility| utility.set(self.0));
+        }
+    }
+
+    // These run the user's own statements rather than planning on their own.
+    let runs_statements = matches!(
+        (*(*pstmt).utilityStmt).type_,
+        pg_sys::NodeTag::T_ExplainStmt
+            | pg_sys::NodeTag::T_PrepareStmt
+            | pg_sys::NodeTag::T_ExecuteStmt
+            | pg_sys::NodeTag::T_DeclareCursorStmt
+            | pg_sys::NodeTag::T_DoStmt
+            | pg_sys::NodeTag::T_CallStmt
+    );
+    // The planner hook plans its queries one level deeper.
+    let level = (!runs_statements).then(|| LEVEL.with(Cell::get) + 1);
+    let _reset = Reset(UTILITY_LEVEL.with(|utility| utility.replace(level)));
+
+    match PREV_PROCESS_UTILITY_HOOK {
+        Some(prev) => prev(
+            pstmt,
+            query_string,
+            read_only_tree,
+            context,
+            params,
+            query_env,
+            dest,
+            qc,
+        ),
+        None => pg_sys::standard_ProcessUtility(
+            pstmt,
+            query_string,
+            read_only_tree,
+            context,
+            params,
+            query_env,
+            dest,
+            qc,
+        ),
+    }
+}
+
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_executor_run(
+    query_desc: *mut pg_sys::QueryDesc,
+    direction: pg_sys::ScanDirection::Type,
+    count: u64,
+) {
+    nested(|| match PREV_EXECUTOR_RUN_HOOK {
+        Some(prev) => prev(query_desc, direction, count),
+        None => pg_sys::standard_ExecutorRun(query_desc, direction, count),
+    })
+}
+
+#[pg_guard]
+unsafe extern "C-unwind" fn pgl_executor_finish(query_desc: *mut pg_sys::QueryDesc) {
+    nested(|| match PREV_EXECUTOR_FINISH_HOOK {
+        Some(prev) => prev(query_desc),
+        None => pg_sys::standard_ExecutorFinish(query_desc),
+    })
+}
+
+pub unsafe fn register() {
+    PREV_PROCESS_UTILITY_HOOK = pg_sys::ProcessUtility_hook;
+    pg_sys::ProcessUtility_hook = Some(pgl_process_utility);
+
+    // Functions run by a statement's plan nest their queries below it.
+    PREV_EXECUTOR_RUN_HOOK = pg_sys::ExecutorRun_hook;
+    pg_sys::ExecutorRun_hook = Some(pgl_executor_run);
+
+    PREV_EXECUTOR_FINISH_HOOK = pg_sys::ExecutorFinish_hook;
+    pg_sys::ExecutorFinish_hook = Some(pgl_executor_finish);
+}
@@ -64,6 +64,41 @@ pub unsafe fn bitmapset_members(relids: pg_sys::Relids) -> Vec<u32> {
     members
 }
 
+/// The pointers in a PostgreSQL List of nodes.
+pub unsafe fn list_ptrs<T>(list: *mut pg_sys::List) -> Vec<*mut T> {
+    if list.is_null() {
+        return Vec::new();
+    }
+
+    let elements = (*list).elements;
+    (0..(*list).length.max(0) as usize)
+        .map(|idx| (*elements.add(idx)).ptr_value as *mut T)
+        .collect()
+}
+
+/// Every relation scanned by `query`, its subqueries and its CTEs, once per
+/// range table entry.
+pub unsafe fn query_relations(query: *mut pg_sys::Query) -> Vec<pg_sys::Oid> {
+    let mut relations = Vec::new();
+    for rte in list_ptrs::<pg_sys::RangeTblEntry>((*query).rtable) {
+        match (*rte).rtekind {
+            pg_sys::RTEKind::RTE_RELATION => relations.push((*rte).relid),
+            pg_sys::RTEKind::RTE_SUBQUERY if !(*rte).subquery.is_null() => {
+                relations.extend(query_relations((*rte).subquery))
+            }
+            _ => {}
+        }
+    }
+
+    for cte in list_ptrs::<pg_sys::CommonTableExpr>((*query).cteList) {
+        let cte_query = (*cte).ctequery;
+        if !cte_query.is_null() && (*cte_query).type_ == pg_sys::NodeTag::T_Query {
+            relations.extend(query_relations(cte_query as *mut pg_sys::Query));
+        }
+    }
+    relations
+}
+
 /// Safely converts a C string pointer to an owned Rust String.
 pub unsafe fn cstr_to_string(ptr: *mut std::os::raw::c_char) -> Option<String> {
     if ptr.is_null() {
@@ -72,3 +107,155 @@ pub unsafe fn cstr_to_string(ptr: *mut std::os::raw::c_char) -> Option<String> {
 
     Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
 }
+
+/// Where the last statement of `sql` starts: after its last semicolon
+/// outside literals, quoted identifiers and comments.
+fn statement_start(sql: &[u8]) -> usize {
+    let ident = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80;
+    let mut start = 0;
+    let mut i = 0;
+    while i < sql.len() {
+        match sql[i] {
+            b';' => {
+                i += 1;
+                start = i;
+            }
+            quote @ (b'\'' | b'"') => {
+                // E'...' strings escape with backslashes.
+                let escapes = quote == b'\''
+                    && i > 0
+                    && sql[i - 1].eq_ignore_ascii_case(&b'e')
+                    && (i < 2 || !ident(sql[i - 2]));
+                i += 1;
+                while let Some(&c) = sql.get(i) {
+                    i += 1;
+                    if escapes && c == b'\\' {
+                        i += 1;
+                    } else if c == quote {
+                        if sql.get(i) != Some(&quote) {
+                            break;
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            b'-' if sql.get(i + 1) == Some(&b'-') => {
+                i = sql[i..]
+                    .iter()
+                    .position(|c| *c == b'\n')
+                    .map_or(sql.len(), |end| i + end + 1);
+            }
+            b'/' if sql.get(i + 1) == Some(&b'*') => {
+                // Block comments nest.
+                let mut depth = 0;
+                while i < sql.len() {
+                    if sql[i..].starts_with(b"/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if sql[i..].starts_with(b"*/") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            b'$' if i == 0 || !ident(sql[i - 1]) => {
+                let tag_len = sql[i + 1..].iter().position(|c| !ident(*c));
+                match tag_len.filter(|len| {
+                    sql[i + 1 + len] == b'$' && !sql.get(i + 1).is_some_and(u8::is_ascii_digit)
+                }) {
+                    Some(len) => {
+                        let delimiter = &sql[i..i + len + 2];
+                        i += delimiter.len();
+                        i = sql[i..]
+                            .windows(delimiter.len())
+                            .position(|window| window == delimiter)
+                            .map_or(sql.len(), |end| i + end + delimiter.len());
+                    }
+                    None => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    start
+}
+
+/// The text of the statement `parse` came from, including the comments and
+/// EXPLAIN that lead it, out of a query string that may hold several.
+pub unsafe fn statement_text(
+    parse: *mut pg_sys::Query,
+    query_string: *const std::os::raw::c_char,
+) -> String {
+    match parse.as_ref() {
+        Some(parse) => statement_at(query_string, parse.stmt_location, parse.stmt_len),
+        None => statement_at(query_string, -1, 0),
+    }
+}
+
+/// Like `statement_text`, for a statement at `location` of `query_string`,
+/// `len` bytes long; e.g. that of a `PlannedStmt`.
+pub unsafe fn statement_at(
+    query_string: *const std::os::raw::c_char,
+    location: i32,
+    len: i32,
+) -> String {
+    if query_string.is_null() {
+        return String::new();
+    }
+    let sql = CStr::from_ptr(query_string).to_bytes();
+    if location < 0 || location as usize > sql.len() {
+        return String::from_utf8_lossy(sql).into_owned();
+    }
+    let location = location as usize;
+
+    // A length of 0 means the rest of the string.
+    let end = match len.max(0) as usize {
+        0 => sql.len(),
+        len => (location + len).min(sql.len()),
+    };
+    // Nested statements, like the query of an EXPLAIN, start at their own
+    // first token, after the comments and EXPLAIN leading the statement.
+    let start = statement_start(&sql[..location]);
+    String::from_utf8_lossy(&sql[start..end]).into_owned()
+}
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// FNV-1a is used instead of `DefaultHasher` because keys must not change
+/// between backends, builds or Rust versions.
+pub fn fnv1a_128(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u128::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+thread_local! {
+    static INTERNAL_LOOKUP: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Runs a query pgl itself needs while planning, e.g. to read a pin or a
+/// model. The hooks leave such queries to PostgreSQL, or every lookup would
+/// plan, estimate and record itself recursively.
+pub fn internal_lookup<T>(f: impl FnOnce() -> T) -> T {
+    struct Reset(bool);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            INTERNAL_LOOKUP.with(|active| active.set(self.0));
+        }
+    }
+
+    let _reset = Reset(INTERNAL_LOOKUP.with(|active| active.replace(true)));
+    f()
+}
+
+/// True while a query run by `internal_lookup` is planned or executed.
+pub fn in_internal_lookup() -> bool {
+    INTERNAL_LOOKUP.with(|active| active.get())
+}
@@ -0,0 +1,50 @@
+LOAD 'pgl';
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'local';
+SET pgl.planner_arm = 5;
+-- statements outside the learned fraction are planned by PostgreSQL alone
+SET pgl.experiment_fraction = 0;
+EXPLAIN (PGL, COSTS OFF) SELECT 1;
+SET pgl.experiment_assignment = 'random';
+EXPLAIN (PGL, COSTS OFF) SELECT 1;
+-- the whole fraction runs no experiment
+SET pgl.experiment_fraction = 1;
+EXPLAIN (PGL, COSTS OFF) SELECT 1;
+RESET pgl.experiment_assignment;
+RESET pgl.experiment_fraction;
+RESET pgl.planner_arm;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+SELECT count(*) FROM pgl.experiment_summary;
+-- with hash assignment a query stays in its group; the summary compares the groups
+SET compute_query_id = on;
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'local';
+SET pgl.planner_arm = 5;
+SET pgl.experiment_fraction = 0.5;
+SET pgl.feedback = on;
+SELECT 1 AS a;
+SELECT 1 AS a, 2 AS b;
+SELECT 1 AS a, 2 AS b, 3 AS c;
+SELECT relname FROM pg_class WHERE oid = 1259;
+SELECT 1 AS a;
+SELECT 1 AS a, 2 AS b;
+SELECT 1 AS a, 2 AS b, 3 AS c;
+SELECT relname FROM pg_class WHERE oid = 1259;
+SELECT 1 AS a;
+SELECT 1 AS a, 2 AS b;
+SELECT 1 AS a, 2 AS b, 3 AS c;
+SELECT relname FROM pg_class WHERE oid = 1259;
+SET pgl.feedback = off;
+SELECT pgl_feedback_flush();
+SELECT query, count(*) AS executions, count(DISTINCT experiment_group) AS groups
+FROM pgl.feedback_log GROUP BY query ORDER BY query;
+SELECT bool_and(executions >= 3 AND mean_ms_ci_low <= mean_ms AND mean_ms <= mean_ms_ci_high) AS ci
+FROM pgl.experiment_summary;
+RESET pgl.experiment_fraction;
+RESET pgl.planner_arm;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+RESET compute_query_id;
+DELETE FROM pgl.plan_latencies;
+DELETE FROM pgl.feedback_log;
@@ -0,0 +1,22 @@
+LOAD 'pgl';
+-- the default planner only reports its method
+EXPLAIN (PGL, COSTS OFF) SELECT 1;
+-- a fixed brute-force arm is decoded into the settings it applies
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'local';
+SET pgl.planner_arm = 5;
+EXPLAIN (PGL, COSTS OFF) SELECT 1;
+RESET pgl.planner_arm;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+-- a failed plan choice is an error unless falling back is allowed
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'model';
+EXPLAIN (PGL, COSTS OFF) SELECT 1;
+SET pgl.planner_fallback = on;
+EXPLAIN (PGL, COSTS OFF) SELECT 1;
+RESET pgl.planner_fallback;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+-- nothing is added unless the option is given
+EXPLAIN (PGL false, COSTS OFF) SELECT 1;
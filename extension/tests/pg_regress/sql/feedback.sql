@@ -0,0 +1,33 @@
+LOAD 'pgl';
+CREATE TABLE pgl_feedback_a (id int);
+INSERT INTO pgl_feedback_a SELECT generate_series(1, 10);
+CREATE FUNCTION pgl_feedback_caught() RETURNS int LANGUAGE plpgsql AS $$
+BEGIN
+    BEGIN
+        PERFORM 1 / (id - id) FROM pgl_feedback_a;
+    EXCEPTION WHEN division_by_zero THEN
+        RETURN -1;
+    END;
+    RETURN 1;
+END
+$$;
+-- without pgl in shared_preload_libraries, records stay in the backend until flushed
+SET pgl.feedback = on;
+SELECT count(*) FROM pgl_feedback_a;
+-- an error caught inside a statement does not lose the statement's record
+SELECT pgl_feedback_caught();
+-- a statement rolled back to a savepoint has none
+BEGIN;
+SAVEPOINT s;
+SELECT 1 / (id - id) FROM pgl_feedback_a;
+ROLLBACK TO SAVEPOINT s;
+SELECT count(*) FROM pgl_feedback_a WHERE id > 5;
+COMMIT;
+SET pgl.feedback = off;
+SELECT pgl_feedback_flush();
+SELECT query, planner_method, execution_ms >= 0 AS timed, jsonb_array_length(nodes) > 0 AS nodes
+FROM pgl.feedback_log ORDER BY id;
+SELECT pgl_feedback_flush();
+DELETE FROM pgl.feedback_log;
+DROP FUNCTION pgl_feedback_caught();
+DROP TABLE pgl_feedback_a;
@@ -0,0 +1,64 @@
+LOAD 'pgl';
+-- guards are on by default: at least one row, at most the cartesian product
+SHOW pgl.cardinality_min_rows;
+SHOW pgl.cardinality_clamp_to_cartesian;
+SHOW pgl.cardinality_max_ratio;
+SHOW pgl.cardinality_blend;
+SET pgl.cardinality_blend = 1.5;
+-- no remote estimate has been applied in this backend yet
+SELECT * FROM pgl_cardinality_guard_stats();
+SELECT pgl_cardinality_guard_stats_reset();
+-- recorded estimates pass the same guards
+CREATE TABLE pgl_guards_a (id int, x int);
+CREATE TABLE pgl_guards_b (id int, x int);
+INSERT INTO pgl_guards_a SELECT i, i FROM generate_series(1, 100) i;
+INSERT INTO pgl_guards_b SELECT i, i FROM generate_series(1, 100) i;
+ANALYZE pgl_guards_a, pgl_guards_b;
+CREATE FUNCTION pgl_learned_rows(query text) RETURNS SETOF text LANGUAGE plpgsql AS $$
+DECLARE
+    plan json;
+BEGIN
+    EXECUTE 'EXPLAIN (FORMAT JSON) ' || query INTO plan;
+    RETURN QUERY SELECT jsonb_path_query(plan::jsonb, 'strict $.**."PGL Learned Rows"') #>> '{}';
+END
+$$;
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 1e15
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_guards_a WHERE x < 10')
+WHERE payload->>'kind' = 'base_rel';
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 1e15
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_guards_a a JOIN pgl_guards_b b ON a.id = b.id WHERE a.x < 10 AND b.x < 10')
+WHERE payload->>'kind' = 'join_rel';
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 0
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_guards_a WHERE x < 20')
+WHERE payload->>'kind' = 'base_rel';
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', -5
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_guards_a WHERE x < 30')
+WHERE payload->>'kind' = 'base_rel';
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 'NaN'
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_guards_a WHERE x < 40')
+WHERE payload->>'kind' = 'base_rel';
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'table';
+-- a scan is capped at the table's tuples
+SELECT pgl_learned_rows('SELECT * FROM pgl_guards_a WHERE x < 10') AS learned;
+-- a join is capped at the product of its base relations' tuples, not of
+-- its filtered inputs' rows
+SELECT pgl_learned_rows('SELECT * FROM pgl_guards_a a JOIN pgl_guards_b b ON a.id = b.id WHERE a.x < 10 AND b.x < 10') AS learned;
+-- estimates below pgl.cardinality_min_rows are raised to it
+SELECT pgl_learned_rows('SELECT * FROM pgl_guards_a WHERE x < 20') AS learned;
+-- so are negative ones
+SELECT pgl_learned_rows('SELECT * FROM pgl_guards_a WHERE x < 30') AS learned;
+-- NaN is rejected and PostgreSQL's estimate is kept
+SELECT pgl_learned_rows('SELECT * FROM pgl_guards_a WHERE x < 40') AS learned;
+SELECT * FROM pgl_cardinality_guard_stats();
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+SELECT pgl_cardinality_guard_stats_reset();
+DELETE FROM pgl.cardinality_overrides;
+DROP FUNCTION pgl_learned_rows(text);
+DROP TABLE pgl_guards_a, pgl_guards_b;
@@ -0,0 +1,22 @@
+LOAD 'pgl';
+-- a hint at the head of a statement applies to it alone
+EXPLAIN (PGL, COSTS OFF) /*+ pgl(method=brute, mode=local, arm=5) */ SELECT 1;
+SHOW pgl.planner_method;
+-- hints can opt a statement out of the session's settings
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'local';
+SET pgl.planner_arm = 5;
+EXPLAIN (PGL, COSTS OFF) -- other comments may come first
+/* like this one */ /*+ Pgl(Method=Default, cardinality=off) */ SELECT 1;
+RESET pgl.planner_arm;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+-- invalid hints are ignored with a warning
+EXPLAIN (PGL, COSTS OFF) /*+ pgl(arm=99) */ SELECT 1;
+EXPLAIN (PGL, COSTS OFF) /*+ pgl(method=greedy) */ SELECT 1;
+EXPLAIN (PGL, COSTS OFF) /*+ pgl(planner=brute) */ SELECT 1;
+-- hints elsewhere in the statement are not read
+EXPLAIN (PGL, COSTS OFF) SELECT /*+ pgl(method=brute) */ 1;
+-- each statement of a query string holding several reads its own hints
+EXPLAIN (PGL, COSTS OFF) /*+ pgl(method=brute, mode=local, arm=5) */ SELECT 1 \; EXPLAIN (PGL, COSTS OFF) SELECT 2;
+EXPLAIN (PGL, COSTS OFF) SELECT 1 \; EXPLAIN (PGL, COSTS OFF) /*+ pgl(method=brute, mode=local, arm=5) */ SELECT 2;
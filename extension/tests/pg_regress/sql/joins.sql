@@ -0,0 +1,39 @@
+CREATE TABLE pgl_join_a (id int, name text, x int);
+CREATE TABLE pgl_join_b (id int, name text, y int);
+-- EXISTS becomes a semi join that preserves the outer side
+SELECT DISTINCT payload->'join_semantics'->>'preserved_side' AS preserved,
+       payload->'join_semantics'->>'nullable_side' AS nullable,
+       payload->'join_semantics'->'min_lefthand' AS min_lefthand,
+       payload->'join_semantics'->'min_righthand' AS min_righthand,
+       payload->'fully_supported' AS supported
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_join_a a WHERE EXISTS (SELECT 1 FROM pgl_join_b b WHERE b.id = a.id)')
+WHERE payload->>'join_type' = 'semi';
+-- NOT EXISTS becomes an anti join
+SELECT DISTINCT payload->'join_semantics'->>'preserved_side' AS preserved,
+       payload->'join_semantics'->>'nullable_side' AS nullable,
+       payload->'join_semantics'->'min_lefthand' AS min_lefthand,
+       payload->'join_semantics'->'min_righthand' AS min_righthand,
+       payload->'fully_supported' AS supported
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_join_a a WHERE NOT EXISTS (SELECT 1 FROM pgl_join_b b WHERE b.id = a.id)')
+WHERE payload->>'join_type' = 'anti';
+-- a left join null-extends its inner side
+SELECT DISTINCT payload->'join_semantics'->>'preserved_side' AS preserved,
+       payload->'join_semantics'->>'nullable_side' AS nullable,
+       payload->'join_semantics'->'min_lefthand' AS min_lefthand,
+       payload->'join_semantics'->'min_righthand' AS min_righthand,
+       payload->'fully_supported' AS supported
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_join_a a LEFT JOIN pgl_join_b b ON a.id = b.id')
+WHERE payload->>'join_type' = 'left';
+-- non-equi predicates and expressions over columns are described
+SELECT DISTINCT j->>'operator' AS operator,
+       coalesce(j->>'left_expression', j->>'right_expression') AS expression,
+       j->'pushed_down' AS pushed_down
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_join_a a JOIN pgl_join_b b ON a.x < b.y AND lower(a.name) = b.name'),
+     jsonb_array_elements(payload->'joins') j
+ORDER BY 1 COLLATE "C";
+-- columns of one relation compared with each other are not a join predicate
+SELECT jsonb_array_length(payload->'joins') AS joins,
+       payload->'fully_supported' AS supported,
+       payload->'unsupported_reasons' AS reasons
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_join_a a WHERE a.id < a.x');
+DROP TABLE pgl_join_a, pgl_join_b;
@@ -0,0 +1,26 @@
+CREATE TABLE pgl_label_a (id int, x int);
+CREATE TABLE pgl_label_b (id int, y int);
+INSERT INTO pgl_label_a SELECT i, i FROM generate_series(1, 100) i;
+INSERT INTO pgl_label_b SELECT i, i % 2 FROM generate_series(1, 20) i;
+ANALYZE pgl_label_a, pgl_label_b;
+SET enable_nestloop = off;
+SET enable_mergejoin = off;
+-- every relation subset is labeled with the rows its plan node produced
+SELECT payload->>'kind' AS kind, payload->'alias_names' AS aliases, true_rows, loops
+FROM pgl_cardinality_labels('SELECT * FROM pgl_label_a a JOIN pgl_label_b b ON a.id = b.id WHERE a.x <= 50 AND b.y = 0')
+ORDER BY 1 COLLATE "C", 2::text COLLATE "C";
+-- labels are stored per dataset query
+SELECT pgl_qdataset_create('labels');
+SELECT pgl_qdataset_insert('labels', 'SELECT * FROM pgl_label_a a JOIN pgl_label_b b ON a.id = b.id WHERE b.y = 1');
+SELECT query_id, payload->>'kind' AS kind, true_rows
+FROM pgl_qdataset_collect_cardinality('labels', -1, 10)
+ORDER BY 2 COLLATE "C", 3;
+SELECT kind, count(*), sum(true_rows) AS true_rows
+FROM pgl.pgl_qdataset_labels_cardinality
+GROUP BY kind
+ORDER BY 1 COLLATE "C";
+SELECT current_pos FROM pgl.pgl_qdataset_status WHERE dataset_name = 'labels';
+SELECT pgl_qdataset_delete('labels');
+RESET enable_nestloop;
+RESET enable_mergejoin;
+DROP TABLE pgl_label_a, pgl_label_b;
@@ -0,0 +1,25 @@
+SELECT key FROM pgl_request_metadata() ORDER BY key;
+SELECT bool_and(CASE key
+                  WHEN 'pgl-backend-pid' THEN value = pg_backend_pid()::text
+                  WHEN 'pgl-database' THEN value = current_database()
+                  WHEN 'pgl-user' THEN value = current_user
+                  WHEN 'pgl-session-id' THEN value = (
+                      SELECT to_hex(floor(extract(epoch FROM backend_start))::bigint) || '.' || to_hex(pid)
+                      FROM pg_stat_activity WHERE pid = pg_backend_pid())
+                END) AS matches
+FROM pgl_request_metadata();
+-- a traceparent from the setting
+SET pgl.traceparent = '00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01';
+SELECT value FROM pgl_request_metadata() WHERE key = 'traceparent';
+-- one in a comment of the query takes precedence
+SELECT value FROM pgl_request_metadata() WHERE key = 'traceparent'
+/*traceparent='00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01'*/;
+-- invalid ones are not sent
+SET pgl.traceparent = '00-00000000000000000000000000000000-00f067aa0ba902b7-01';
+SELECT count(*) FROM pgl_request_metadata() WHERE key = 'traceparent';
+SELECT count(*) FROM pgl_request_metadata() WHERE key = 'traceparent'
+/*traceparent='not-a-traceparent'*/;
+RESET pgl.traceparent;
+SELECT count(*) FROM pgl_request_metadata() WHERE key = 'traceparent';
+-- only the comments of the statement itself count
+SELECT count(*) FROM pgl_request_metadata() WHERE key = 'traceparent' \; SELECT 1 AS one /*traceparent='00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01'*/;
@@ -0,0 +1,39 @@
+SELECT pgl_stat_reset();
+CREATE TABLE pgl_metrics_a (id int, x int);
+INSERT INTO pgl_metrics_a SELECT i, i FROM generate_series(1, 100) i;
+ANALYZE pgl_metrics_a;
+-- a learned estimate is timed, and its q-error measured once the statement ran
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 18
+FROM pgl_cardinality_payloads('SELECT count(*) FROM pgl_metrics_a WHERE x < 10');
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'table';
+SET pgl.feedback = on;
+SELECT count(*) FROM pgl_metrics_a WHERE x < 10;
+SET pgl.feedback = off;
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+-- a planner choice that fails falls back to the native arm
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'model';
+SET pgl.planner_fallback = on;
+SELECT 1 AS one;
+RESET pgl.planner_fallback;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+SELECT line
+FROM regexp_split_to_table(pgl_metrics(), E'\n') AS line
+WHERE line ~ '^# TYPE '
+   OR line ~ '^pgl_(requests|request_failures|fallbacks|arm_choices)_total'
+   OR line ~ '^pgl_request_duration_seconds_(count|bucket\{.*le="\+Inf")'
+   OR line ~ '^pgl_cardinality_q_error_';
+-- the latency buckets are cumulative
+SELECT bool_and(count >= previous) AS cumulative
+FROM (SELECT split_part(line, ' ', 2)::bigint AS count,
+             lag(split_part(line, ' ', 2)::bigint, 1, 0) OVER (ORDER BY n) AS previous
+      FROM regexp_split_to_table(pgl_metrics(), E'\n') WITH ORDINALITY AS l(line, n)
+      WHERE line LIKE 'pgl_request_duration_seconds_bucket{component="cardinality"%') AS buckets;
+SELECT right(pgl_metrics(), 6) = E'# EOF\n' AS terminated;
+SELECT pgl_stat_reset();
+DELETE FROM pgl.cardinality_overrides;
+DROP TABLE pgl_metrics_a;
@@ -0,0 +1,48 @@
+LOAD 'pgl';
+-- every registration adds a version, active unless asked otherwise
+SELECT pgl_model_register('cost', 'planner', 'linear', '{"weights": {"log_total_cost": 1}}');
+SELECT pgl_model_register('cost', 'planner', 'linear', '{"weights": {"log_total_cost": 2}}');
+SELECT pgl_model_register('cost', 'planner', 'linear', '{"weights": {"log_total_cost": 3}}', activate => false);
+SELECT version, kind, active FROM pgl.models WHERE name = 'cost' ORDER BY version;
+SELECT pgl_model_predict('cost', '{"log_total_cost": 1}');
+-- activation switches versions for the next statement
+SELECT pgl_model_activate('cost', 3);
+SELECT pgl_model_predict('cost', '{"log_total_cost": 1}');
+-- rollbacks undo activations in reverse order
+SELECT pgl_model_rollback('cost');
+SELECT pgl_model_predict('cost', '{"log_total_cost": 1}');
+SELECT pgl_model_rollback('cost');
+SELECT pgl_model_rollback('cost');
+SELECT version FROM pgl.models WHERE name = 'cost' AND active;
+SELECT pgl_model_activate('cost', 7);
+SELECT pgl_model_register('cost', 'cardinality', 'linear', '{"weights": {}}');
+SELECT pgl_model_register('cost', 'planner', 'remote', '{"weights": {}}');
+SELECT pgl_model_register('cost', 'planner', 'linear', endpoint => 'http://127.0.0.1:1');
+-- remote models are asked at their endpoint and cannot be evaluated locally
+SELECT pgl_model_register('server', 'planner', 'remote', endpoint => 'http://127.0.0.1:1');
+SELECT pgl_model_predict('server', '{}');
+-- the model version is part of the decision, even when the plan fell back
+CREATE FUNCTION pgl_explain_lines(query text, pattern text) RETURNS SETOF text LANGUAGE plpgsql AS $$
+DECLARE
+    line text;
+BEGIN
+    FOR line IN EXECUTE 'EXPLAIN (PGL, COSTS OFF) ' || query LOOP
+        IF line ~ pattern THEN
+            RETURN NEXT line;
+        END IF;
+    END LOOP;
+END
+$$;
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'model';
+SET pgl.planner_fallback = on;
+SET pgl.planner_model = 'server';
+SELECT pgl_explain_lines('SELECT 1', '^(Model|Fallback)');
+SET pgl.planner_model = 'cost';
+SELECT pgl_explain_lines('SELECT 1', '^Model');
+RESET pgl.planner_model;
+RESET pgl.planner_fallback;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+DROP FUNCTION pgl_explain_lines(text, text);
+DELETE FROM pgl.models;
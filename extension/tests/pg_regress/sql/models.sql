@@ -0,0 +1,51 @@
+LOAD 'pgl';
+SELECT pgl_model_register('arm5', 'planner', 'linear', '{"weights": {"arm.enable_hashjoin": -1, "arm.enable_nestloop": -1,
+    "arm.enable_mergejoin": 1, "arm.enable_indexscan": 1, "arm.enable_seqscan": 1,
+    "arm.enable_indexonlyscan": 1}}');
+SELECT pgl_model_register('tree', 'cardinality', 'gbdt', '{"base_score": 1, "trees": [
+    {"nodes": [{"feature": "relations", "threshold": 2, "left": 1, "right": 2},
+               {"leaf": 0.5}, {"leaf": 2}]},
+    {"nodes": [{"leaf": 0.25}]}]}');
+SELECT pgl_model_register('const', 'cardinality', 'linear', jsonb_build_object('weights', '{}'::jsonb, 'bias', ln(42)));
+-- invalid models are rejected when they are registered
+SELECT pgl_model_register('broken', 'cardinality', 'gbdt', '{"trees": [{"nodes": [{"feature": "x", "threshold": 0, "left": 0, "right": 0}]}]}');
+-- models are evaluated in the backend
+SELECT pgl_model_predict('arm5', '{"arm.enable_hashjoin": 1, "arm.enable_seqscan": 1}');
+SELECT pgl_model_predict('tree', '{"relations": 1}'), pgl_model_predict('tree', '{"relations": 3}');
+SELECT pgl_model_predict('missing', '{}');
+-- a planner model scores every brute-force arm and the cheapest is executed
+CREATE FUNCTION pgl_explain_lines(query text, pattern text) RETURNS SETOF text LANGUAGE plpgsql AS $$
+DECLARE
+    line text;
+BEGIN
+    FOR line IN EXECUTE 'EXPLAIN (PGL, COSTS OFF) ' || query LOOP
+        IF line ~ pattern THEN
+            RETURN NEXT line;
+        END IF;
+    END LOOP;
+END
+$$;
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'model';
+SET pgl.planner_model = 'arm5';
+SELECT pgl_explain_lines('SELECT 1', '^(Planner Mode|Arm|Model|Fallback)');
+-- a cardinality model replaces PostgreSQL's estimates
+CREATE TABLE pgl_models_a (id int, x int);
+INSERT INTO pgl_models_a SELECT i, i FROM generate_series(1, 100) i;
+ANALYZE pgl_models_a;
+RESET pgl.planner_model;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'model';
+SET pgl.cardinality_model = 'const';
+SELECT pgl_explain_lines('SELECT * FROM pgl_models_a WHERE x < 10', 'PGL (Learned Rows|Estimate Source|Model)');
+-- a planner model cannot estimate cardinalities
+SET pgl.cardinality_model = 'arm5';
+SELECT pgl_explain_lines('SELECT * FROM pgl_models_a WHERE x < 10', 'PGL Learned Rows');
+RESET pgl.cardinality_model;
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+DROP TABLE pgl_models_a;
+DROP FUNCTION pgl_explain_lines(text, text);
+DELETE FROM pgl.models;
@@ -0,0 +1,44 @@
+CREATE TABLE pgl_override_a (id int, x int);
+INSERT INTO pgl_override_a SELECT i, i FROM generate_series(1, 100) i;
+ANALYZE pgl_override_a;
+CREATE FUNCTION pgl_plan_rows(query text) RETURNS float8 LANGUAGE plpgsql AS $$
+DECLARE
+    plan json;
+BEGIN
+    EXECUTE 'EXPLAIN (FORMAT JSON) ' || query INTO plan;
+    RETURN (plan->0->'Plan'->>'Plan Rows')::float8;
+END
+$$;
+-- record an estimate for the filtered scan
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 42
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_override_a WHERE x < 10');
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'table';
+-- the recorded estimate replaces PostgreSQL's
+SELECT pgl_plan_rows('SELECT * FROM pgl_override_a WHERE x < 10');
+-- EXPLAIN shows both estimates and where the applied one came from
+CREATE FUNCTION pgl_plan_node(query text) RETURNS json LANGUAGE plpgsql AS $$
+DECLARE
+    plan json;
+BEGIN
+    EXECUTE 'EXPLAIN (FORMAT JSON) ' || query INTO plan;
+    RETURN plan->0->'Plan';
+END
+$$;
+SELECT node->>'PGL Learned Rows' AS learned,
+       node->>'PGL Estimate Source' AS source,
+       (node->>'PGL Native Rows')::float8 < 100 AS native
+FROM pgl_plan_node('SELECT * FROM pgl_override_a WHERE x < 10') node;
+-- queries without a recorded estimate keep PostgreSQL's
+SELECT pgl_plan_rows('SELECT * FROM pgl_override_a WHERE x < 20') <> 42 AS native;
+-- without the overrides table, PostgreSQL's estimates are kept
+ALTER TABLE pgl.cardinality_overrides RENAME TO cardinality_overrides_moved;
+SELECT pgl_plan_rows('SELECT * FROM pgl_override_a WHERE x < 10') <> 42 AS native;
+ALTER TABLE pgl.cardinality_overrides_moved RENAME TO cardinality_overrides;
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+DELETE FROM pgl.cardinality_overrides;
+DROP FUNCTION pgl_plan_rows(text);
+DROP FUNCTION pgl_plan_node(text);
+DROP TABLE pgl_override_a;
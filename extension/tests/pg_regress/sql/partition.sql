@@ -0,0 +1,47 @@
+CREATE TABLE pgl_part_range (id int, v int) PARTITION BY RANGE (id);
+CREATE TABLE pgl_part_range_1 PARTITION OF pgl_part_range FOR VALUES FROM (0) TO (100);
+CREATE TABLE pgl_part_range_2 PARTITION OF pgl_part_range FOR VALUES FROM (100) TO (200);
+CREATE TABLE pgl_part_list (region text, v int) PARTITION BY LIST (region);
+CREATE TABLE pgl_part_list_eu PARTITION OF pgl_part_list FOR VALUES IN ('eu');
+CREATE TABLE pgl_part_list_us PARTITION OF pgl_part_list FOR VALUES IN ('us');
+CREATE TABLE pgl_part_hash (id int, v int) PARTITION BY HASH (id);
+CREATE TABLE pgl_part_hash_0 PARTITION OF pgl_part_hash FOR VALUES WITH (MODULUS 2, REMAINDER 0);
+CREATE TABLE pgl_part_hash_1 PARTITION OF pgl_part_hash FOR VALUES WITH (MODULUS 2, REMAINDER 1);
+-- payloads that carry partition details are version 2
+SELECT DISTINCT payload->>'payload_version' AS version
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_part_range');
+-- every surviving partition points back at its parent
+SELECT payload->'relation_names'->>0 AS relation,
+       payload->'partition'->>'parent_name' AS parent,
+       payload->'partition'->>'strategy' AS strategy,
+       payload->'partition'->>'bound' AS bound,
+       payload->'partition'->'child_rt_indexes' AS children
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_part_range')
+ORDER BY 1;
+-- pruned partitions are not reported as children
+SELECT payload->'relation_names'->>0 AS relation,
+       payload->'partition'->>'parent_name' AS parent,
+       payload->'partition'->>'strategy' AS strategy,
+       payload->'partition'->>'bound' AS bound,
+       payload->'partition'->'child_rt_indexes' AS children
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_part_range WHERE id < 50')
+ORDER BY 1;
+SELECT payload->'relation_names'->>0 AS relation,
+       payload->'partition'->>'parent_name' AS parent,
+       payload->'partition'->>'strategy' AS strategy,
+       payload->'partition'->>'bound' AS bound,
+       payload->'partition'->'child_rt_indexes' AS children
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_part_list WHERE region = ''us''')
+ORDER BY 1;
+SELECT payload->'relation_names'->>0 AS relation,
+       payload->'partition'->>'parent_name' AS parent,
+       payload->'partition'->>'strategy' AS strategy,
+       payload->'partition'->>'bound' AS bound,
+       payload->'partition'->'child_rt_indexes' AS children
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_part_hash')
+ORDER BY 1;
+-- plain tables carry no partition information
+CREATE TABLE pgl_part_plain (id int);
+SELECT payload ? 'partition' AS has_partition
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_part_plain');
+DROP TABLE pgl_part_range, pgl_part_list, pgl_part_hash, pgl_part_plain;
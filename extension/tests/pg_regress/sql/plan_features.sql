@@ -0,0 +1,35 @@
+CREATE TABLE pgl_features_a (id int PRIMARY KEY, x int);
+CREATE TEMP TABLE pgl_features AS
+SELECT pgl_plan_features('{"Plan": {"Node Type": "Append", "Plan Rows": 10, "Total Cost": 5,
+    "Startup Cost": 0, "Plans": [
+        {"Node Type": "Seq Scan", "Relation Name": "pgl_features_a", "Plan Rows": 4},
+        {"Node Type": "Seq Scan", "Plan Rows": 3},
+        {"Node Type": "Index Scan", "Relation Name": "pgl_features_a",
+         "Index Name": "pgl_features_a_pkey", "Plan Rows": 3}]}}') AS f;
+-- the third child of the Append is chained under a second Append node
+SELECT f->'left' AS left, f->'right' AS right FROM pgl_features;
+SELECT jsonb_array_length(f->'features') AS nodes,
+       jsonb_array_length(f->'features'->0) AS width,
+       (SELECT count(*) FROM pgl_plan_feature_names()) AS names
+FROM pgl_features;
+SELECT n.name, f->'features'->1->(n.i::int - 1) AS root
+FROM pgl_features, pgl_plan_feature_names() WITH ORDINALITY AS n(name, i)
+WHERE (f->'features'->1->>(n.i::int - 1))::float8 <> 0;
+SELECT (f->'relations'->>2)::oid = 'pgl_features_a'::regclass AS scan,
+       (f->'relations'->>5)::oid = 'pgl_features_a'::regclass AS index_scan,
+       (f->'indexes'->>5)::oid = 'pgl_features_a_pkey'::regclass AS index
+FROM pgl_features;
+-- EXPLAIN output can be featurized as is
+CREATE FUNCTION pgl_explain_json(query text) RETURNS jsonb LANGUAGE plpgsql AS $$
+DECLARE
+    plan json;
+BEGIN
+    EXECUTE 'EXPLAIN (FORMAT JSON) ' || query INTO plan;
+    RETURN plan::jsonb;
+END
+$$;
+SELECT pgl_plan_features(pgl_explain_json('SELECT * FROM pgl_features_a'))->'left' AS left;
+SELECT pgl_plan_features('{}');
+DROP FUNCTION pgl_explain_json(text);
+DROP TABLE pgl_features;
+DROP TABLE pgl_features_a;
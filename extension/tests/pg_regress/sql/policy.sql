@@ -0,0 +1,67 @@
+LOAD 'pgl';
+CREATE TABLE pgl_policy_a (id int, x int);
+INSERT INTO pgl_policy_a SELECT i, i FROM generate_series(1, 100) i;
+ANALYZE pgl_policy_a;
+CREATE FUNCTION pgl_plan_rows(query text) RETURNS float8 LANGUAGE plpgsql AS $$
+DECLARE
+    plan json;
+BEGIN
+    EXECUTE 'EXPLAIN (FORMAT JSON) ' || query INTO plan;
+    RETURN (plan->0->'Plan'->>'Plan Rows')::float8;
+END
+$$;
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'local';
+SET pgl.planner_arm = 21;
+-- a planner rule for a schema falls back to PostgreSQL's planner
+INSERT INTO pgl.policy (component, enabled, schema) VALUES ('planner', false, 'public');
+EXPLAIN (PGL, COSTS OFF) SELECT * FROM pgl_policy_a;
+-- statements not touching the schema are planned as configured
+EXPLAIN (PGL, COSTS OFF) SELECT 1;
+-- a more specific rule carves out an exception
+INSERT INTO pgl.policy (component, enabled, schema, relation)
+VALUES ('planner', true, 'public', 'pgl_policy_a');
+EXPLAIN (PGL, COSTS OFF) SELECT * FROM pgl_policy_a;
+-- unless a broader rule has a higher priority
+UPDATE pgl.policy SET priority = 1 WHERE relation IS NULL;
+EXPLAIN (PGL, COSTS OFF) SELECT * FROM pgl_policy_a;
+-- rules for other roles or databases do not apply
+UPDATE pgl.policy SET role = 'pgl_policy_nobody' WHERE relation IS NULL;
+EXPLAIN (PGL, COSTS OFF) SELECT * FROM pgl_policy_a;
+UPDATE pgl.policy SET role = NULL, database = 'pgl_policy_nowhere' WHERE relation IS NULL;
+EXPLAIN (PGL, COSTS OFF) SELECT * FROM pgl_policy_a;
+-- a role's rules apply to its members; superusers are not members of every role
+CREATE ROLE pgl_policy_member;
+GRANT SELECT ON pgl_policy_a TO pgl_policy_member;
+UPDATE pgl.policy SET role = 'pgl_policy_member', database = NULL WHERE relation IS NULL;
+EXPLAIN (PGL, COSTS OFF) SELECT * FROM pgl_policy_a;
+SET ROLE pgl_policy_member;
+EXPLAIN (PGL, COSTS OFF) SELECT * FROM pgl_policy_a;
+RESET ROLE;
+REVOKE SELECT ON pgl_policy_a FROM pgl_policy_member;
+DROP ROLE pgl_policy_member;
+DELETE FROM pgl.policy;
+RESET pgl.planner_arm;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+-- cardinality rules keep PostgreSQL's estimates for the tables they match
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 42
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_policy_a WHERE x < 10');
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'table';
+SELECT pgl_plan_rows('SELECT * FROM pgl_policy_a WHERE x < 10');
+INSERT INTO pgl.policy (component, enabled, max_pages) VALUES ('cardinality', false, 10);
+SELECT pgl_plan_rows('SELECT * FROM pgl_policy_a WHERE x < 10') < 42 AS native;
+UPDATE pgl.policy SET max_pages = 0;
+SELECT pgl_plan_rows('SELECT * FROM pgl_policy_a WHERE x < 10');
+TRUNCATE pgl.policy;
+INSERT INTO pgl.policy (component, enabled, relation) VALUES ('cardinality', false, 'pgl_policy_a');
+SELECT pgl_plan_rows('SELECT * FROM pgl_policy_a WHERE x < 10') < 42 AS native;
+DELETE FROM pgl.policy;
+SELECT pgl_plan_rows('SELECT * FROM pgl_policy_a WHERE x < 10');
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+DELETE FROM pgl.cardinality_overrides;
+DROP FUNCTION pgl_plan_rows(text);
+DROP TABLE pgl_policy_a;
@@ -0,0 +1,67 @@
+INSERT INTO pgl.regression_pins (query_id, arm, learned_arm, learned_ms, native_ms, pinned_until)
+VALUES (1, 63, 5, 120, 10, now() + interval '1 hour'),
+       (2, 31, 7, 90, 30, now() + interval '1 hour'),
+       (3, 63, 1, 50, 5, now() - interval '1 second');
+-- expired pins are not listed
+SELECT query_id, arm, learned_arm, learned_ms, native_ms, pinned_until > pinned_at AS active
+FROM pgl_regression_pins() ORDER BY query_id;
+SELECT pgl_regression_pins_clear(2);
+SELECT query_id FROM pgl_regression_pins();
+SELECT pgl_regression_pins_clear();
+SELECT count(*) FROM pgl.regression_pins;
+-- executions feed the plan latencies, and a learned arm slower than the native one is pinned
+LOAD 'pgl';
+ALTER SYSTEM SET pgl.regression_guard = on;
+ALTER SYSTEM SET pgl.regression_min_executions = 1;
+SELECT pg_reload_conf();
+SELECT pg_sleep(0.5);
+SET compute_query_id = on;
+CREATE TABLE pgl_pins_a (id int);
+INSERT INTO pgl_pins_a SELECT generate_series(1, 10);
+SELECT pgl_model_register('pins_arm5', 'planner', 'linear', '{"weights": {"arm.enable_hashjoin": -1, "arm.enable_nestloop": -1,
+    "arm.enable_mergejoin": 1, "arm.enable_indexscan": 1, "arm.enable_seqscan": 1,
+    "arm.enable_indexonlyscan": 1}}');
+CREATE FUNCTION pgl_pins_explain(query text) RETURNS SETOF text LANGUAGE plpgsql AS $$
+DECLARE
+    line text;
+BEGIN
+    FOR line IN EXECUTE 'EXPLAIN (PGL, COSTS OFF) ' || query LOOP
+        IF line ~ '^\s*(Arm|Pinned):' THEN
+            RETURN NEXT trim(line);
+        END IF;
+    END LOOP;
+END
+$$;
+SET pgl.feedback = on;
+SELECT count(*) FROM pgl_pins_a;
+SET pgl.feedback = off;
+SELECT pgl_feedback_flush();
+-- the native plan ran in no time
+UPDATE pgl.plan_latencies SET mean_ms = 0;
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'model';
+SET pgl.planner_model = 'pins_arm5';
+SET pgl.feedback = on;
+SELECT count(*) FROM pgl_pins_a;
+SET pgl.feedback = off;
+SELECT pgl_feedback_flush();
+SELECT arm, executions FROM pgl.plan_latencies ORDER BY arm;
+SELECT arm, learned_arm, learned_ms > native_ms AS slower FROM pgl_regression_pins();
+SELECT pgl_pins_explain('SELECT count(*) FROM pgl_pins_a');
+-- without the pins table, queries are planned as if none were pinned
+ALTER TABLE pgl.regression_pins RENAME TO regression_pins_moved;
+SELECT pgl_pins_explain('SELECT count(*) FROM pgl_pins_a');
+ALTER TABLE pgl.regression_pins_moved RENAME TO regression_pins;
+RESET pgl.planner_model;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+RESET compute_query_id;
+SELECT pgl_regression_pins_clear();
+DELETE FROM pgl.plan_latencies;
+DELETE FROM pgl.feedback_log;
+DELETE FROM pgl.models;
+DROP FUNCTION pgl_pins_explain(text);
+DROP TABLE pgl_pins_a;
+ALTER SYSTEM RESET pgl.regression_min_executions;
+ALTER SYSTEM RESET pgl.regression_guard;
+SELECT pg_reload_conf();
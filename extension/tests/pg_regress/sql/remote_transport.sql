@@ -0,0 +1,39 @@
+LOAD 'pgl';
+CREATE FUNCTION pgl_transport_fallback(query text) RETURNS SETOF text LANGUAGE plpgsql AS $$
+DECLARE
+    line text;
+BEGIN
+    FOR line IN EXECUTE 'EXPLAIN (PGL, COSTS OFF) ' || query LOOP
+        IF line ~ '^\s*(Arm|Fallback):' THEN
+            RETURN NEXT trim(line);
+        END IF;
+    END LOOP;
+END
+$$;
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'remote';
+-- PostgreSQL's own socket stands in for a server that does not speak gRPC
+SELECT set_config('pgl.remote_planner_url', 'unix://' || split_part(current_setting('unix_socket_directories'), ',', 1)
+    || '/.s.PGSQL.' || current_setting('port'), false) LIKE 'unix://%' AS unix;
+SET pgl.planner_fallback = on;
+SET client_min_messages = error;
+SELECT pgl_transport_fallback('SELECT 1');
+RESET client_min_messages;
+RESET pgl.planner_fallback;
+-- an unreadable CA certificate fails the request, not the backend
+ALTER SYSTEM SET pgl.remote_tls_ca = '/nonexistent/ca.pem';
+SELECT pg_reload_conf();
+SELECT pg_sleep(0.5);
+SET pgl.remote_planner_url = 'https://localhost:1';
+SELECT 1 AS one;
+SET pgl.planner_fallback = on;
+SET client_min_messages = error;
+SELECT pgl_transport_fallback('SELECT 1');
+RESET client_min_messages;
+ALTER SYSTEM RESET pgl.remote_tls_ca;
+SELECT pg_reload_conf();
+RESET pgl.planner_fallback;
+RESET pgl.remote_planner_url;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+DROP FUNCTION pgl_transport_fallback(text);
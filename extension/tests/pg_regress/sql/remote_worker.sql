@@ -0,0 +1,14 @@
+LOAD 'pgl';
+SET pgl.remote_transport = 'worker';
+SET pgl.remote_planner_url = 'unix:///nonexistent/pgl.sock';
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'remote';
+-- without a running worker, requests through it fail
+SELECT 1 AS one;
+SET pgl.planner_fallback = on;
+SELECT 1 AS one;
+RESET pgl.planner_fallback;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+RESET pgl.remote_planner_url;
+RESET pgl.remote_transport;
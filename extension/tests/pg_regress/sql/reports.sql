@@ -0,0 +1,17 @@
+LOAD 'pgl';
+-- reports are queued without waiting for the server, and lost when it cannot be reached
+SET pgl.remote_planner_url = 'unix:///nonexistent/pgl.sock';
+SET pgl.report_execution = on;
+SELECT 1 AS one;
+SET pgl.report_execution = off;
+DO $$
+BEGIN
+    FOR i IN 1..500 LOOP
+        EXIT WHEN (SELECT sent + failed FROM pgl_stat_reports()) > 0;
+        PERFORM pg_sleep(0.01);
+    END LOOP;
+END
+$$;
+SELECT * FROM pgl_stat_reports();
+RESET pgl.report_execution;
+RESET pgl.remote_planner_url;
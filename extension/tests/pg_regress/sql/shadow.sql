@@ -0,0 +1,33 @@
+CREATE TABLE pgl_shadow_a (id int, x int);
+INSERT INTO pgl_shadow_a SELECT i, i FROM generate_series(1, 100) i;
+ANALYZE pgl_shadow_a;
+CREATE FUNCTION pgl_plan_node(query text) RETURNS json LANGUAGE plpgsql AS $$
+DECLARE
+    plan json;
+BEGIN
+    EXECUTE 'EXPLAIN (FORMAT JSON) ' || query INTO plan;
+    RETURN plan->0->'Plan';
+END
+$$;
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 42
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_shadow_a WHERE x < 10');
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'table';
+SET pgl.shadow = on;
+SELECT pgl_shadow_decisions_reset();
+-- the learned estimate is reported but PostgreSQL's is kept
+SELECT (node->>'Plan Rows')::float8 < 42 AS native,
+       node->>'PGL Shadow Rows' AS shadow,
+       node->>'PGL Learned Rows' AS learned
+FROM pgl_plan_node('SELECT * FROM pgl_shadow_a WHERE x < 10') node;
+SELECT component, native_rows < 100 AS native, shadow_rows
+FROM pgl.shadow_decisions;
+SELECT pgl_shadow_decisions_reset();
+SELECT count(*) FROM pgl.shadow_decisions;
+RESET pgl.shadow;
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+DELETE FROM pgl.cardinality_overrides;
+DROP FUNCTION pgl_plan_node(text);
+DROP TABLE pgl_shadow_a;
@@ -0,0 +1,23 @@
+CREATE TABLE pgl_key_a (id int, x int);
+CREATE TABLE pgl_key_b (id int, y int);
+-- keys are versioned 128-bit hashes
+SELECT DISTINCT left(payload->>'state_key', 3) AS version,
+       length(payload->>'state_key') AS length
+FROM pgl_cardinality_payloads('SELECT * FROM pgl_key_a a JOIN pgl_key_b b ON a.id = b.id');
+-- keys ignore predicate order, operand order and whitespace
+SELECT payload->>'kind' AS kind, count(DISTINCT payload->>'state_key') AS keys
+FROM (
+    SELECT payload FROM pgl_cardinality_payloads('SELECT * FROM pgl_key_a a JOIN pgl_key_b b ON a.id = b.id WHERE a.x > 5 AND b.y = 1')
+    UNION ALL
+    SELECT payload FROM pgl_cardinality_payloads('SELECT *   FROM pgl_key_b b JOIN pgl_key_a a ON b.id = a.id WHERE b.y = 1 AND 5 < a.x')
+) p
+GROUP BY 1
+ORDER BY 1;
+-- different predicates give different keys
+SELECT count(DISTINCT payload->>'state_key') AS keys
+FROM (
+    SELECT payload FROM pgl_cardinality_payloads('SELECT * FROM pgl_key_a a WHERE a.x > 5')
+    UNION ALL
+    SELECT payload FROM pgl_cardinality_payloads('SELECT * FROM pgl_key_a a WHERE a.x > 6')
+) p;
+DROP TABLE pgl_key_a, pgl_key_b;
@@ -0,0 +1,38 @@
+CREATE TABLE pgl_stats_a (id int, x int);
+INSERT INTO pgl_stats_a SELECT i, i FROM generate_series(1, 100) i;
+ANALYZE pgl_stats_a;
+SELECT pgl_stat_reset();
+SELECT count(*) FROM pgl.stat_components;
+-- estimates read from the overrides table are counted, misses as fallbacks
+INSERT INTO pgl.cardinality_overrides (query_hash, state_key, rows)
+SELECT (payload->>'query_hash')::bigint, payload->>'state_key', 42
+FROM pgl_cardinality_payloads('SELECT count(*) FROM pgl_stats_a WHERE x < 10');
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'table';
+SELECT count(*) FROM pgl_stats_a WHERE x < 10;
+SELECT count(*) FROM pgl_stats_a WHERE x < 20;
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+-- planner choices that fail fall back to the native plan
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'model';
+SET pgl.planner_fallback = on;
+SELECT 1 AS one;
+RESET pgl.planner_fallback;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+SELECT component, endpoint, calls, failures, fallbacks, cache_hits,
+       p50_ms IS NOT NULL AS timed, p50_ms <= p99_ms AS ordered,
+       overhead_ms >= 0 AS overhead, bytes_sent
+FROM pgl.stat_components
+ORDER BY component, endpoint;
+SELECT pgl_stat_reset();
+SELECT count(*) FROM pgl.stat_components;
+DELETE FROM pgl.cardinality_overrides;
+DROP TABLE pgl_stats_a;
+-- resetting is reserved to superusers, as pg_stat_reset() is
+CREATE ROLE regress_pgl_stats;
+SET ROLE regress_pgl_stats;
+SELECT pgl_stat_reset();
+RESET ROLE;
+DROP ROLE regress_pgl_stats;
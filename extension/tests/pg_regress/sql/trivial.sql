@@ -0,0 +1,71 @@
+LOAD 'pgl';
+CREATE TABLE pgl_trivial_a (id int, x int);
+INSERT INTO pgl_trivial_a SELECT i, i FROM generate_series(1, 100) i;
+ANALYZE pgl_trivial_a;
+CREATE FUNCTION pgl_skipped(query text) RETURNS SETOF text LANGUAGE plpgsql AS $$
+DECLARE
+    line text;
+BEGIN
+    FOR line IN EXECUTE 'EXPLAIN (PGL, COSTS OFF) ' || query LOOP
+        IF line ~ '^\s*(Planner Method|Skipped):' THEN
+            RETURN NEXT trim(line);
+        END IF;
+    END LOOP;
+END
+$$;
+SET pgl.planner_method = 'brute';
+SET pgl.planner_mode = 'local';
+SET pgl.planner_arm = 21;
+-- catalog queries are planned like any other by default
+SELECT pgl_skipped('SELECT relname FROM pg_class WHERE oid = 1259');
+-- pgl.skip_catalog_queries leaves them to PostgreSQL
+SET pgl.skip_catalog_queries = on;
+SELECT pgl_skipped('SELECT relname FROM pg_class WHERE oid = 1259');
+SELECT pgl_skipped('SELECT table_name FROM information_schema.tables');
+SELECT pgl_skipped('SELECT 1');
+RESET pgl.skip_catalog_queries;
+-- so are statements scanning fewer relations than pgl.min_relations
+SET pgl.min_relations = 2;
+SELECT pgl_skipped('SELECT * FROM pgl_trivial_a WHERE id = 1');
+SELECT pgl_skipped('SELECT * FROM pgl_trivial_a a JOIN pgl_trivial_a b USING (id)');
+RESET pgl.min_relations;
+-- a fixed arm plans no more than the native plan, so its cost is not checked
+SET pgl.min_native_cost = 1e9;
+SELECT pgl_skipped('SELECT * FROM pgl_trivial_a WHERE id = 1');
+-- but statements using learned cardinalities whose native plan is cheaper
+-- than pgl.min_native_cost are skipped
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'table';
+SELECT pgl_skipped('SELECT * FROM pgl_trivial_a WHERE id = 1');
+SET pgl.min_native_cost = 0.001;
+SELECT pgl_skipped('SELECT * FROM pgl_trivial_a WHERE id = 1');
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+RESET pgl.min_native_cost;
+-- pgl.skip_utility_statements leaves queries planned by utility statements
+-- to PostgreSQL, so a failing planner does not fail them
+SET pgl.planner_mode = 'model';
+CREATE TABLE pgl_trivial_copy AS SELECT * FROM pgl_trivial_a WHERE id = 1;
+SET pgl.skip_utility_statements = on;
+CREATE TABLE pgl_trivial_copy AS SELECT * FROM pgl_trivial_a WHERE id = 1;
+SET pgl.planner_mode = 'local';
+-- the queries run by functions they call are not skipped
+CREATE TABLE pgl_trivial_lines AS
+SELECT pgl_skipped('SELECT * FROM pgl_trivial_a WHERE id = 1') AS line;
+SELECT line FROM pgl_trivial_lines;
+RESET pgl.skip_utility_statements;
+RESET pgl.planner_arm;
+RESET pgl.planner_mode;
+RESET pgl.planner_method;
+-- the thresholds apply to learned cardinalities alone as well
+SET pgl.enable_remote_cardinality = on;
+SET pgl.cardinality_source = 'table';
+SET pgl.min_relations = 2;
+SELECT pgl_skipped('SELECT * FROM pgl_trivial_a WHERE id = 1');
+RESET pgl.min_relations;
+SELECT pgl_skipped('SELECT * FROM pgl_trivial_a WHERE id = 1');
+RESET pgl.cardinality_source;
+RESET pgl.enable_remote_cardinality;
+DROP TABLE pgl_trivial_copy, pgl_trivial_lines;
+DROP FUNCTION pgl_skipped(text);
+DROP TABLE pgl_trivial_a;
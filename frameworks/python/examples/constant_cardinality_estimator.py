@@ -1,3 +1,4 @@
+import argparse
 import json
 
 from pgl import PglAdapter, run_server
@@ -20,4 +21,18 @@ class ConstantCardinalityEstimator(PglAdapter):
 
 
 if __name__ == "__main__":
-    run_server(ConstantCardinalityEstimator())
+    parser = argparse.ArgumentParser(description="Stand-in pglearned model server")
+    parser.add_argument("--address", help="e.g. localhost:50051 or unix:///tmp/pgl.sock")
+    parser.add_argument("--scale", type=float, default=1.0)
+    parser.add_argument("--tls-cert")
+    parser.add_argument("--tls-key")
+    parser.add_argument("--tls-client-ca")
+    args = parser.parse_args()
+
+    run_server(
+        ConstantCardinalityEstimator(args.scale),
+        address=args.address,
+        tls_cert=args.tls_cert,
+        tls_key=args.tls_key,
+        tls_client_ca=args.tls_client_ca,
+    )
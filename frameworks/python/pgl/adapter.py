@@ -1,5 +1,5 @@
 from abc import ABC, abstractmethod
-from typing import List, Dict, Any
+from typing import List, Dict, Any, Optional
 
 
 class PglAdapter(ABC):
@@ -20,6 +20,30 @@ class PglAdapter(ABC):
         """
         pass
 
+    def choose_plan_with_features(
+        self,
+        plans: List[Dict[str, Any]],
+        features: Optional[List[Optional[Dict[str, Any]]]],
+    ) -> int:
+        """
+        Choose the best query plan, given the extension's featurization of
+        each plan. Optional; the default ignores the features and calls
+        choose_plan.
+
+        Args:
+            plans: A list of query plans (parsed as dictionaries).
+            features: With pgl.plan_features on, the pgl_plan_features
+                output of each plan (None for a plan that could not be
+                featurized): `features`, one vector per node with row 0 as
+                padding, `left` and `right` child rows (0 for none), and the
+                `relations` and `indexes` OIDs of each row. None when the
+                extension did not send features.
+
+        Returns:
+            The index of the chosen plan (0-based).
+        """
+        return self.choose_plan(plans)
+
     @abstractmethod
     def cardinality_estimate(self, rel_opts: List[str]) -> List[int]:
         """
@@ -32,3 +56,18 @@ class PglAdapter(ABC):
             A list of estimated cardinalities.
         """
         pass
+
+    def on_execution(self, execution: Dict[str, Any]) -> None:
+        """
+        Receive the outcome of an executed query. Optional; the default
+        ignores it.
+
+        Args:
+            execution: A dictionary with `query_id`, `plan` (the executed
+                plan, or None when it was not rendered), `plan_fingerprint`,
+                `arm`, `planning_ms`, `execution_ms`, `nodes`, a list of
+                dictionaries with `node_type`, `relids`, `plan_rows`,
+                `actual_rows` and `loops`, and `model`, the `name` and
+                `version` of the planner model that chose the plan, or None.
+        """
+        pass
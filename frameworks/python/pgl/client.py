@@ -1,3 +1,5 @@
+import json
+
 import psycopg
 from typing import Iterator, Tuple, Dict, Any
 
@@ -50,3 +52,21 @@ class PglClient:
                 # Iterate over results
                 for row in cur:
                     yield row[0], row[1]
+
+    def plan_features(self, plan: Dict[str, Any]) -> Dict[str, Any]:
+        """
+        Featurize a collected plan the way the extension featurizes the
+        candidates it sends to the remote planner with pgl.plan_features on.
+
+        Args:
+            plan: A plan as yielded by qdataset_collect.
+
+        Returns:
+            The pgl_plan_features output for the plan.
+        """
+        with psycopg.connect(self.dburl) as conn:
+            with conn.cursor() as cur:
+                cur.execute(
+                    "SELECT pgl_plan_features(%s::jsonb)", (json.dumps(plan),)
+                )
+                return cur.fetchone()[0]
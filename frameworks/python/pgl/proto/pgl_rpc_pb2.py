@@ -24,21 +24,29 @@ _sym_db = _symbol_database.Default()
 
 
 
-DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\rpgl_rpc.proto\x12\x07pgl_rpc\"\"\n\x11\x43hoosePlanRequest\x12\r\n\x05plans\x18\x01 \x03(\t\"/\n\x12\x43hoosePlanResponse\x12\x19\n\x11\x63hosen_plan_index\x18\x01 \x01(\x05\".\n\x1a\x43\x61rdinalityEstimateRequest\x12\x10\n\x08rel_opts\x18\x01 \x03(\t\"<\n\x1b\x43\x61rdinalityEstimateResponse\x12\x1d\n\x15\x63\x61rdinality_estimates\x18\x01 \x03(\x03\x32\xb4\x01\n\tPglRemote\x12\x45\n\nChoosePlan\x12\x1a.pgl_rpc.ChoosePlanRequest\x1a\x1b.pgl_rpc.ChoosePlanResponse\x12`\n\x13\x43\x61rdinalityEstimate\x12#.pgl_rpc.CardinalityEstimateRequest\x1a$.pgl_rpc.CardinalityEstimateResponseb\x06proto3')
+DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\rpgl_rpc.proto\x12\x07pgl_rpc\"-\n\x0cModelVersion\x12\x0c\n\x04name\x18\x01 \x01(\t\x12\x0f\n\x07version\x18\x02 \x01(\x05\"Z\n\x11\x43hoosePlanRequest\x12\r\n\x05plans\x18\x01 \x03(\t\x12\x10\n\x08\x66\x65\x61tures\x18\x02 \x03(\t\x12$\n\x05model\x18\x03 \x01(\x0b\x32\x15.pgl_rpc.ModelVersion\"/\n\x12\x43hoosePlanResponse\x12\x19\n\x11\x63hosen_plan_index\x18\x01 \x01(\x05\"T\n\x1a\x43\x61rdinalityEstimateRequest\x12\x10\n\x08rel_opts\x18\x01 \x03(\t\x12$\n\x05model\x18\x02 \x01(\x0b\x32\x15.pgl_rpc.ModelVersion\"<\n\x1b\x43\x61rdinalityEstimateResponse\x12\x1d\n\x15\x63\x61rdinality_estimates\x18\x01 \x03(\x03\"h\n\x0c\x45xecutedNode\x12\x11\n\tnode_type\x18\x01 \x01(\t\x12\x0e\n\x06relids\x18\x02 \x03(\r\x12\x11\n\tplan_rows\x18\x03 \x01(\x01\x12\x13\n\x0b\x61\x63tual_rows\x18\x04 \x01(\x01\x12\r\n\x05loops\x18\x05 \x01(\x01\"\xd6\x01\n\x16ReportExecutionRequest\x12\x10\n\x08query_id\x18\x01 \x01(\x03\x12\x0c\n\x04plan\x18\x02 \x01(\t\x12\x18\n\x10plan_fingerprint\x18\x03 \x01(\t\x12\x0b\n\x03\x61rm\x18\x04 \x01(\x05\x12\x13\n\x0bplanning_ms\x18\x05 \x01(\x01\x12\x14\n\x0c\x65xecution_ms\x18\x06 \x01(\x01\x12$\n\x05nodes\x18\x07 \x03(\x0b\x32\x15.pgl_rpc.ExecutedNode\x12$\n\x05model\x18\x08 \x01(\x0b\x32\x15.pgl_rpc.ModelVersion\"\x19\n\x17ReportExecutionResponse2\x8a\x02\n\tPglRemote\x12\x45\n\nChoosePlan\x12\x1a.pgl_rpc.ChoosePlanRequest\x1a\x1b.pgl_rpc.ChoosePlanResponse\x12`\n\x13\x43\x61rdinalityEstimate\x12#.pgl_rpc.CardinalityEstimateRequest\x1a$.pgl_rpc.CardinalityEstimateResponse\x12T\n\x0fReportExecution\x12\x1f.pgl_rpc.ReportExecutionRequest\x1a .pgl_rpc.ReportExecutionResponseb\x06proto3')
 
 _globals = globals()
 _builder.BuildMessageAndEnumDescriptors(DESCRIPTOR, _globals)
 _builder.BuildTopDescriptorsAndMessages(DESCRIPTOR, 'pgl_rpc_pb2', _globals)
 if not _descriptor._USE_C_DESCRIPTORS:
   DESCRIPTOR._loaded_options = None
-  _globals['_CHOOSEPLANREQUEST']._serialized_start=26
-  _globals['_CHOOSEPLANREQUEST']._serialized_end=60
-  _globals['_CHOOSEPLANRESPONSE']._serialized_start=62
-  _globals['_CHOOSEPLANRESPONSE']._serialized_end=109
-  _globals['_CARDINALITYESTIMATEREQUEST']._serialized_start=111
-  _globals['_CARDINALITYESTIMATEREQUEST']._serialized_end=157
-  _globals['_CARDINALITYESTIMATERESPONSE']._serialized_start=159
-  _globals['_CARDINALITYESTIMATERESPONSE']._serialized_end=219
-  _globals['_PGLREMOTE']._serialized_start=222
-  _globals['_PGLREMOTE']._serialized_end=402
+  _globals['_MODELVERSION']._serialized_start=26
+  _globals['_MODELVERSION']._serialized_end=71
+  _globals['_CHOOSEPLANREQUEST']._serialized_start=73
+  _globals['_CHOOSEPLANREQUEST']._serialized_end=163
+  _globals['_CHOOSEPLANRESPONSE']._serialized_start=165
+  _globals['_CHOOSEPLANRESPONSE']._serialized_end=212
+  _globals['_CARDINALITYESTIMATEREQUEST']._serialized_start=214
+  _globals['_CARDINALITYESTIMATEREQUEST']._serialized_end=298
+  _globals['_CARDINALITYESTIMATERESPONSE']._serialized_start=300
+  _globals['_CARDINALITYESTIMATERESPONSE']._serialized_end=360
+  _globals['_EXECUTEDNODE']._serialized_start=362
+  _globals['_EXECUTEDNODE']._serialized_end=466
+  _globals['_REPORTEXECUTIONREQUEST']._serialized_start=469
+  _globals['_REPORTEXECUTIONREQUEST']._serialized_end=683
+  _globals['_REPORTEXECUTIONRESPONSE']._serialized_start=685
+  _globals['_REPORTEXECUTIONRESPONSE']._serialized_end=710
+  _globals['_PGLREMOTE']._serialized_start=713
+  _globals['_PGLREMOTE']._serialized_end=979
 # @@protoc_insertion_point(module_scope)
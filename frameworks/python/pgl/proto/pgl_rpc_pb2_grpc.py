@@ -27,6 +27,12 @@ class PglRemoteStub(object):
             response_deserializer=pgl__rpc__pb2.CardinalityEstimateResponse.FromString,
             _registered_method=True,
         )
+        self.ReportExecution = channel.unary_unary(
+            "/pgl_rpc.PglRemote/ReportExecution",
+            request_serializer=pgl__rpc__pb2.ReportExecutionRequest.SerializeToString,
+            response_deserializer=pgl__rpc__pb2.ReportExecutionResponse.FromString,
+            _registered_method=True,
+        )
 
 
 class PglRemoteServicer(object):
@@ -44,6 +50,12 @@ class PglRemoteServicer(object):
         context.set_details("Method not implemented!")
         raise NotImplementedError("Method not implemented!")
 
+    def ReportExecution(self, request, context):
+        """Missing associated documentation comment in .proto file."""
+        context.set_code(grpc.StatusCode.UNIMPLEMENTED)
+        context.set_details("Method not implemented!")
+        raise NotImplementedError("Method not implemented!")
+
 
 def add_PglRemoteServicer_to_server(servicer, server):
     rpc_method_handlers = {
@@ -57,6 +69,11 @@ def add_PglRemoteServicer_to_server(servicer, server):
             request_deserializer=pgl__rpc__pb2.CardinalityEstimateRequest.FromString,
             response_serializer=pgl__rpc__pb2.CardinalityEstimateResponse.SerializeToString,
         ),
+        "ReportExecution": grpc.unary_unary_rpc_method_handler(
+            servicer.ReportExecution,
+            request_deserializer=pgl__rpc__pb2.ReportExecutionRequest.FromString,
+            response_serializer=pgl__rpc__pb2.ReportExecutionResponse.SerializeToString,
+        ),
     }
     generic_handler = grpc.method_handlers_generic_handler(
         "pgl_rpc.PglRemote", rpc_method_handlers
@@ -128,3 +145,33 @@ class PglRemote(object):
             metadata,
             _registered_method=True,
         )
+
+    @staticmethod
+    def ReportExecution(
+        request,
+        target,
+        options=(),
+        channel_credentials=None,
+        call_credentials=None,
+        insecure=False,
+        compression=None,
+        wait_for_ready=None,
+        timeout=None,
+        metadata=None,
+    ):
+        return grpc.experimental.unary_unary(
+            request,
+            target,
+            "/pgl_rpc.PglRemote/ReportExecution",
+            pgl__rpc__pb2.ReportExecutionRequest.SerializeToString,
+            pgl__rpc__pb2.ReportExecutionResponse.FromString,
+            options,
+            channel_credentials,
+            insecure,
+            call_credentials,
+            compression,
+            wait_for_ready,
+            timeout,
+            metadata,
+            _registered_method=True,
+        )
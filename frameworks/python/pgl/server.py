@@ -1,6 +1,7 @@
 import json
 import logging
 from concurrent import futures
+from typing import Optional
 
 import grpc
 from grpc_reflection.v1alpha import reflection
@@ -49,8 +50,18 @@ class PglRemoteAdapter(pgl_rpc_pb2_grpc.PglRemoteServicer):
                 logger.error(f"Failed to parse plan at index {i}: {e}")
                 parsed_plans.append({})
 
+        features = None
+        if request.features:
+            features = []
+            for i, features_str in enumerate(request.features):
+                try:
+                    features.append(json.loads(features_str) if features_str else None)
+                except json.JSONDecodeError as e:
+                    logger.error(f"Failed to parse features of plan {i}: {e}")
+                    features.append(None)
+
         try:
-            chosen_index = self.adapter.choose_plan(parsed_plans)
+            chosen_index = self.adapter.choose_plan_with_features(parsed_plans, features)
         except Exception as e:
             logger.exception("Error in user adapter logic")
             context.abort(grpc.StatusCode.INTERNAL, f"Adapter error: {str(e)}")
@@ -80,9 +91,61 @@ class PglRemoteAdapter(pgl_rpc_pb2_grpc.PglRemoteServicer):
             cardinality_estimates=cardinality_estimates
         )
 
+    def ReportExecution(self, request, context):
+        plan = None
+        if request.plan:
+            try:
+                plan = json.loads(request.plan)
+            except json.JSONDecodeError as e:
+                logger.error(f"Failed to parse executed plan: {e}")
+
+        execution = {
+            "query_id": request.query_id,
+            "plan": plan,
+            "plan_fingerprint": request.plan_fingerprint,
+            "arm": request.arm,
+            "planning_ms": request.planning_ms,
+            "execution_ms": request.execution_ms,
+            "nodes": [
+                {
+                    "node_type": node.node_type,
+                    "relids": list(node.relids),
+                    "plan_rows": node.plan_rows,
+                    "actual_rows": node.actual_rows,
+                    "loops": node.loops,
+                }
+                for node in request.nodes
+            ],
+            "model": (
+                {"name": request.model.name, "version": request.model.version}
+                if request.HasField("model")
+                else None
+            ),
+        }
+
+        # Reports are best-effort; a failing callback is only logged.
+        try:
+            self.adapter.on_execution(execution)
+        except Exception:
+            logger.exception("Error in user adapter on_execution")
+
+        return pgl_rpc_pb2.ReportExecutionResponse()
+
+
+def _read(path: str) -> bytes:
+    with open(path, "rb") as f:
+        return f.read()
+
 
 def run_server(
-    adapter: PglAdapter, host: str = "0.0.0.0", port: int = 50051, max_workers: int = 10
+    adapter: PglAdapter,
+    host: str = "0.0.0.0",
+    port: int = 50051,
+    max_workers: int = 10,
+    address: Optional[str] = None,
+    tls_cert: Optional[str] = None,
+    tls_key: Optional[str] = None,
+    tls_client_ca: Optional[str] = None,
 ):
     """
     Start the gRPC server hosting the given adapter.
@@ -92,6 +155,12 @@ def run_server(
         host: Host to bind to.
         port: Port to bind to.
         max_workers: Number of worker threads for the gRPC server.
+        address: Address to bind to instead of host and port, e.g.
+            "unix:///tmp/pgl.sock" for a Unix-domain socket.
+        tls_cert: PEM server certificate; enables TLS together with tls_key.
+        tls_key: PEM private key of tls_cert.
+        tls_client_ca: PEM CA certificate; when set, clients must present a
+            certificate signed by it.
     """
     server = grpc.server(futures.ThreadPoolExecutor(max_workers=max_workers))
     servicer = PglRemoteAdapter(adapter)
@@ -107,8 +176,20 @@ def run_server(
     )
     reflection.enable_server_reflection(SERVICE_NAMES, server)
 
-    address = f"{host}:{port}"
-    server.add_insecure_port(address)
+    if address is None:
+        address = f"{host}:{port}"
+
+    if tls_cert or tls_key:
+        if not (tls_cert and tls_key):
+            raise ValueError("tls_cert and tls_key must be given together")
+        credentials = grpc.ssl_server_credentials(
+            [(_read(tls_key), _read(tls_cert))],
+            root_certificates=_read(tls_client_ca) if tls_client_ca else None,
+            require_client_auth=tls_client_ca is not None,
+        )
+        server.add_secure_port(address, credentials)
+    else:
+        server.add_insecure_port(address)
 
     print(f"PglServer starting on {address}...")
     server.start()